@@ -4,12 +4,61 @@
 //! and creating PF8 archives. PF6 archives are read-only and do not use encryption,
 //! while PF8 archives support both reading and writing with encryption capabilities.
 
-use crate::builder::Pf8Builder;
-use crate::callbacks::ArchiveHandler;
-use crate::error::Result;
+use crate::builder::{EstimatedSize, Pf8Builder};
+use crate::callbacks::{ArchiveHandler, ControlAction, NoOpHandler, OperationType, ProgressInfo};
+#[cfg(feature = "encoding")]
+use crate::encoding::NameEncoding;
+use crate::error::{Error, Result};
+use crate::extract::ExtractOptions;
 use crate::reader::Pf8Reader;
+use crate::reader::{ReaderOptions, Strictness};
+use crate::repack;
 use std::ops::{Deref, DerefMut};
 use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Statistics returned by the one-off extraction functions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct UnpackResult {
+    /// Number of files written to the output directory
+    pub files: usize,
+    /// Total number of bytes written
+    pub bytes: u64,
+    /// Number of entries that were not extracted
+    pub skipped: usize,
+}
+
+/// Statistics returned by [`Pf8Archive::extract_all_counting`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ExtractStats {
+    /// Number of files written to the output directory
+    pub files: usize,
+    /// Total number of bytes written
+    pub bytes: u64,
+    /// Wall-clock time the extraction took
+    pub duration: Duration,
+}
+
+/// Statistics returned by the one-off packing functions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PackResult {
+    /// Number of files stored in the archive
+    pub files: usize,
+    /// Total size of the stored file data in bytes
+    pub bytes: u64,
+    /// Size of the written archive file in bytes
+    pub archive_size: u64,
+}
+
+/// How a directory is mapped into the archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DirPolicy {
+    /// Store the directory contents at the archive root (`dir/a.txt` -> `a.txt`)
+    #[default]
+    Contents,
+    /// Keep the directory name as prefix (`dir/a.txt` -> `dir/a.txt`)
+    PreserveName,
+}
 
 /// High-level interface for working with PF6/PF8 archives
 pub struct Pf8Archive {
@@ -18,8 +67,31 @@ pub struct Pf8Archive {
 
 impl Pf8Archive {
     /// Opens an existing PF6/PF8 archive
+    ///
+    /// With the `encoding` feature, names are decoded with
+    /// [`NameEncoding::Auto`], so archives with Shift-JIS names open too.
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let reader = Pf8Reader::open(path)?;
+        Self::open_with_strictness(path, Strictness::default())
+    }
+
+    /// Opens an archive for inspection with [`Strictness::Permissive`]
+    ///
+    /// Entries outside the data region and a wrong entry count do not fail
+    /// the open, so [`Pf8Reader::verify`] can report them.
+    pub fn open_permissive<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_with_strictness(path, Strictness::Permissive)
+    }
+
+    fn open_with_strictness<P: AsRef<Path>>(path: P, strictness: Strictness) -> Result<Self> {
+        let reader = Pf8Reader::open_with(
+            path,
+            ReaderOptions {
+                strictness,
+                #[cfg(feature = "encoding")]
+                name_encoding: NameEncoding::Auto,
+                ..Default::default()
+            },
+        )?;
         Ok(Self { reader })
     }
 
@@ -63,6 +135,57 @@ impl Pf8Archive {
             .extract_file_with_progress(archive_path, output_path, handler)
     }
 
+    /// Extracts all files and returns what was written
+    ///
+    /// Counts are collected by an internal progress handler, so callers that
+    /// only need totals do not have to implement [`ArchiveHandler`].
+    pub fn extract_all_counting<P: AsRef<Path>>(&mut self, output_dir: P) -> Result<ExtractStats> {
+        let started = Instant::now();
+        let mut inner = NoOpHandler;
+        let mut handler = CountingHandler::new(&mut inner);
+        self.reader
+            .extract_all_with_progress(output_dir, &mut handler)?;
+
+        Ok(ExtractStats {
+            files: handler.files,
+            bytes: handler.bytes,
+            duration: started.elapsed(),
+        })
+    }
+
+    /// Writes a copy of the archive without the named entries
+    ///
+    /// Names are matched like [`Pf8Reader::get_entry`]; every name must
+    /// exist, otherwise [`Error::FileNotFound`] is returned and nothing is
+    /// written. The remaining entries are streamed into a fresh archive
+    /// with a rebuilt index, new offsets and the key derived from it, in
+    /// the same format and name encoding and with their encryption kept.
+    /// `output_path` may be the file this archive was opened from.
+    pub fn remove_entries<P: AsRef<Path>, Q: AsRef<Path>>(
+        &mut self,
+        paths: &[P],
+        output_path: Q,
+    ) -> Result<PackResult> {
+        repack::remove_entries(&mut self.reader, paths, output_path.as_ref())
+    }
+
+    /// Writes a copy of the archive with the files under `dir` applied
+    ///
+    /// Existing entries whose path relative to `dir` matches a file are
+    /// replaced by it, other files are added, and all remaining entries are
+    /// kept. The archive is rewritten in full, with new offsets and key, in
+    /// the same format and name encoding. Kept and replaced entries keep
+    /// their encryption; new entries follow the patterns the archive was
+    /// opened with. `output_path` may be the file this archive was opened
+    /// from.
+    pub fn update_from_dir<P: AsRef<Path>, Q: AsRef<Path>>(
+        &mut self,
+        dir: P,
+        output_path: Q,
+    ) -> Result<PackResult> {
+        repack::update_from_dir(&mut self.reader, dir.as_ref(), output_path.as_ref())
+    }
+
     /// Gets the underlying reader (for advanced use cases)
     pub fn reader(&self) -> &Pf8Reader {
         &self.reader
@@ -74,6 +197,149 @@ impl Pf8Archive {
     }
 }
 
+/// Tallies finished entries and written bytes, forwarding every event
+struct CountingHandler<'a, H: ArchiveHandler> {
+    inner: &'a mut H,
+    files: usize,
+    bytes: u64,
+}
+
+impl<'a, H: ArchiveHandler> CountingHandler<'a, H> {
+    fn new(inner: &'a mut H) -> Self {
+        Self {
+            inner,
+            files: 0,
+            bytes: 0,
+        }
+    }
+}
+
+impl<H: ArchiveHandler> ArchiveHandler for CountingHandler<'_, H> {
+    fn on_started(&mut self, op_type: OperationType) -> ControlAction {
+        self.inner.on_started(op_type)
+    }
+
+    fn on_entry_started(&mut self, name: &str) -> ControlAction {
+        self.inner.on_entry_started(name)
+    }
+
+    fn on_progress(&mut self, info: &ProgressInfo) -> ControlAction {
+        self.bytes = info.processed_bytes;
+        self.inner.on_progress(info)
+    }
+
+    fn on_entry_finished(&mut self, name: &str) -> ControlAction {
+        self.files += 1;
+        self.inner.on_entry_finished(name)
+    }
+
+    fn on_warning(&mut self, message: &str) -> ControlAction {
+        self.inner.on_warning(message)
+    }
+
+    fn on_finished(&mut self) -> ControlAction {
+        self.inner.on_finished()
+    }
+}
+
+/// Handler accumulating statistics across several extractions
+///
+/// Pass the same handler to the extraction of every archive in a batch;
+/// files and bytes add up across archives, and the duration runs from
+/// [`AggregatingHandler::new`]. Every event is also forwarded to `inner`.
+pub struct AggregatingHandler<H: ArchiveHandler = NoOpHandler> {
+    inner: H,
+    started: Instant,
+    archives: usize,
+    files: usize,
+    /// Bytes written by archives that already finished
+    finished_bytes: u64,
+    /// Bytes written so far by the archive in progress
+    current_bytes: u64,
+}
+
+impl<H: ArchiveHandler> AggregatingHandler<H> {
+    /// Creates a handler forwarding events to `inner`
+    pub fn new(inner: H) -> Self {
+        Self {
+            inner,
+            started: Instant::now(),
+            archives: 0,
+            files: 0,
+            finished_bytes: 0,
+            current_bytes: 0,
+        }
+    }
+
+    /// Returns the combined statistics of all extractions so far
+    pub fn stats(&self) -> ExtractStats {
+        ExtractStats {
+            files: self.files,
+            bytes: self.finished_bytes + self.current_bytes,
+            duration: self.started.elapsed(),
+        }
+    }
+
+    /// Returns the number of extractions started with this handler
+    pub fn archives(&self) -> usize {
+        self.archives
+    }
+
+    /// Gets the wrapped handler
+    pub fn inner(&self) -> &H {
+        &self.inner
+    }
+
+    /// Gets the wrapped handler mutably
+    pub fn inner_mut(&mut self) -> &mut H {
+        &mut self.inner
+    }
+}
+
+impl Default for AggregatingHandler {
+    fn default() -> Self {
+        Self::new(NoOpHandler)
+    }
+}
+
+impl<H: ArchiveHandler> ArchiveHandler for AggregatingHandler<H> {
+    fn on_started(&mut self, op_type: OperationType) -> ControlAction {
+        self.archives += 1;
+        self.finished_bytes += self.current_bytes;
+        self.current_bytes = 0;
+        self.inner.on_started(op_type)
+    }
+
+    fn on_entry_started(&mut self, name: &str) -> ControlAction {
+        self.inner.on_entry_started(name)
+    }
+
+    fn on_progress(&mut self, info: &ProgressInfo) -> ControlAction {
+        self.current_bytes = info.processed_bytes;
+        self.inner.on_progress(info)
+    }
+
+    fn on_entry_finished(&mut self, name: &str) -> ControlAction {
+        self.files += 1;
+        self.inner.on_entry_finished(name)
+    }
+
+    fn on_warning(&mut self, message: &str) -> ControlAction {
+        self.inner.on_warning(message)
+    }
+
+    fn on_finished(&mut self) -> ControlAction {
+        self.inner.on_finished()
+    }
+}
+
+impl From<Pf8Reader> for Pf8Archive {
+    /// Wraps a reader opened with custom [`ReaderOptions`]
+    fn from(reader: Pf8Reader) -> Self {
+        Self { reader }
+    }
+}
+
 impl Deref for Pf8Archive {
     type Target = Pf8Reader;
 
@@ -91,9 +357,12 @@ impl DerefMut for Pf8Archive {
 // Convenience functions for one-off operations
 
 /// Extracts a PF8 archive to the specified directory
-pub fn extract<P: AsRef<Path>, Q: AsRef<Path>>(archive_path: P, output_dir: Q) -> Result<()> {
-    let mut archive = Pf8Archive::open(archive_path)?;
-    archive.extract_all(output_dir)
+pub fn extract<P: AsRef<Path>, Q: AsRef<Path>>(
+    archive_path: P,
+    output_dir: Q,
+) -> Result<UnpackResult> {
+    let mut handler = NoOpHandler;
+    extract_with_progress(archive_path, output_dir, &mut handler)
 }
 
 /// Extracts a PF8 archive to the specified directory with progress reporting
@@ -101,16 +370,62 @@ pub fn extract_with_progress<P: AsRef<Path>, Q: AsRef<Path>, H: ArchiveHandler>(
     archive_path: P,
     output_dir: Q,
     handler: &mut H,
-) -> Result<()> {
+) -> Result<UnpackResult> {
     let mut archive = Pf8Archive::open(archive_path)?;
-    archive.extract_all_with_progress(output_dir, handler)
+    let mut counting = CountingHandler::new(handler);
+    archive.extract_all_with_progress(output_dir, &mut counting)?;
+
+    Ok(UnpackResult {
+        files: counting.files,
+        bytes: counting.bytes,
+        skipped: 0,
+    })
 }
 
-/// Creates a PF8 archive from a directory
-pub fn create_from_dir<P: AsRef<Path>, Q: AsRef<Path>>(input_dir: P, output_path: Q) -> Result<()> {
+/// Extracts the entries of an archive selected by `options`
+///
+/// This is the single configurable entry point shared by the CLI and library
+/// users; see [`ExtractOptions`] for the available filters.
+pub fn extract_with_options<P: AsRef<Path>, Q: AsRef<Path>>(
+    archive_path: P,
+    output_dir: Q,
+    options: &ExtractOptions,
+) -> Result<UnpackResult> {
+    let archive = Pf8Archive::open(archive_path)?;
+    archive.extract_with_options(output_dir, options, &mut NoOpHandler)
+}
+
+/// Projects the size of the archive that packing a directory would produce
+///
+/// Walks the directory like [`Pf8Builder::add_dir`] and sums the index and
+/// data sizes without writing anything.
+pub fn estimate_archive_size<P: AsRef<Path>>(dir: P, policy: DirPolicy) -> Result<EstimatedSize> {
+    let dir = dir.as_ref();
     let mut builder = Pf8Builder::new();
-    builder.add_dir(input_dir)?;
-    builder.write_to_file(output_path)
+
+    match policy {
+        DirPolicy::Contents => builder.add_dir(dir)?,
+        DirPolicy::PreserveName => {
+            let dir_name = dir.file_name().ok_or_else(|| {
+                Error::InvalidFormat(format!(
+                    "Cannot determine directory name: {}",
+                    dir.display()
+                ))
+            })?;
+            builder.add_dir_as(dir, dir_name)?
+        }
+    };
+
+    builder.estimated_size()
+}
+
+/// Creates a PF8 archive from a directory
+pub fn create_from_dir<P: AsRef<Path>, Q: AsRef<Path>>(
+    input_dir: P,
+    output_path: Q,
+) -> Result<PackResult> {
+    let mut handler = NoOpHandler;
+    create_from_dir_with_progress(input_dir, output_path, &mut handler)
 }
 
 /// Creates a PF8 archive from a directory with progress callback
@@ -118,8 +433,9 @@ pub fn create_from_dir_with_progress<P: AsRef<Path>, Q: AsRef<Path>, H: ArchiveH
     input_dir: P,
     output_path: Q,
     handler: &mut H,
-) -> Result<()> {
+) -> Result<PackResult> {
     let mut builder = Pf8Builder::new();
     builder.add_dir(input_dir)?;
-    builder.write_to_file_with_progress(output_path, handler)
+    let (result, _) = builder.write_file_counted(output_path.as_ref(), false, handler)?;
+    Ok(result)
 }
@@ -1,19 +1,184 @@
 //! Builder for creating PF8 archives.
 
-use crate::callbacks::{ArchiveHandler, ControlAction, OperationType};
+use crate::archive::PackResult;
+use crate::callbacks::{
+    ArchiveHandler, ControlAction, NoOpHandler, OperationPhase, OperationType, ProgressInfo,
+};
+use crate::constants::UNENCRYPTED_FILTER;
+use crate::encoding::NameEncoding;
 use crate::entry::Pf8Entry;
 use crate::error::{Error, Result};
+use crate::format::{self, ArchiveFormat};
+use crate::reader::{Pf8Reader, ReaderOptions};
+use crate::utils;
+use crate::volumes::volume_paths;
 use crate::writer::Pf8Writer;
+use glob::{MatchOptions, Pattern};
+use sha1::{Digest, Sha1};
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::HashMap;
 use std::fs;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
+/// Timestamp (seconds since the Unix epoch) used for generated metadata in
+/// reproducible builds
+pub const REPRODUCIBLE_EPOCH: u64 = 0;
+
+/// Archive path of the entry written by [`Pf8Builder::embed_tool_marker`]
+pub const TOOL_MARKER_NAME: &str = ".pfsinfo";
+
+/// Projected layout of an archive, as computed by [`Pf8Builder::estimated_size`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EstimatedSize {
+    /// Number of entries in the index
+    pub entry_count: usize,
+    /// Size of the index region as stored in the `index_size` header field
+    pub index_size: u64,
+    /// Total size of all file data
+    pub data_size: u64,
+    /// Final size of the archive file
+    pub total_size: u64,
+}
+
+/// An entry of the archive a builder would write, as returned by
+/// [`Pf8Builder::plan`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedEntry {
+    /// The index entry, with its final offset, size and encryption
+    pub entry: Pf8Entry,
+    /// File the data is read from, or `None` for data generated by the
+    /// builder such as the tool marker
+    pub source: Option<PathBuf>,
+    /// Whether the data is shared with an earlier entry of identical content
+    pub shared: bool,
+}
+
 /// A builder for creating PF8 archives with a fluent API
 pub struct Pf8Builder {
     /// Files to include in the archive
     files: Vec<(PathBuf, PathBuf)>, // (source_path, archive_path)
     /// Base path for relative file paths
     base_path: Option<PathBuf>,
+    /// Whether adding a directory without files is an error
+    error_on_empty_dir: bool,
+    /// Directories added that contained no files
+    empty_dirs: Vec<PathBuf>,
+    /// Patterns of paths skipped when walking a directory
+    exclude: Vec<Pattern>,
+    /// How the archive is written
+    options: WriteOptions,
+}
+
+/// Options controlling how [`Pf8Builder`] writes an archive
+///
+/// Every field has a matching setter on the builder; use
+/// [`Pf8Builder::write_with`] to apply a complete configuration at once.
+/// With the `json` feature the options can be serialized, so build scripts
+/// can keep them in a file; fields left out take their default.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json", serde(default))]
+pub struct WriteOptions {
+    /// Archive format; PF6 archives are written without encryption
+    pub format: ArchiveFormat,
+    /// Encoding used for entry names in the index
+    pub name_encoding: NameEncoding,
+    /// Name patterns of files stored unencrypted (default `mp4`, `flv`)
+    pub unencrypted_patterns: Vec<String>,
+    /// Extensions of files stored unencrypted, with a leading `.`
+    pub unencrypted_extensions: Vec<String>,
+    /// Order of entries in the index
+    pub entry_order: EntryOrder,
+    /// Order of file data in the archive
+    pub sort_order: SortOrder,
+    /// Whether unreadable source files are skipped with a warning
+    pub skip_unreadable: bool,
+    /// Whether generated metadata must be independent of time and environment
+    pub reproducible: bool,
+    /// Whether a `.pfsinfo` entry naming the producing tool is added
+    pub embed_tool_marker: bool,
+    /// Whether archive paths are lowercased when the layout is planned
+    pub case_fold_paths: bool,
+    /// Largest source file, in bytes, included in the archive
+    pub max_file_size: Option<u64>,
+    /// Largest archive file, in bytes, the builder may produce
+    pub max_archive_size: Option<u64>,
+    /// Whether the archive is written to a temp file and renamed into place
+    pub atomic: bool,
+    /// Whether the archive is flushed to disk before writing returns
+    pub sync: bool,
+    /// Whether entries with identical content share one copy of the data
+    pub dedup: bool,
+    /// Size in bytes of the buffer used to stream file data
+    ///
+    /// `None` keeps the size the [`Pf8Writer`] was configured with (default
+    /// 4 MiB), see [`Pf8Writer::set_buffer_size`].
+    pub buffer_size: Option<usize>,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self {
+            format: ArchiveFormat::Pf8,
+            name_encoding: NameEncoding::default(),
+            unencrypted_patterns: UNENCRYPTED_FILTER.iter().map(|p| p.to_string()).collect(),
+            unencrypted_extensions: Vec::new(),
+            entry_order: EntryOrder::default(),
+            sort_order: SortOrder::default(),
+            skip_unreadable: false,
+            reproducible: false,
+            embed_tool_marker: false,
+            case_fold_paths: false,
+            max_file_size: None,
+            max_archive_size: None,
+            atomic: false,
+            sync: false,
+            dedup: false,
+            buffer_size: None,
+        }
+    }
+}
+
+/// Order in which [`Pf8Builder`] lists entries in the index
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
+pub enum EntryOrder {
+    /// Sorted by archive path
+    #[default]
+    ByPath,
+    /// In the order files were added; files of a directory follow the order
+    /// the directory walk returned them in
+    Insertion,
+}
+
+/// Order in which file data is laid out by [`Pf8Builder`]
+///
+/// The index order is set by [`EntryOrder`]; this only decides the order of
+/// the data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
+pub enum SortOrder {
+    /// Same order as the index
+    #[default]
+    Lexicographic,
+    /// Smallest files first; files of equal size keep index order
+    SizeAscending,
+    /// Largest files first; files of equal size keep index order
+    SizeDescending,
+}
+
+/// Where the data of a planned entry comes from
+enum EntrySource {
+    /// A file on disk
+    File(PathBuf),
+    /// Data generated by the builder
+    Generated(Vec<u8>),
+    /// Data already written for another entry with identical content; holds
+    /// where the data of this entry would have come from
+    Shared(Box<EntrySource>),
 }
 
 impl Pf8Builder {
@@ -22,6 +187,10 @@ impl Pf8Builder {
         Self {
             files: Vec::new(),
             base_path: None,
+            error_on_empty_dir: false,
+            empty_dirs: Vec::new(),
+            exclude: Vec::new(),
+            options: WriteOptions::default(),
         }
     }
 
@@ -31,6 +200,241 @@ impl Pf8Builder {
         self
     }
 
+    /// Sets whether source files that cannot be opened are skipped
+    ///
+    /// When enabled, every source file is probed while the archive layout is
+    /// planned; files that fail to open are reported through
+    /// [`ArchiveHandler::on_warning`] and left out of the archive instead of
+    /// aborting the pack. Errors after the header has been written (e.g. a
+    /// file removed mid-pack) still abort.
+    pub fn skip_unreadable(&mut self, enabled: bool) -> &mut Self {
+        self.options.skip_unreadable = enabled;
+        self
+    }
+
+    /// Sets the archive format (default [`ArchiveFormat::Pf8`])
+    ///
+    /// [`ArchiveFormat::Pf6`] writes the `pf6` magic and stores every entry
+    /// unencrypted, regardless of the unencrypted patterns.
+    pub fn format(&mut self, format: ArchiveFormat) -> &mut Self {
+        self.options.format = format;
+        self
+    }
+
+    /// Sets the encoding used for entry names (default UTF-8)
+    ///
+    /// Legacy games may expect Shift-JIS names. The encoded names determine
+    /// the index size and therefore the encryption key.
+    pub fn name_encoding(&mut self, encoding: NameEncoding) -> &mut Self {
+        self.options.name_encoding = encoding;
+        self
+    }
+
+    /// Makes the build reproducible
+    ///
+    /// The archive itself stores no timestamps and entries are written in
+    /// archive path order by default, so identical inputs already produce
+    /// identical archives; [`EntryOrder::Insertion`] depends on the order of
+    /// directory walks instead. In reproducible mode, any metadata generated by
+    /// the builder additionally uses [`REPRODUCIBLE_EPOCH`] instead of the
+    /// current time and never captures source modification times.
+    pub fn reproducible(&mut self) -> &mut Self {
+        self.options.reproducible = true;
+        self
+    }
+
+    /// Sets whether a tool marker entry is added (default false)
+    ///
+    /// The marker is a small [`TOOL_MARKER_NAME`] entry at the archive root
+    /// holding `key=value` lines with the crate name, its version and the
+    /// creation time in seconds since the Unix epoch ([`REPRODUCIBLE_EPOCH`]
+    /// for reproducible builds). Readers treat it as a sidecar.
+    pub fn embed_tool_marker(&mut self, enabled: bool) -> &mut Self {
+        self.options.embed_tool_marker = enabled;
+        self
+    }
+
+    /// Sets the extensions of files stored unencrypted (default none)
+    ///
+    /// A leading `.` is optional. Readers must be opened with the same
+    /// patterns, see [`Pf8Builder::unencrypted_filter`].
+    pub fn unencrypted_extensions(&mut self, extensions: &[&str]) -> &mut Self {
+        self.options.unencrypted_extensions = extensions
+            .iter()
+            .map(|ext| format!(".{}", ext.trim_start_matches('.')))
+            .collect();
+        self
+    }
+
+    /// Sets the name patterns of files stored unencrypted (default `mp4`, `flv`)
+    ///
+    /// A file is left unencrypted if its archive name contains one of the
+    /// patterns. Readers must be opened with the same patterns, see
+    /// [`Pf8Builder::unencrypted_filter`].
+    pub fn unencrypted_patterns(&mut self, patterns: &[&str]) -> &mut Self {
+        self.options.unencrypted_patterns = patterns.iter().map(|p| p.to_string()).collect();
+        self
+    }
+
+    /// Returns every pattern deciding which files are stored unencrypted
+    ///
+    /// Pass this as [`ReaderOptions::unencrypted_patterns`] to read the
+    /// archive back.
+    pub fn unencrypted_filter(&self) -> Vec<String> {
+        self.options
+            .unencrypted_patterns
+            .iter()
+            .chain(&self.options.unencrypted_extensions)
+            .cloned()
+            .collect()
+    }
+
+    /// Sets the order in which entries are listed in the index
+    ///
+    /// With [`SortOrder::Lexicographic`], data follows the same order. Like
+    /// [`Self::sort_order`], this changes the encryption key.
+    pub fn entry_order(&mut self, order: EntryOrder) -> &mut Self {
+        self.options.entry_order = order;
+        self
+    }
+
+    /// Sets whether entries are written in the order files were added
+    /// instead of sorted by path (default false)
+    ///
+    /// Shorthand for [`Self::entry_order`] with [`EntryOrder::Insertion`],
+    /// for reproducing the layout of an original archive.
+    pub fn preserve_order(&mut self, enabled: bool) -> &mut Self {
+        self.entry_order(if enabled {
+            EntryOrder::Insertion
+        } else {
+            EntryOrder::ByPath
+        })
+    }
+
+    /// Sets the order in which file data is laid out
+    ///
+    /// The index is unaffected, but entry offsets change, and with them the
+    /// encryption key, which is derived from the index.
+    pub fn sort_order(&mut self, order: SortOrder) -> &mut Self {
+        self.options.sort_order = order;
+        self
+    }
+
+    /// Sets whether the archive is written to a temp file and renamed into
+    /// place (default false)
+    ///
+    /// See [`Pf8Builder::write_to_file_atomic`].
+    pub fn atomic(&mut self, enabled: bool) -> &mut Self {
+        self.options.atomic = enabled;
+        self
+    }
+
+    /// Sets whether written archives are flushed to disk before returning
+    /// (default false)
+    pub fn sync(&mut self, enabled: bool) -> &mut Self {
+        self.options.sync = enabled;
+        self
+    }
+
+    /// Sets the size in bytes of the buffer used to stream file data
+    /// (default 4 MiB)
+    ///
+    /// Sizes below 4 KiB are rejected, see [`Pf8Writer::set_buffer_size`].
+    pub fn buffer_size(&mut self, size: usize) -> Result<&mut Self> {
+        utils::check_buffer_size(size)?;
+        self.options.buffer_size = Some(size);
+        Ok(self)
+    }
+
+    /// Replaces every write option at once
+    pub fn set_options(&mut self, options: WriteOptions) -> &mut Self {
+        self.options = options;
+        self
+    }
+
+    /// Returns the current write options
+    pub fn options(&self) -> &WriteOptions {
+        &self.options
+    }
+
+    /// Returns true if the build is reproducible
+    pub fn is_reproducible(&self) -> bool {
+        self.options.reproducible
+    }
+
+    /// Caps the size of the archive file
+    ///
+    /// The projected size (header, index and all file data) is checked while
+    /// the layout is planned, so an oversized pack fails before anything is
+    /// written to the output.
+    pub fn max_archive_size(&mut self, bytes: u64) -> &mut Self {
+        self.options.max_archive_size = Some(bytes);
+        self
+    }
+
+    /// Sets whether adding a directory that contains no files is an error
+    ///
+    /// By default such directories are remembered (see
+    /// [`Pf8Builder::empty_dirs`]) and reported through
+    /// [`ArchiveHandler::on_warning`] when the archive is written. When
+    /// enabled, [`Pf8Builder::add_dir`] and [`Pf8Builder::add_dir_as`] fail
+    /// right away instead.
+    pub fn error_on_empty_dir(&mut self, enabled: bool) -> &mut Self {
+        self.error_on_empty_dir = enabled;
+        self
+    }
+
+    /// Skips source files larger than `bytes`
+    ///
+    /// Unlike the 4 GiB format limit, which fails the pack, oversized files
+    /// are left out of the archive and reported through
+    /// [`ArchiveHandler::on_warning`] while the layout is planned. They are
+    /// also excluded from [`Pf8Builder::estimated_size`].
+    pub fn max_file_size(&mut self, bytes: u64) -> &mut Self {
+        self.options.max_file_size = Some(bytes);
+        self
+    }
+
+    /// Sets whether archive paths are lowercased
+    ///
+    /// Source paths differing only in case collide on case-insensitive
+    /// filesystems. When enabled, every archive path is lowercased while the
+    /// layout is planned and paths that become identical are an error, so
+    /// the archive reads the same on case-sensitive loaders. Applies to all
+    /// files, including those added before the call.
+    pub fn case_fold_paths(&mut self, enabled: bool) -> &mut Self {
+        self.options.case_fold_paths = enabled;
+        self
+    }
+
+    /// Sets whether identical file contents are stored once (default false)
+    ///
+    /// Every source file is hashed (SHA-1) while the layout is planned, and
+    /// entries with the same content, size and encryption point at the same
+    /// offset. The index still lists every file; the key is derived from the
+    /// index alone, so shared data decrypts for each entry. Hashing reads
+    /// every source file once more before writing.
+    pub fn dedup(&mut self, enabled: bool) -> &mut Self {
+        self.options.dedup = enabled;
+        self
+    }
+
+    /// Skips paths matching `pattern` when walking directories (repeatable)
+    ///
+    /// Patterns are globs matched against the path relative to the directory
+    /// passed to [`Pf8Builder::add_dir`] or [`Pf8Builder::add_dir_as`], with
+    /// `/` separators. `*` does not cross directory boundaries, `**` does, so
+    /// `**/*.bak` skips backups at any depth. A directory matching a pattern,
+    /// either as `dir` or as `dir/**`, is not descended into. Only
+    /// directories added after the call are filtered; files added one by one
+    /// never are.
+    pub fn exclude(&mut self, pattern: &str) -> Result<&mut Self> {
+        let pattern = Pattern::new(pattern)
+            .map_err(|err| Error::InvalidFormat(format!("Invalid pattern {pattern:?}: {err}")))?;
+        self.exclude.push(pattern);
+        Ok(self)
+    }
+
     /// Adds a single file to the archive
     pub fn add_file<P: AsRef<Path>>(&mut self, file_path: P) -> Result<&mut Self> {
         let file_path = file_path.as_ref();
@@ -116,20 +520,12 @@ impl Pf8Builder {
             )));
         }
 
-        for entry in WalkDir::new(dir_path) {
-            let entry = entry?;
-            let file_path = entry.path();
-
-            if file_path.is_file() {
-                let relative_path = file_path.strip_prefix(dir_path).map_err(|_| {
-                    Error::InvalidFormat("Failed to create relative path".to_string())
-                })?;
-
-                self.files
-                    .push((file_path.to_path_buf(), relative_path.to_path_buf()));
-            }
+        let files_before = self.files.len();
+        for (file_path, relative_path) in self.walk_dir(dir_path)? {
+            self.files.push((file_path, relative_path));
         }
 
+        self.check_dir_added(dir_path, files_before)?;
         Ok(self)
     }
 
@@ -156,7 +552,24 @@ impl Pf8Builder {
             )));
         }
 
-        for entry in WalkDir::new(dir_path) {
+        let files_before = self.files.len();
+        for (file_path, relative_path) in self.walk_dir(dir_path)? {
+            let archive_path = archive_prefix.join(relative_path);
+            self.files.push((file_path, archive_path));
+        }
+
+        self.check_dir_added(dir_path, files_before)?;
+        Ok(self)
+    }
+
+    /// Returns every file under `dir_path` with its path relative to it,
+    /// skipping and pruning paths matched by [`Pf8Builder::exclude`]
+    fn walk_dir(&self, dir_path: &Path) -> Result<Vec<(PathBuf, PathBuf)>> {
+        let mut files = Vec::new();
+        let walker = WalkDir::new(dir_path)
+            .into_iter()
+            .filter_entry(|entry| entry.depth() == 0 || !self.is_excluded(dir_path, entry));
+        for entry in walker {
             let entry = entry?;
             let file_path = entry.path();
 
@@ -165,18 +578,98 @@ impl Pf8Builder {
                     Error::InvalidFormat("Failed to create relative path".to_string())
                 })?;
 
-                let archive_path = archive_prefix.join(relative_path);
-                self.files.push((file_path.to_path_buf(), archive_path));
+                files.push((file_path.to_path_buf(), relative_path.to_path_buf()));
             }
         }
+        Ok(files)
+    }
 
+    /// Returns true if a walked path matches an exclude pattern
+    ///
+    /// Directories are also tested with a trailing `/`, so `dir/**` prunes
+    /// `dir` itself.
+    fn is_excluded(&self, dir_path: &Path, entry: &walkdir::DirEntry) -> bool {
+        let Ok(relative_path) = entry.path().strip_prefix(dir_path) else {
+            return false;
+        };
+        let relative_path = utils::normalize_lookup_path(relative_path);
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+        self.exclude.iter().any(|pattern| {
+            pattern.matches_with(&relative_path, options)
+                || (entry.file_type().is_dir()
+                    && pattern.matches_with(&format!("{relative_path}/"), options))
+        })
+    }
+
+    /// Records `dir_path` as empty, or fails, if walking it added no files
+    fn check_dir_added(&mut self, dir_path: &Path, files_before: usize) -> Result<()> {
+        if self.files.len() > files_before {
+            return Ok(());
+        }
+        if self.error_on_empty_dir {
+            return Err(Error::InvalidFormat(format!(
+                "Directory contains no files: {}",
+                dir_path.display()
+            )));
+        }
+        self.empty_dirs.push(dir_path.to_path_buf());
+        Ok(())
+    }
+
+    /// Sets the base path, taking the builder by value for chaining
+    pub fn with_base_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.base_path(path);
+        self
+    }
+
+    /// Adds a single file, taking the builder by value for chaining
+    pub fn with_file<P: AsRef<Path>>(mut self, file_path: P) -> Result<Self> {
+        self.add_file(file_path)?;
         Ok(self)
     }
 
+    /// Adds a single file with a custom archive path, taking the builder by value for chaining
+    pub fn with_file_as<P: AsRef<Path>, Q: AsRef<Path>>(
+        mut self,
+        file_path: P,
+        archive_path: Q,
+    ) -> Result<Self> {
+        self.add_file_as(file_path, archive_path)?;
+        Ok(self)
+    }
+
+    /// Adds all files from a directory, taking the builder by value for chaining
+    pub fn with_dir<P: AsRef<Path>>(mut self, dir_path: P) -> Result<Self> {
+        self.add_dir(dir_path)?;
+        Ok(self)
+    }
+
+    /// Adds files from a directory with a custom archive prefix, taking the builder by value for chaining
+    pub fn with_dir_as<P: AsRef<Path>, Q: AsRef<Path>>(
+        mut self,
+        dir_path: P,
+        archive_prefix: Q,
+    ) -> Result<Self> {
+        self.add_dir_as(dir_path, archive_prefix)?;
+        Ok(self)
+    }
+
+    /// Writes the archive to a file, consuming the builder
+    ///
+    /// Ends a chain of `with_*` calls:
+    /// `Pf8Builder::new().with_dir("game")?.build_to("game.pfs")?`.
+    pub fn build_to<P: AsRef<Path>>(self, output_path: P) -> Result<()> {
+        self.write_to_file(output_path)
+    }
+
     /// Writes the archive to a file
+    ///
+    /// Honors [`WriteOptions::atomic`] and [`WriteOptions::sync`].
     pub fn write_to_file<P: AsRef<Path>>(&self, output_path: P) -> Result<()> {
-        let mut writer = Pf8Writer::create(output_path)?;
-        self.write_to_writer(&mut writer)
+        self.write_to_file_with_progress(output_path, &mut NoOpHandler)
     }
 
     /// Writes the archive to a file with progress callback
@@ -185,91 +678,530 @@ impl Pf8Builder {
         output_path: P,
         handler: &mut H,
     ) -> Result<()> {
-        let mut writer = Pf8Writer::create(output_path)?;
-        self.write_to_writer_with_progress(&mut writer, handler)
+        self.write_file_counted(output_path.as_ref(), false, handler)
+            .map(|_| ())
     }
 
-    /// Returns sorted file indices
-    fn sorted_indices(&self) -> Vec<usize> {
-        let mut indices: Vec<_> = (0..self.files.len()).collect();
-        indices.sort_by(|&a, &b| self.files[a].1.cmp(&self.files[b].1));
-        indices
+    /// Writes the archive to a file, honoring [`WriteOptions::atomic`] and
+    /// [`WriteOptions::sync`]
+    ///
+    /// Returns what was written and, if `hashed`, the SHA1 of the archive.
+    pub(crate) fn write_file_counted<H: ArchiveHandler>(
+        &self,
+        output_path: &Path,
+        hashed: bool,
+        handler: &mut H,
+    ) -> Result<(PackResult, Option<[u8; 20]>)> {
+        if self.options.atomic {
+            self.write_file_atomic(output_path, hashed, handler)
+        } else {
+            self.write_file_in_place(output_path, hashed, handler)
+        }
     }
 
-    /// Writes the archive using the provided writer
+    /// Writes the archive to a file using `options` instead of the
+    /// builder's own configuration
     ///
-    /// This method uses streaming I/O to minimize memory usage during the packing process.
-    /// Files are read and written in chunks rather than loading entire files into memory.
-    pub fn write_to_writer(&self, writer: &mut Pf8Writer) -> Result<()> {
+    /// The files added to the builder are packed as usual; the builder's
+    /// options are left unchanged.
+    pub fn write_with<P: AsRef<Path>>(&self, options: WriteOptions, output_path: P) -> Result<()> {
+        let builder = Self {
+            files: self.files.clone(),
+            base_path: self.base_path.clone(),
+            error_on_empty_dir: self.error_on_empty_dir,
+            empty_dirs: self.empty_dirs.clone(),
+            exclude: self.exclude.clone(),
+            options,
+        };
+        builder.write_to_file(output_path)
+    }
+
+    /// Writes the files as a series of volumes of about `volume_size` bytes
+    ///
+    /// The first volume is written to `output_base` (e.g. `root.pfs`), the
+    /// following ones to `root.pfs.000`, `root.pfs.001` and so on, the naming
+    /// the game loads patch archives by. Every volume is a complete archive:
+    /// its own index lists only its files, offsets are relative to the start
+    /// of that volume and each volume has its own key, so volumes can be read
+    /// and extracted on their own.
+    ///
+    /// Files are assigned to volumes in index order (see [`EntryOrder`]) and
+    /// never split. A volume is closed once the next file would take it over
+    /// `volume_size`; a file larger than `volume_size` gets a volume of its
+    /// own, which then exceeds the limit. Each volume is written like
+    /// [`Self::write_to_file`] with the builder's options. Numbered volumes
+    /// of `output_base` beyond the last one written, left over from an
+    /// earlier and larger split, are removed so they are not loaded along
+    /// with the new ones.
+    ///
+    /// Returns the paths of the written volumes in order.
+    pub fn write_split<P: AsRef<Path>>(
+        &self,
+        output_base: P,
+        volume_size: u64,
+    ) -> Result<Vec<PathBuf>> {
+        if volume_size == 0 {
+            return Err(Error::InvalidFormat(
+                "Volume size must be non-zero".to_string(),
+            ));
+        }
         if self.files.is_empty() {
+            return Err(self.no_files_error());
+        }
+
+        let archive_paths = self.archive_paths()?;
+        let entry_cost = |name_len: usize| {
+            (format::index_size_for([name_len]) - format::index_size_for([])) as u64
+        };
+        let mut base_cost =
+            format::offsets::INDEX_DATA_START as u64 + format::index_size_for([]) as u64;
+        if self.options.embed_tool_marker {
+            base_cost += self.tool_marker().len() as u64
+                + entry_cost(self.encoded_name_len(Path::new(TOOL_MARKER_NAME))?);
+        }
+
+        let mut volumes: Vec<Vec<usize>> = Vec::new();
+        let mut current: Vec<usize> = Vec::new();
+        let mut current_size = base_cost;
+        for i in self.ordered_indices(&archive_paths) {
+            let size = fs::metadata(&self.files[i].0)
+                .map_err(|err| with_source_path(err.into(), &self.files[i].0))?
+                .len();
+            if self.exceeds_max_file_size(size) {
+                continue;
+            }
+            let cost = size + entry_cost(self.encoded_name_len(&archive_paths[i])?);
+            if !current.is_empty() && current_size + cost > volume_size {
+                volumes.push(std::mem::take(&mut current));
+                current_size = base_cost;
+            }
+            current.push(i);
+            current_size += cost;
+        }
+        if current.is_empty() {
             return Err(Error::InvalidFormat("No files to archive".to_string()));
         }
+        volumes.push(current);
 
-        // Build entries with metadata
-        let mut entries = Vec::new();
-        let mut total_data_size = 0u32;
+        let output_base = output_base.as_ref();
+        let mut written = Vec::with_capacity(volumes.len());
+        for (number, indices) in volumes.into_iter().enumerate() {
+            let path = match number {
+                0 => output_base.to_path_buf(),
+                _ => {
+                    let mut name = output_base.as_os_str().to_os_string();
+                    name.push(format!(".{:03}", number - 1));
+                    PathBuf::from(name)
+                }
+            };
+            let volume = Self {
+                files: indices.iter().map(|&i| self.files[i].clone()).collect(),
+                base_path: self.base_path.clone(),
+                error_on_empty_dir: self.error_on_empty_dir,
+                empty_dirs: Vec::new(),
+                exclude: self.exclude.clone(),
+                options: self.options.clone(),
+            };
+            volume.write_to_file(&path)?;
+            written.push(path);
+        }
+        for stale in volume_paths(output_base).into_iter().skip(written.len()) {
+            fs::remove_file(&stale)?;
+        }
+        Ok(written)
+    }
 
-        // Sort files by archive path index
-        let indices = self.sorted_indices();
+    /// Creates `output_path` and writes the archive directly into it
+    fn write_file_in_place<H: ArchiveHandler>(
+        &self,
+        output_path: &Path,
+        hashed: bool,
+        handler: &mut H,
+    ) -> Result<(PackResult, Option<[u8; 20]>)> {
+        let mut writer = Pf8Writer::create(output_path)?;
+        if hashed {
+            writer.enable_hashing()?;
+        }
+        let (files, bytes) = self.write_entries(&mut writer, handler)?;
+        if self.options.sync {
+            writer.sync_all()?;
+        }
+        let result = PackResult {
+            files,
+            bytes,
+            archive_size: writer.size() as u64,
+        };
+        Ok((result, writer.archive_hash()))
+    }
 
-        for &i in &indices {
-            let (source_path, archive_path) = &self.files[i];
-            let metadata = fs::metadata(source_path)?;
-            let size = metadata.len();
+    /// Writes the archive to a sibling temp file, then renames it into place
+    ///
+    /// The temp file is `<path>.tmp` in the same directory, so the rename stays
+    /// on one filesystem. If packing fails the temp file is removed and
+    /// `output_path` is left untouched.
+    pub fn write_to_file_atomic<P: AsRef<Path>>(&self, output_path: P) -> Result<()> {
+        self.write_to_file_atomic_with_progress(output_path, &mut NoOpHandler)
+    }
 
-            if size > u32::MAX as u64 {
+    /// Writes the archive atomically with progress callback
+    ///
+    /// See [`Self::write_to_file_atomic`].
+    pub fn write_to_file_atomic_with_progress<P: AsRef<Path>, H: ArchiveHandler>(
+        &self,
+        output_path: P,
+        handler: &mut H,
+    ) -> Result<()> {
+        self.write_file_atomic(output_path.as_ref(), false, handler)
+            .map(|_| ())
+    }
+
+    /// Writes the archive through a temp file, see [`Self::write_to_file_atomic`]
+    fn write_file_atomic<H: ArchiveHandler>(
+        &self,
+        output_path: &Path,
+        hashed: bool,
+        handler: &mut H,
+    ) -> Result<(PackResult, Option<[u8; 20]>)> {
+        let mut temp_name = output_path.file_name().unwrap_or_default().to_os_string();
+        temp_name.push(".tmp");
+        let temp_path = output_path.with_file_name(temp_name);
+
+        let result = self.write_file_in_place(&temp_path, hashed, handler);
+        match result.and_then(|written| {
+            std::fs::rename(&temp_path, output_path)?;
+            Ok(written)
+        }) {
+            Ok(written) => Ok(written),
+            Err(e) => {
+                let _ = std::fs::remove_file(&temp_path);
+                Err(e)
+            }
+        }
+    }
+
+    /// Writes the archive to a file and verifies its layout
+    ///
+    /// After writing, the archive is reopened and the `(offset, size)` of every
+    /// parsed entry is compared against the layout planned before writing, so
+    /// any drift in the header arithmetic is reported as an error.
+    pub fn write_to_file_verified<P: AsRef<Path>>(&self, output_path: P) -> Result<()> {
+        let output_path = output_path.as_ref();
+        let planned = self.planned_entries()?;
+        self.write_to_file(output_path)?;
+
+        let options = ReaderOptions {
+            name_encoding: self.options.name_encoding,
+            unencrypted_patterns: Some(self.unencrypted_filter()),
+            ..Default::default()
+        };
+        let reader = Pf8Reader::open_with(output_path, options)?;
+        let written = reader.all_entries().count();
+        if written != planned.len() {
+            return Err(Error::InvalidFormat(format!(
+                "Layout mismatch: planned {} entries, archive has {}",
+                planned.len(),
+                written
+            )));
+        }
+
+        for (planned, written) in planned.iter().zip(reader.all_entries()) {
+            if planned.pf8_path() != written.pf8_path()
+                || planned.offset() != written.offset()
+                || planned.size() != written.size()
+            {
                 return Err(Error::InvalidFormat(format!(
-                    "File too large: {} bytes (max: {} bytes)",
-                    size,
-                    u32::MAX
+                    "Layout mismatch for {}: planned offset {} size {}, archive has {} at offset {} size {}",
+                    planned.pf8_path(),
+                    planned.offset(),
+                    planned.size(),
+                    written.pf8_path(),
+                    written.offset(),
+                    written.size()
                 )));
             }
+        }
 
-            let size = size as u32;
-            let entry = Pf8Entry::new(archive_path, total_data_size, size);
+        Ok(())
+    }
 
-            entries.push((entry, source_path.clone()));
-            total_data_size += size;
+    /// Returns the entries this builder would write, in index order
+    ///
+    /// Offsets are absolute positions of the entry data in the archive file.
+    pub fn planned_entries(&self) -> Result<Vec<Pf8Entry>> {
+        Ok(self
+            .plan_entries(&mut NoOpHandler)?
+            .into_iter()
+            .map(|(entry, _)| entry)
+            .collect())
+    }
+
+    /// Returns the archive path of every file, case-folded if enabled
+    fn archive_paths(&self) -> Result<Vec<Cow<'_, Path>>> {
+        if !self.options.case_fold_paths {
+            return Ok(self
+                .files
+                .iter()
+                .map(|(_, archive_path)| Cow::Borrowed(archive_path.as_path()))
+                .collect());
         }
 
-        // Write header and entries
-        writer.write_header(&entries.iter().map(|(entry, _)| entry).collect::<Vec<_>>())?;
+        let mut seen: HashMap<PathBuf, &Path> = HashMap::new();
+        let mut paths = Vec::with_capacity(self.files.len());
+        for (_, archive_path) in &self.files {
+            let folded = PathBuf::from(archive_path.to_string_lossy().to_lowercase());
+            if let Some(previous) = seen.insert(folded.clone(), archive_path) {
+                return Err(Error::InvalidFormat(format!(
+                    "Archive paths {} and {} collide when case-folded",
+                    previous.display(),
+                    archive_path.display()
+                )));
+            }
+            paths.push(Cow::Owned(folded));
+        }
+        Ok(paths)
+    }
 
-        // Write file data using streaming to minimize memory usage
-        for (entry, source_path) in entries {
-            writer.write_file_data(&entry, &source_path)?;
+    /// Returns file indices in index order, see [`EntryOrder`]
+    fn ordered_indices(&self, archive_paths: &[Cow<'_, Path>]) -> Vec<usize> {
+        let mut indices: Vec<_> = (0..archive_paths.len()).collect();
+        if self.options.entry_order == EntryOrder::ByPath {
+            indices.sort_by(|&a, &b| archive_paths[a].cmp(&archive_paths[b]));
         }
+        indices
+    }
 
-        writer.finalize()?;
-        Ok(())
+    /// Writes the archive using the provided writer
+    ///
+    /// This method uses streaming I/O to minimize memory usage during the packing process.
+    /// Files are read and written in chunks rather than loading entire files into memory.
+    pub fn write_to_writer(&self, writer: &mut Pf8Writer) -> Result<()> {
+        let mut handler = NoOpHandler;
+        self.write_to_writer_with_progress(writer, &mut handler)
     }
 
     /// Writes the archive using the provided writer with progress callback
+    ///
+    /// Progress is reported in two phases: [`OperationPhase::Scanning`] while the
+    /// source files are stat'ed to plan the archive layout, then
+    /// [`OperationPhase::Writing`] while their data is written. While
+    /// writing, an event is sent after each entry and after every
+    /// buffer-sized chunk of larger entries (see [`WriteOptions::buffer_size`]),
+    /// so `processed_bytes` advances within them.
     pub fn write_to_writer_with_progress<H: ArchiveHandler>(
         &self,
         writer: &mut Pf8Writer,
         handler: &mut H,
     ) -> Result<()> {
+        self.write_entries(writer, handler).map(|_| ())
+    }
+
+    /// Streams the archive to any [`Write`] sink, such as stdout or a socket
+    ///
+    /// The output is byte-identical to [`Self::write_to_file`]; entry data
+    /// is copied through in chunks, so memory use does not grow with the
+    /// archive size. [`WriteOptions::atomic`] does not apply to a stream;
+    /// with [`WriteOptions::sync`] the sink is flushed before returning.
+    pub fn write_to_stream<W: Write + Send + 'static>(&self, output: W) -> Result<PackResult> {
+        let mut writer = Pf8Writer::from_writer(output);
+        let (files, bytes) = self.write_entries(&mut writer, &mut NoOpHandler)?;
+        if self.options.sync {
+            writer.sync_all()?;
+        }
+        Ok(PackResult {
+            files,
+            bytes,
+            archive_size: writer.size() as u64,
+        })
+    }
+
+    /// Writes the archive to a file and returns the SHA1 of the written bytes
+    ///
+    /// The hash is computed while writing, so the archive is never read back.
+    /// Honors [`WriteOptions::atomic`] and [`WriteOptions::sync`] like
+    /// [`Self::write_to_file`].
+    pub fn write_to_file_hashed<P: AsRef<Path>>(
+        &self,
+        output_path: P,
+    ) -> Result<(PackResult, [u8; 20])> {
+        let (result, hash) =
+            self.write_file_counted(output_path.as_ref(), true, &mut NoOpHandler)?;
+        let hash =
+            hash.ok_or_else(|| Error::InvalidFormat("Archive hash unavailable".to_string()))?;
+        Ok((result, hash))
+    }
+
+    /// Plans and writes all entries, returning the file count and data size
+    fn write_entries<H: ArchiveHandler>(
+        &self,
+        writer: &mut Pf8Writer,
+        handler: &mut H,
+    ) -> Result<(usize, u64)> {
         if self.files.is_empty() {
-            return Err(Error::InvalidFormat("No files to archive".to_string()));
+            return Err(self.no_files_error());
+        }
+        if let Some(size) = self.options.buffer_size {
+            writer.set_buffer_size(size)?;
         }
+        writer
+            .set_name_encoding(self.options.name_encoding)
+            .set_format(self.options.format);
 
         // Notify start
         if handler.on_started(OperationType::Pack) == ControlAction::Abort {
             return Err(Error::Cancelled);
         }
 
-        // Build entries with metadata
-        let mut entries = Vec::new();
-        let mut total_data_size = 0u32;
+        for dir in &self.empty_dirs {
+            let message = format!("Directory contains no files: {}", dir.display());
+            if handler.on_warning(&message) == ControlAction::Abort {
+                return Err(Error::Cancelled);
+            }
+        }
+
+        let entries = self.plan_entries(handler)?;
+        let total_files = entries.len();
+        let total_bytes: u64 = entries.iter().map(|(entry, _)| entry.size_u64()).sum();
+
+        // Write header and entries
+        writer.write_header_with_offsets(
+            &entries.iter().map(|(entry, _)| entry).collect::<Vec<_>>(),
+        )?;
+
+        // Announce the totals of all inputs before the first entry is written
+        let progress = ProgressInfo {
+            phase: OperationPhase::Writing,
+            processed_bytes: 0,
+            total_bytes: Some(total_bytes),
+            processed_files: 0,
+            total_files: Some(total_files),
+            current_file: String::new(),
+        };
+        if handler.on_progress(&progress) == ControlAction::Abort {
+            return Err(Error::Cancelled);
+        }
+
+        // Write file data using streaming to minimize memory usage with progress callback
+        let mut write_order: Vec<_> = entries.iter().collect();
+        write_order.sort_by_key(|(entry, _)| entry.offset());
+        let mut processed_bytes = 0u64;
+        for (index, (entry, source)) in write_order.into_iter().enumerate() {
+            let archive_path = entry.path().to_string_lossy().to_string();
+
+            if handler.on_entry_started(&archive_path) == ControlAction::Abort {
+                return Err(Error::Cancelled);
+            }
 
-        // Sort files by archive path index
-        let indices = self.sorted_indices();
+            // Report progress within large files after every chunk; the
+            // event for the completed entry follows below
+            let entry_size = entry.size_u64();
+            let on_chunk = |written: u64| {
+                if written == entry_size {
+                    return Ok(());
+                }
+                let progress = ProgressInfo {
+                    phase: OperationPhase::Writing,
+                    processed_bytes: processed_bytes + written,
+                    total_bytes: Some(total_bytes),
+                    processed_files: index,
+                    total_files: Some(total_files),
+                    current_file: archive_path.clone(),
+                };
+                if handler.on_progress(&progress) == ControlAction::Abort {
+                    return Err(Error::Cancelled);
+                }
+                Ok(())
+            };
+            match source {
+                EntrySource::File(source_path) => fs::File::open(source_path)
+                    .map_err(Error::from)
+                    .and_then(|file| writer.write_file_data_chunked(entry, file, on_chunk))
+                    .map_err(|err| with_source_path(err, source_path))?,
+                EntrySource::Generated(data) => {
+                    writer.write_file_data_chunked(entry, data.as_slice(), on_chunk)?
+                }
+                EntrySource::Shared(_) => {}
+            }
+            processed_bytes += entry_size;
+
+            let progress = ProgressInfo {
+                phase: OperationPhase::Writing,
+                processed_bytes,
+                total_bytes: Some(total_bytes),
+                processed_files: index + 1,
+                total_files: Some(total_files),
+                current_file: archive_path.clone(),
+            };
+            if handler.on_progress(&progress) == ControlAction::Abort {
+                return Err(Error::Cancelled);
+            }
+
+            if handler.on_entry_finished(&archive_path) == ControlAction::Abort {
+                return Err(Error::Cancelled);
+            }
+        }
+
+        writer.finalize()?;
+
+        handler.on_finished();
+        Ok((total_files, total_bytes))
+    }
+
+    /// Error for a build without files, naming the empty directories added
+    fn no_files_error(&self) -> Error {
+        if self.empty_dirs.is_empty() {
+            return Error::InvalidFormat("No files to archive".to_string());
+        }
+        let dirs = self
+            .empty_dirs
+            .iter()
+            .map(|dir| dir.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        Error::InvalidFormat(format!("No files to archive (empty directories: {dirs})"))
+    }
 
-        for &i in &indices {
-            let (source_path, archive_path) = &self.files[i];
-            let metadata = fs::metadata(source_path)?;
-            let size = metadata.len();
+    /// Stats all source files and builds the archive entries in write order
+    fn plan_entries<H: ArchiveHandler>(
+        &self,
+        handler: &mut H,
+    ) -> Result<Vec<(Pf8Entry, EntrySource)>> {
+        let total_files = self.files.len();
+        let archive_paths = self.archive_paths()?;
+
+        // First pass: stat files in index order, dropping unreadable ones
+        let mut planned = Vec::new();
+        let mut scanned_bytes = 0u64;
+        for i in self.ordered_indices(&archive_paths) {
+            let (source_path, _) = &self.files[i];
+            let archive_path = &archive_paths[i];
+            let size = match self.probe_source(source_path) {
+                Ok(size) => size,
+                Err(err) if self.options.skip_unreadable => {
+                    let message = format!(
+                        "Skipping unreadable file {}: {}",
+                        source_path.display(),
+                        err
+                    );
+                    if handler.on_warning(&message) == ControlAction::Abort {
+                        return Err(Error::Cancelled);
+                    }
+                    continue;
+                }
+                Err(err) => return Err(with_source_path(err, source_path)),
+            };
+
+            if self.exceeds_max_file_size(size) {
+                let message = format!(
+                    "Skipping {} ({} bytes exceeds the limit of {} bytes)",
+                    source_path.display(),
+                    size,
+                    self.options.max_file_size.unwrap_or_default()
+                );
+                if handler.on_warning(&message) == ControlAction::Abort {
+                    return Err(Error::Cancelled);
+                }
+                continue;
+            }
 
             if size > u32::MAX as u64 {
                 return Err(Error::InvalidFormat(format!(
@@ -279,35 +1211,285 @@ impl Pf8Builder {
                 )));
             }
 
-            let size = size as u32;
-            let entry = Pf8Entry::new(archive_path, total_data_size, size);
+            planned.push((i, size as u32));
+            scanned_bytes += size;
 
-            entries.push((entry, source_path.clone()));
-            total_data_size += size;
+            let progress = ProgressInfo {
+                phase: OperationPhase::Scanning,
+                processed_bytes: scanned_bytes,
+                total_bytes: None,
+                processed_files: planned.len(),
+                total_files: Some(total_files),
+                current_file: archive_path.to_string_lossy().to_string(),
+            };
+            if handler.on_progress(&progress) == ControlAction::Abort {
+                return Err(Error::Cancelled);
+            }
         }
 
-        // Write header and entries
-        writer.write_header(&entries.iter().map(|(entry, _)| entry).collect::<Vec<_>>())?;
+        if planned.is_empty() {
+            return Err(Error::InvalidFormat("No files to archive".to_string()));
+        }
 
-        // Write file data using streaming to minimize memory usage with progress callback
-        for (entry, source_path) in entries {
-            let archive_path = entry.path().to_string_lossy().to_string();
+        let mut planned = planned
+            .into_iter()
+            .map(|(i, size)| {
+                let source = EntrySource::File(self.files[i].0.clone());
+                (archive_paths[i].clone(), size, source)
+            })
+            .collect::<Vec<_>>();
+        if self.options.embed_tool_marker {
+            let marker_path = Path::new(TOOL_MARKER_NAME);
+            let position = match self.options.entry_order {
+                EntryOrder::ByPath => planned
+                    .binary_search_by(|(path, _, _)| path.as_ref().cmp(marker_path))
+                    .err(),
+                EntryOrder::Insertion => (!planned.iter().any(|(path, _, _)| path == marker_path))
+                    .then_some(planned.len()),
+            }
+            .ok_or_else(|| Error::DuplicateEntry(TOOL_MARKER_NAME.to_string()))?;
+            let marker = self.tool_marker();
+            planned.insert(
+                position,
+                (
+                    Cow::Borrowed(marker_path),
+                    marker.len() as u32,
+                    EntrySource::Generated(marker),
+                ),
+            );
+        }
 
-            if handler.on_entry_started(&archive_path) == ControlAction::Abort {
-                return Err(Error::Cancelled);
+        // Second pass: data follows the index directly, in entry order
+        let data_start = format::offsets::INDEX_DATA_START
+            + format::index_size_for(
+                planned
+                    .iter()
+                    .map(|(path, _, _)| self.encoded_name_len(path))
+                    .collect::<Result<Vec<_>>>()?,
+            );
+
+        let unencrypted = self.unencrypted_filter();
+        let encrypted = planned
+            .iter()
+            .map(|(path, size, _)| {
+                self.options.format == ArchiveFormat::Pf8
+                    && Pf8Entry::with_unencrypted_patterns(path, 0, *size, &unencrypted)
+                        .is_encrypted()
+            })
+            .collect::<Vec<_>>();
+
+        // Entries repeating the content of an earlier one, and that entry
+        let duplicates = if self.options.dedup {
+            self.find_duplicates(&planned, &encrypted)?
+        } else {
+            vec![None; planned.len()]
+        };
+
+        if let Some(limit) = self.options.max_archive_size {
+            let projected = data_start as u64
+                + planned
+                    .iter()
+                    .zip(&duplicates)
+                    .filter(|(_, duplicate)| duplicate.is_none())
+                    .map(|(&(_, size, _), _)| size as u64)
+                    .sum::<u64>();
+            if projected > limit {
+                return Err(Error::InvalidFormat(format!(
+                    "Archive would be {} bytes, exceeding the limit of {} bytes",
+                    projected, limit
+                )));
             }
+        }
 
-            writer.write_file_data(&entry, &source_path)?;
+        // The index keeps entry_order; only the data order follows sort_order
+        let mut data_order: Vec<usize> = (0..planned.len()).collect();
+        match self.options.sort_order {
+            SortOrder::Lexicographic => {}
+            SortOrder::SizeAscending => data_order.sort_by_key(|&i| planned[i].1),
+            SortOrder::SizeDescending => data_order.sort_by_key(|&i| Reverse(planned[i].1)),
+        }
 
-            if handler.on_entry_finished(&archive_path) == ControlAction::Abort {
-                return Err(Error::Cancelled);
+        let mut offsets = vec![0u32; planned.len()];
+        let mut total_data_size = 0u32;
+        for i in data_order.iter().copied() {
+            if duplicates[i].is_some() {
+                continue;
+            }
+            let size = planned[i].1;
+            offsets[i] = u32::try_from(data_start + total_data_size as usize)
+                .ok()
+                .filter(|offset| offset.checked_add(size).is_some())
+                .ok_or_else(|| {
+                    Error::InvalidFormat("Archive exceeds the 4 GiB format limit".to_string())
+                })?;
+            total_data_size += size;
+        }
+        for (i, duplicate) in duplicates.iter().enumerate() {
+            if let Some(original) = duplicate {
+                offsets[i] = offsets[*original];
             }
         }
 
-        writer.finalize()?;
+        Ok(planned
+            .into_iter()
+            .zip(offsets)
+            .zip(duplicates)
+            .map(|(((archive_path, size, source), offset), duplicate)| {
+                let entry =
+                    Pf8Entry::with_unencrypted_patterns(&archive_path, offset, size, &unencrypted);
+                let source = match duplicate {
+                    Some(_) => EntrySource::Shared(Box::new(source)),
+                    None => source,
+                };
+                match self.options.format {
+                    ArchiveFormat::Pf6 => (entry.into_unencrypted(), source),
+                    ArchiveFormat::Pf8 => (entry, source),
+                }
+            })
+            .collect())
+    }
 
-        handler.on_finished();
-        Ok(())
+    /// Hashes the planned entries and returns, for each one repeating the
+    /// content of an earlier entry, the index of that entry
+    ///
+    /// Empty entries are never shared. Entries are only shared when both are
+    /// encrypted or both are not, since the stored bytes differ otherwise.
+    fn find_duplicates(
+        &self,
+        planned: &[(Cow<'_, Path>, u32, EntrySource)],
+        encrypted: &[bool],
+    ) -> Result<Vec<Option<usize>>> {
+        let mut seen: HashMap<([u8; 20], u32, bool), usize> = HashMap::new();
+        let mut duplicates = vec![None; planned.len()];
+        for (i, (_, size, source)) in planned.iter().enumerate() {
+            if *size == 0 {
+                continue;
+            }
+            let mut hasher = Sha1::new();
+            match source {
+                EntrySource::File(source_path) => {
+                    let mut file = fs::File::open(source_path)
+                        .map_err(|err| with_source_path(err.into(), source_path))?;
+                    std::io::copy(&mut file, &mut hasher)
+                        .map_err(|err| with_source_path(err.into(), source_path))?;
+                }
+                EntrySource::Generated(data) => hasher.update(data),
+                EntrySource::Shared(_) => continue,
+            }
+            let key = (hasher.finalize().into(), *size, encrypted[i]);
+            if let Some(&original) = seen.get(&key) {
+                duplicates[i] = Some(original);
+            } else {
+                seen.insert(key, i);
+            }
+        }
+        Ok(duplicates)
+    }
+
+    /// Returns the contents of the tool marker entry
+    fn tool_marker(&self) -> Vec<u8> {
+        let created = if self.options.reproducible {
+            REPRODUCIBLE_EPOCH
+        } else {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(REPRODUCIBLE_EPOCH, |elapsed| elapsed.as_secs())
+        };
+        format!(
+            "tool={}\nversion={}\ncreated={}\n",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION"),
+            created
+        )
+        .into_bytes()
+    }
+
+    /// Returns the length of an archive path once encoded for the index
+    fn encoded_name_len(&self, archive_path: &Path) -> Result<usize> {
+        self.options
+            .name_encoding
+            .encoded_len(&utils::pathbuf_to_pf8_path(archive_path))
+    }
+
+    /// Returns true if a source file of `size` bytes is skipped
+    fn exceeds_max_file_size(&self, size: u64) -> bool {
+        self.options.max_file_size.is_some_and(|limit| size > limit)
+    }
+
+    /// Returns the size of a source file, checking that it can be opened
+    /// when unreadable files are skipped
+    fn probe_source(&self, source_path: &Path) -> Result<u64> {
+        let metadata = fs::metadata(source_path)?;
+        if self.options.skip_unreadable {
+            fs::File::open(source_path)?;
+        }
+        Ok(metadata.len())
+    }
+
+    /// Computes the size of the archive this builder would write
+    ///
+    /// Uses the same layout as [`Pf8Writer::write_header`], so the estimate is
+    /// exact: PF8 stores data uncompressed. With [`Pf8Builder::dedup`] it is
+    /// an upper bound, as content is not hashed here.
+    pub fn estimated_size(&self) -> Result<EstimatedSize> {
+        let archive_paths = self.archive_paths()?;
+        let mut data_size = 0u64;
+        let mut name_lengths = Vec::with_capacity(self.files.len());
+        for ((source_path, _), archive_path) in self.files.iter().zip(&archive_paths) {
+            let size = fs::metadata(source_path)?.len();
+            if self.exceeds_max_file_size(size) {
+                continue;
+            }
+            data_size += size;
+            name_lengths.push(self.encoded_name_len(archive_path)?);
+        }
+
+        if self.options.embed_tool_marker {
+            data_size += self.tool_marker().len() as u64;
+            name_lengths.push(self.encoded_name_len(Path::new(TOOL_MARKER_NAME))?);
+        }
+
+        let entry_count = name_lengths.len();
+        let index_size = format::index_size_for(name_lengths) as u64;
+
+        Ok(EstimatedSize {
+            entry_count,
+            index_size,
+            data_size,
+            total_size: format::offsets::INDEX_DATA_START as u64 + index_size + data_size,
+        })
+    }
+
+    /// Resolves the archive this builder would write, without writing it
+    ///
+    /// Source files are stat'ed (and hashed with [`Pf8Builder::dedup`]) but
+    /// no output is created. Entries are returned in index order, with the
+    /// offsets, sizes and encryption the written archive will have; files
+    /// skipped by [`Pf8Builder::skip_unreadable`] or
+    /// [`Pf8Builder::max_file_size`] are left out.
+    pub fn plan(&self) -> Result<Vec<PlannedEntry>> {
+        if self.files.is_empty() {
+            return Err(self.no_files_error());
+        }
+        Ok(self
+            .plan_entries(&mut NoOpHandler)?
+            .into_iter()
+            .map(|(entry, source)| {
+                let (source, shared) = match source {
+                    EntrySource::Shared(original) => (*original, true),
+                    source => (source, false),
+                };
+                let source = match source {
+                    EntrySource::File(path) => Some(path),
+                    _ => None,
+                };
+                PlannedEntry {
+                    entry,
+                    source,
+                    shared,
+                }
+            })
+            .collect())
     }
 
     /// Returns the number of files that will be included
@@ -323,9 +1505,15 @@ impl Pf8Builder {
     /// Clears all added files
     pub fn clear(&mut self) -> &mut Self {
         self.files.clear();
+        self.empty_dirs.clear();
         self
     }
 
+    /// Gets the added directories that contained no files
+    pub fn empty_dirs(&self) -> impl Iterator<Item = &Path> {
+        self.empty_dirs.iter().map(PathBuf::as_path)
+    }
+
     /// Gets a list of all files that will be archived
     pub fn files(&self) -> impl Iterator<Item = (&Path, &Path)> {
         self.files
@@ -334,6 +1522,18 @@ impl Pf8Builder {
     }
 }
 
+/// Adds the source path to an I/O error, e.g. a file removed or truncated
+/// between planning and writing
+fn with_source_path(err: Error, source_path: &Path) -> Error {
+    match err {
+        Error::Io(err) => Error::Io(std::io::Error::new(
+            err.kind(),
+            format!("{}: {}", source_path.display(), err),
+        )),
+        other => other,
+    }
+}
+
 impl Default for Pf8Builder {
     fn default() -> Self {
         Self::new()
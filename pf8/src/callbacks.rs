@@ -53,9 +53,29 @@ impl fmt::Display for OperationType {
     }
 }
 
+/// Phase of an archive operation reported with each progress update
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperationPhase {
+    /// Collecting and stat'ing the files to process
+    Scanning,
+    /// Writing file data (to the archive when packing, to disk when unpacking)
+    Writing,
+}
+
+impl fmt::Display for OperationPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OperationPhase::Scanning => write!(f, "Scanning"),
+            OperationPhase::Writing => write!(f, "Writing"),
+        }
+    }
+}
+
 /// Progress information for archive operations
 #[derive(Debug, Clone)]
 pub struct ProgressInfo {
+    /// Phase the operation is currently in
+    pub phase: OperationPhase,
     /// Bytes processed so far
     pub processed_bytes: u64,
     /// Total bytes to process (None if unknown, e.g., during packing)
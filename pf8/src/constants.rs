@@ -1,5 +1,11 @@
 // Default unencrypted file extensions
 pub const UNENCRYPTED_FILTER: [&str; 2] = ["mp4", "flv"];
 
+// File names of sidecar entries holding internal bookkeeping
+pub const SIDECAR_NAMES: [&str; 3] = [".pfsmeta", ".pfsinfo", ".keep"];
+
 // 4MiB Buffer size for reading/writing data
 pub const BUFFER_SIZE: usize = 4 * 1024 * 1024;
+
+// 4KiB Smallest buffer size accepted by the buffer size setters
+pub const MIN_BUFFER_SIZE: usize = 4 * 1024;
@@ -1,5 +1,6 @@
 //! Cryptographic operations for PF8 files.
 
+use crate::error::{Error, Result};
 use crate::format;
 use sha1::{Digest, Sha1};
 
@@ -23,16 +24,30 @@ pub fn generate_key(data: &[u8], index_size: u32) -> Vec<u8> {
 }
 
 /// Encrypts data using XOR with the provided key, starting from a specific offset
-pub fn encrypt(data: &mut [u8], key: &[u8], offset: usize) {
+///
+/// Fails on an empty key instead of dividing by zero.
+pub fn encrypt(data: &mut [u8], key: &[u8], offset: usize) -> Result<()> {
+    check_key(key)?;
     for (i, byte) in data.iter_mut().enumerate() {
         *byte ^= key[(offset + i) % key.len()];
     }
+    Ok(())
 }
 
 /// Decrypts data using XOR with the provided key (same as encrypt for XOR)
-pub fn decrypt(data: &[u8], key: &[u8]) -> Vec<u8> {
-    data.iter()
-        .enumerate()
-        .map(|(i, &byte)| byte ^ key[i % key.len()])
-        .collect()
+pub fn decrypt(data: &[u8], key: &[u8]) -> Result<Vec<u8>> {
+    let mut data = data.to_vec();
+    encrypt(&mut data, key, 0)?;
+    Ok(data)
+}
+
+/// Rejects keys that cannot be applied
+///
+/// Keys derived by [`generate_key`] are always 20 bytes; this guards against
+/// degenerate keys reaching the XOR loops.
+pub fn check_key(key: &[u8]) -> Result<()> {
+    if key.is_empty() {
+        return Err(Error::Crypto("empty key".to_string()));
+    }
+    Ok(())
 }
@@ -0,0 +1,102 @@
+//! Comparing the contents of two archives.
+
+use crate::entry::Pf8Entry;
+use crate::error::Result;
+use crate::reader::Pf8Reader;
+use crate::utils;
+use crate::verify;
+use std::collections::BTreeMap;
+
+/// An entry present in both archives of a [`DiffReport`] with different data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedEntry {
+    /// Archive-relative path, always using `/` as separator
+    pub path: String,
+    /// Size of the entry in the first archive
+    pub old_size: u32,
+    /// Size of the entry in the second archive
+    pub new_size: u32,
+}
+
+/// Result of [`Pf8Reader::diff`] and [`Pf8Reader::diff_index`]
+///
+/// Every list is sorted by path, using `/` as separator.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiffReport {
+    /// Paths of entries found only in the first archive
+    pub only_in_a: Vec<String>,
+    /// Paths of entries found only in the second archive
+    pub only_in_b: Vec<String>,
+    /// Entries found in both archives whose size or content differs
+    pub changed: Vec<ChangedEntry>,
+}
+
+impl DiffReport {
+    /// Returns true if both archives hold the same entries
+    pub fn is_empty(&self) -> bool {
+        self.only_in_a.is_empty() && self.only_in_b.is_empty() && self.changed.is_empty()
+    }
+
+    /// Returns the total number of differences
+    pub fn len(&self) -> usize {
+        self.only_in_a.len() + self.only_in_b.len() + self.changed.len()
+    }
+}
+
+/// Entries of equal size found in both archives, with their path
+type SameSizePairs<'a> = Vec<(String, &'a Pf8Entry, &'a Pf8Entry)>;
+
+/// Compares the indexes of two archives by path and size
+pub(crate) fn diff_index(a: &Pf8Reader, b: &Pf8Reader) -> DiffReport {
+    compare_index(a, b).0
+}
+
+/// Compares two archives by path, size and decrypted content
+pub(crate) fn diff_content(a: &Pf8Reader, b: &Pf8Reader) -> Result<DiffReport> {
+    let (mut report, same_size) = compare_index(a, b);
+    for (path, a_entry, b_entry) in same_size {
+        if !verify::streams_equal(a.entry_data(a_entry)?, b.entry_data(b_entry)?)? {
+            report.changed.push(ChangedEntry {
+                path,
+                old_size: a_entry.size(),
+                new_size: b_entry.size(),
+            });
+        }
+    }
+    report.changed.sort_by(|x, y| x.path.cmp(&y.path));
+    Ok(report)
+}
+
+/// Matches entries by path and reports those missing on either side or
+/// differing in size, returning the remaining pairs for content comparison
+fn compare_index<'a>(a: &'a Pf8Reader, b: &'a Pf8Reader) -> (DiffReport, SameSizePairs<'a>) {
+    let a_entries = by_path(a);
+    let mut b_entries = by_path(b);
+    let mut report = DiffReport::default();
+    let mut same_size = Vec::new();
+
+    for (path, a_entry) in a_entries {
+        match b_entries.remove(&path) {
+            None => report.only_in_a.push(path),
+            Some(b_entry) if a_entry.size() != b_entry.size() => {
+                report.changed.push(ChangedEntry {
+                    path,
+                    old_size: a_entry.size(),
+                    new_size: b_entry.size(),
+                });
+            }
+            Some(b_entry) => same_size.push((path, a_entry, b_entry)),
+        }
+    }
+    report.only_in_b = b_entries.into_keys().collect();
+
+    (report, same_size)
+}
+
+/// Maps the normalized path of every entry to the entry, sorted by path
+fn by_path(reader: &Pf8Reader) -> BTreeMap<String, &Pf8Entry> {
+    reader
+        .entries()
+        .map(|entry| (utils::normalize_lookup_path(entry.path()), entry))
+        .collect()
+}
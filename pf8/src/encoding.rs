@@ -0,0 +1,81 @@
+//! Character encodings for entry names.
+
+#[cfg(feature = "encoding")]
+use crate::error::Error;
+use crate::error::Result;
+use std::borrow::Cow;
+
+/// Character encoding of entry names in the archive index
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
+pub enum NameEncoding {
+    /// UTF-8, used by this crate and most modern tools
+    #[default]
+    Utf8,
+    /// Shift-JIS, expected by some legacy games
+    #[cfg(feature = "encoding")]
+    ShiftJis,
+    /// UTF-8 if the name is valid UTF-8, Shift-JIS otherwise
+    ///
+    /// Detection is per name. Names are always written as UTF-8.
+    #[cfg(feature = "encoding")]
+    Auto,
+}
+
+impl NameEncoding {
+    /// Encodes an entry name into the bytes stored in the index
+    pub(crate) fn encode<'a>(&self, name: &'a str) -> Result<Cow<'a, [u8]>> {
+        match self {
+            NameEncoding::Utf8 => Ok(Cow::Borrowed(name.as_bytes())),
+            #[cfg(feature = "encoding")]
+            NameEncoding::Auto => Ok(Cow::Borrowed(name.as_bytes())),
+            #[cfg(feature = "encoding")]
+            NameEncoding::ShiftJis => {
+                let (bytes, _, unmappable) = encoding_rs::SHIFT_JIS.encode(name);
+                if unmappable {
+                    return Err(Error::InvalidFormat(format!(
+                        "File name cannot be encoded in Shift-JIS: {name}"
+                    )));
+                }
+                Ok(bytes)
+            }
+        }
+    }
+
+    /// Decodes the bytes stored in the index into an entry name
+    pub(crate) fn decode(&self, bytes: &[u8]) -> Result<String> {
+        match self {
+            NameEncoding::Utf8 => Ok(String::from_utf8(bytes.to_vec())?),
+            #[cfg(feature = "encoding")]
+            NameEncoding::ShiftJis => encoding_rs::SHIFT_JIS
+                .decode_without_bom_handling_and_without_replacement(bytes)
+                .map(Cow::into_owned)
+                .ok_or_else(|| Error::InvalidFormat("Invalid Shift-JIS in file name".to_string())),
+            #[cfg(feature = "encoding")]
+            NameEncoding::Auto => match std::str::from_utf8(bytes) {
+                Ok(name) => Ok(name.to_string()),
+                Err(_) => NameEncoding::ShiftJis.decode(bytes).map_err(|_| {
+                    Error::InvalidFormat("File name is neither UTF-8 nor Shift-JIS".to_string())
+                }),
+            },
+        }
+    }
+
+    /// Returns the encoding `bytes` are decoded with: the one detected for
+    /// [`NameEncoding::Auto`], `self` otherwise
+    #[cfg_attr(not(feature = "encoding"), allow(unused_variables))]
+    pub(crate) fn detect(&self, bytes: &[u8]) -> NameEncoding {
+        match self {
+            #[cfg(feature = "encoding")]
+            NameEncoding::Auto if std::str::from_utf8(bytes).is_err() => NameEncoding::ShiftJis,
+            #[cfg(feature = "encoding")]
+            NameEncoding::Auto => NameEncoding::Utf8,
+            other => *other,
+        }
+    }
+
+    /// Returns the length of an entry name once encoded
+    pub(crate) fn encoded_len(&self, name: &str) -> Result<usize> {
+        Ok(self.encode(name)?.len())
+    }
+}
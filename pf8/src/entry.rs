@@ -1,11 +1,28 @@
 //! File entry representation and operations.
 
+use crate::constants::UNENCRYPTED_FILTER;
 use crate::crypto;
 use crate::error::{Error, Result};
 use crate::format::{ArchiveFormat, RawEntry};
 use crate::utils;
 use std::path::{Path, PathBuf};
 
+/// How entry names are split into path components
+///
+/// The format uses `\` between components, but some packers write `/`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PathNormalization {
+    /// Only `\` separates components
+    ///
+    /// A `/` inside a name cannot be stored in a file name on disk and is
+    /// replaced with `_`, so `a/b.txt` becomes the single file `a_b.txt`
+    /// (and is looked up under that name).
+    Strict,
+    /// Both `\` and `/` separate components
+    #[default]
+    Lenient,
+}
+
 /// Represents a file entry in a PF8 archive
 #[derive(Debug, Clone)]
 pub struct Pf8Entry {
@@ -19,9 +36,12 @@ pub struct Pf8Entry {
 
 impl Pf8Entry {
     /// Creates a new entry from raw data
-    pub fn from_raw(raw: RawEntry) -> Self {
-        let path = utils::pf8_path_to_pathbuf(raw.name.trim_end_matches('\0'));
-        let encrypted = !utils::matches_any_pattern(&raw.name);
+    ///
+    /// Trailing NUL terminators are stripped from the name.
+    pub fn from_raw(mut raw: RawEntry) -> Self {
+        utils::strip_nul_terminator(&mut raw.name);
+        let path = utils::pf8_path_to_pathbuf(&raw.name);
+        let encrypted = !utils::matches_any_pattern(&raw.name, &UNENCRYPTED_FILTER);
 
         Self {
             raw,
@@ -31,12 +51,33 @@ impl Pf8Entry {
     }
 
     /// Creates a new entry from raw data with format awareness
+    ///
+    /// Trailing NUL terminators are stripped from the name.
     pub fn from_raw_with_format(raw: RawEntry, format: ArchiveFormat) -> Self {
-        let path = utils::pf8_path_to_pathbuf(raw.name.trim_end_matches('\0'));
+        Self::from_raw_normalized(raw, format, PathNormalization::default(), None)
+    }
+
+    /// Creates a new entry from raw data, splitting its name as `normalization` says
+    ///
+    /// `unencrypted` overrides the default unencrypted patterns.
+    pub(crate) fn from_raw_normalized(
+        mut raw: RawEntry,
+        format: ArchiveFormat,
+        normalization: PathNormalization,
+        unencrypted: Option<&[String]>,
+    ) -> Self {
+        utils::strip_nul_terminator(&mut raw.name);
+        let path = match normalization {
+            PathNormalization::Strict => utils::pf8_path_to_pathbuf_strict(&raw.name),
+            PathNormalization::Lenient => utils::pf8_path_to_pathbuf(&raw.name),
+        };
         // In PF6 format, no files are encrypted
         let encrypted = match format {
             ArchiveFormat::Pf6 => false,
-            ArchiveFormat::Pf8 => !utils::matches_any_pattern(&raw.name),
+            ArchiveFormat::Pf8 => match unencrypted {
+                Some(patterns) => !utils::matches_any_pattern(&raw.name, patterns),
+                None => !utils::matches_any_pattern(&raw.name, &UNENCRYPTED_FILTER),
+            },
         };
 
         Self {
@@ -48,9 +89,24 @@ impl Pf8Entry {
 
     /// Creates a new entry for building archives
     pub fn new<P: AsRef<Path>>(path: P, offset: u32, size: u32) -> Self {
+        Self::with_unencrypted_patterns(path, offset, size, &UNENCRYPTED_FILTER)
+    }
+
+    /// Creates a new entry for building archives, left unencrypted if its
+    /// name matches one of `patterns`
+    ///
+    /// Patterns starting with `.` match the end of the name; others match
+    /// any part of it.
+    pub fn with_unencrypted_patterns<P: AsRef<Path>, S: AsRef<str>>(
+        path: P,
+        offset: u32,
+        size: u32,
+        patterns: &[S],
+    ) -> Self {
         let path_ref = path.as_ref();
-        let pf8_name = utils::pathbuf_to_pf8_path(path_ref);
-        let encrypted = !utils::matches_any_pattern(&pf8_name);
+        let mut pf8_name = utils::pathbuf_to_pf8_path(path_ref);
+        utils::strip_nul_terminator(&mut pf8_name);
+        let encrypted = !utils::matches_any_pattern(&pf8_name, patterns);
 
         Self {
             raw: RawEntry {
@@ -63,6 +119,18 @@ impl Pf8Entry {
         }
     }
 
+    /// Sets whether the entry data is stored encrypted
+    pub(crate) fn with_encryption(mut self, encrypted: bool) -> Self {
+        self.encrypted = encrypted;
+        self
+    }
+
+    /// Marks the entry as stored unencrypted, as every PF6 entry is
+    pub(crate) fn into_unencrypted(mut self) -> Self {
+        self.encrypted = false;
+        self
+    }
+
     /// Gets the file path within the archive
     pub fn path(&self) -> &Path {
         &self.path
@@ -83,12 +151,28 @@ impl Pf8Entry {
         self.raw.offset
     }
 
+    /// Gets the file size in bytes, widened for 64-bit position arithmetic
+    ///
+    /// Prefer this over `size() as u64` when computing file positions, so
+    /// sums of offsets and sizes cannot wrap.
+    pub fn size_u64(&self) -> u64 {
+        self.raw.size as u64
+    }
+
+    /// Gets the offset of the file data, widened for 64-bit position
+    /// arithmetic
+    pub fn offset_u64(&self) -> u64 {
+        self.raw.offset as u64
+    }
+
     /// Returns whether this file is encrypted
     pub fn is_encrypted(&self) -> bool {
         self.encrypted
     }
 
-    /// Gets the raw PF8 path string
+    /// Gets the PF8 path string (backslash-separated, without NUL terminator)
+    ///
+    /// This is exactly the name written to the index by [`crate::Pf8Writer`].
     pub fn pf8_path(&self) -> &str {
         &self.raw.name
     }
@@ -110,7 +194,7 @@ impl Pf8Entry {
 
         if self.encrypted {
             if let Some(key) = encryption_key {
-                Ok(crypto::decrypt(data, key))
+                crypto::decrypt(data, key)
             } else {
                 Err(Error::Crypto(
                     "File is encrypted but no key provided".to_string(),
@@ -151,9 +235,8 @@ impl Pf8Entry {
 
         if self.encrypted {
             if let Some(key) = encryption_key {
-                for (i, &byte) in data.iter().enumerate() {
-                    buffer[i] = byte ^ key[i % key.len()];
-                }
+                buffer.copy_from_slice(data);
+                crypto::encrypt(buffer, key, 0)?;
             } else {
                 return Err(Error::Crypto(
                     "File is encrypted but no key provided".to_string(),
@@ -180,3 +263,27 @@ impl std::hash::Hash for Pf8Entry {
         self.path.hash(state);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_uses_default_filter() {
+        assert!(Pf8Entry::new("script/main.txt", 0, 4).is_encrypted());
+        assert!(!Pf8Entry::new("movie/op.mp4", 0, 4).is_encrypted());
+    }
+
+    #[test]
+    fn test_with_unencrypted_patterns() {
+        let patterns = [".txt", "readme"];
+        let entry = |path| Pf8Entry::with_unencrypted_patterns(path, 0, 4, &patterns);
+
+        assert!(!entry("script/main.txt").is_encrypted());
+        assert!(!entry("docs/readme.md").is_encrypted());
+        assert!(entry("image/bg.png").is_encrypted());
+        // Custom patterns replace the defaults
+        assert!(entry("movie/op.mp4").is_encrypted());
+        assert_eq!(entry("script/main.txt").pf8_path(), "script\\main.txt");
+    }
+}
@@ -1,4 +1,25 @@
 //! Error types for the PF8 library.
+//!
+//! Every public API of this crate returns [`Result`], whose error type is the
+//! concrete [`Error`] enum. The crate does not depend on `anyhow`, so callers
+//! can match on the error variants directly:
+//!
+//! ```rust
+//! use pf8::{Error, Pf8Reader};
+//!
+//! fn entry_count(path: &str) -> Result<usize, Error> {
+//!     Ok(Pf8Reader::open(path)?.len())
+//! }
+//!
+//! match entry_count("does-not-exist.pfs") {
+//!     Err(Error::Io(err)) => assert_eq!(err.kind(), std::io::ErrorKind::NotFound),
+//!     other => panic!("unexpected result: {other:?}"),
+//! }
+//!
+//! // `Error` can be boxed or sent across threads like any standard error
+//! fn assert_std_error<E: std::error::Error + Send + Sync + 'static>() {}
+//! assert_std_error::<Error>();
+//! ```
 
 use std::io;
 use std::string::FromUtf8Error;
@@ -27,6 +48,12 @@ pub enum Error {
     /// Archive is corrupted.
     #[error("Archive is corrupted: {0}")]
     Corrupted(String),
+    /// Entry path is unsafe to write to disk.
+    #[error("Unsafe path in archive: {0}")]
+    UnsafePath(String),
+    /// The same entry path was supplied more than once.
+    #[error("Duplicate entry: {0}")]
+    DuplicateEntry(String),
     /// Operation was cancelled.
     #[error("Operation was cancelled")]
     Cancelled,
@@ -0,0 +1,132 @@
+//! Options for selective extraction.
+
+use crate::entry::Pf8Entry;
+use crate::error::{Error, Result};
+use crate::utils;
+use glob::{MatchOptions, Pattern};
+use std::path::PathBuf;
+
+/// What to do when an extracted file already exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverwritePolicy {
+    /// Replace the existing file
+    #[default]
+    Overwrite,
+    /// Keep the existing file and count the entry as skipped
+    Skip,
+    /// Fail the extraction
+    Error,
+}
+
+/// How archive names are mapped to output paths
+///
+/// Archive names always use `\` between components.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NameStyle {
+    /// Each component becomes a directory level, joined with the OS separator
+    #[default]
+    Native,
+    /// Components are joined with `/`, creating nested directories
+    ///
+    /// Identical to [`NameStyle::Native`] on Unix. On Windows the resulting
+    /// paths use `/`, which the filesystem accepts but some tools compare as
+    /// different from `\` paths.
+    Forward,
+    /// Names are kept verbatim as a single flattened file name
+    ///
+    /// `script\main.txt` is written as a file literally named
+    /// `script\main.txt` directly in the output directory. Many tools and
+    /// shells mishandle such names, and on Windows `\` is a separator, so
+    /// this style behaves like [`NameStyle::Native`] there.
+    Backslash,
+}
+
+/// Options for [`crate::extract_with_options`]
+///
+/// Patterns are globs matched against the archive path with `/` separators.
+/// `*` does not cross directory boundaries, `**` does.
+#[derive(Debug, Clone, Default)]
+pub struct ExtractOptions {
+    /// Only entries matching one of these patterns are extracted (all if empty)
+    pub include: Vec<String>,
+    /// Entries matching one of these patterns are not extracted
+    pub exclude: Vec<String>,
+    /// Number of leading path components stripped from each entry; entries
+    /// with no components left are skipped
+    pub strip_components: usize,
+    /// Behavior when an output file already exists
+    pub overwrite: OverwritePolicy,
+    /// How archive names are mapped to output paths
+    pub name_style: NameStyle,
+}
+
+/// Compiled form of [`ExtractOptions`]
+pub(crate) struct ExtractFilter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    strip_components: usize,
+    name_style: NameStyle,
+}
+
+impl ExtractOptions {
+    /// Compiles the glob patterns
+    pub(crate) fn compile(&self) -> Result<ExtractFilter> {
+        let compile = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|pattern| {
+                    Pattern::new(pattern).map_err(|err| {
+                        Error::InvalidFormat(format!("Invalid pattern {pattern:?}: {err}"))
+                    })
+                })
+                .collect::<Result<Vec<_>>>()
+        };
+
+        Ok(ExtractFilter {
+            include: compile(&self.include)?,
+            exclude: compile(&self.exclude)?,
+            strip_components: self.strip_components,
+            name_style: self.name_style,
+        })
+    }
+}
+
+impl ExtractFilter {
+    /// Returns the path an entry is extracted to, relative to the output
+    /// directory, or `None` if the entry is filtered out
+    pub(crate) fn output_relative(&self, entry: &Pf8Entry) -> Option<PathBuf> {
+        let archive_path = utils::normalize_lookup_path(entry.path());
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+        let matches = |patterns: &[Pattern]| {
+            patterns
+                .iter()
+                .any(|pattern| pattern.matches_with(&archive_path, options))
+        };
+
+        if !self.include.is_empty() && !matches(&self.include) {
+            return None;
+        }
+        if matches(&self.exclude) {
+            return None;
+        }
+
+        let components = entry
+            .path()
+            .iter()
+            .skip(self.strip_components)
+            .map(|component| component.to_string_lossy())
+            .collect::<Vec<_>>();
+        if components.is_empty() {
+            return None;
+        }
+
+        Some(match self.name_style {
+            NameStyle::Native => components.iter().map(|c| c.as_ref()).collect(),
+            NameStyle::Forward => PathBuf::from(components.join("/")),
+            NameStyle::Backslash => PathBuf::from(components.join("\\")),
+        })
+    }
+}
@@ -6,14 +6,21 @@
 //    |index_count 4
 //    |file_entrys[]
 //      |name_length 4
-//      |name //string with '\0'
+//      |name //name_length bytes, see "Entry names" below
 //      |00 00 00 00
 //      |offset 4
 //      |size 4
 //    |filesize_count 4
 //    |filesize_offsets[] 8 //offset from faddr 0xf, last is 00 00 00 00 00 00 00 00
 //    |filesize_count_offset 4 //offset from faddr 0x7
+//
+//    Entry names
+//    Some tools terminate names with '\0' and count it in name_length. Names
+//    are read with any trailing '\0' stripped and always written without one,
+//    so a round trip yields the same index bytes and thus the same key as an
+//    archive originally written by this crate.
 
+use crate::encoding::NameEncoding;
 use crate::error::{Error, Result};
 
 /// PF6 magic number
@@ -24,11 +31,32 @@ pub const PF8_MAGIC: &[u8] = b"pf8";
 
 /// Archive format type
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json", serde(rename_all = "UPPERCASE"))]
 pub enum ArchiveFormat {
     Pf6,
     Pf8,
 }
 
+impl ArchiveFormat {
+    /// Returns the magic number written at the start of the archive
+    pub fn magic(self) -> &'static [u8] {
+        match self {
+            ArchiveFormat::Pf6 => PF6_MAGIC,
+            ArchiveFormat::Pf8 => PF8_MAGIC,
+        }
+    }
+}
+
+impl std::fmt::Display for ArchiveFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArchiveFormat::Pf6 => write!(f, "PF6"),
+            ArchiveFormat::Pf8 => write!(f, "PF8"),
+        }
+    }
+}
+
 /// PF8 format header offsets
 pub mod offsets {
     pub const MAGIC: usize = 0x00;
@@ -80,38 +108,109 @@ pub fn read_u32_le(data: &[u8], offset: usize) -> Result<u32> {
     ]))
 }
 
-/// Parses the PF6/PF8 header and returns file entries along with format information
-pub fn parse_entries(data: &[u8]) -> Result<(Vec<RawEntry>, ArchiveFormat)> {
-    let format = validate_magic(data)?;
+/// How entry offsets stored in the index are interpreted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OffsetMode {
+    /// Offsets are absolute file positions, as written by this crate
+    #[default]
+    Absolute,
+    /// Offsets are relative to the start of the data, right after the index
+    ///
+    /// Some variant dumps use this dialect.
+    Relative,
+    /// Relative if the offset of the first entry holding data is 0,
+    /// absolute otherwise
+    Auto,
+}
 
+/// Parses the file entries of a PF6/PF8 index
+///
+/// The magic and the `index_size` header field are not read; the caller
+/// passes `index_size`, which allows reading archives whose header is
+/// damaged. Entry names are decoded with the given encoding. Offsets are
+/// interpreted according to `offset_mode` and always returned as absolute
+/// positions. Zero-size entries hold no data, so their offset is kept as
+/// stored and never checked.
+///
+/// Also returns the encoding the names were found in: for
+/// [`NameEncoding::Auto`], Shift-JIS as soon as one name is not UTF-8.
+///
+/// With `permissive`, parsing stops at the first malformed entry instead of
+/// failing, and an entry count differing from the header or data offsets
+/// inside the index are accepted so they can be reported by verification.
+pub fn parse_entries(
+    data: &[u8],
+    index_size: u32,
+    encoding: NameEncoding,
+    offset_mode: OffsetMode,
+    permissive: bool,
+) -> Result<(Vec<RawEntry>, NameEncoding)> {
     if data.len() < 11 {
         return Err(Error::InvalidFormat(
             "Data too short to parse header".to_string(),
         ));
     }
 
-    let index_size = read_u32_le(data, offsets::INDEX_SIZE)?;
     let index_count = read_u32_le(data, offsets::INDEX_COUNT)?;
 
-    let mut file_entries = Vec::new();
+    // Every entry needs its length, name (at least one byte), reserved
+    // bytes, offset and size, so a forged count cannot fit the index
+    let min_entry_size = 4 + 1 + 4 + 4 + 4;
+    let max_count = index_size as u64 / min_entry_size;
+    if index_count as u64 > max_count && !permissive {
+        return Err(Error::Corrupted(format!(
+            "Index claims {} entries but is only {} bytes",
+            index_count, index_size
+        )));
+    }
+
+    let mut file_entries = Vec::with_capacity(index_count.min(max_count as u32) as usize);
+    let mut name_encoding = encoding.detect(b"");
     let mut cursor = offsets::ENTRIES_START;
     let index_end_pos = (offsets::INDEX_DATA_START + index_size as usize).min(data.len());
+    // File data starts right after the index
+    let data_start = offsets::INDEX_DATA_START as u64 + index_size as u64;
 
     while cursor < index_end_pos && file_entries.len() < index_count as usize {
-        if cursor + 4 > data.len() {
+        if cursor + 4 > index_end_pos {
             break;
         }
 
-        let name_length = read_u32_le(data, cursor)?;
-        cursor += 4;
+        let name_length = read_u32_le(data, cursor)? as usize;
+        let malformed = if name_length == 0 {
+            Some(format!(
+                "Entry {} has implausible name length 0",
+                file_entries.len()
+            ))
+        } else if name_length.saturating_add(cursor + 4 + 12) > index_end_pos {
+            Some(format!(
+                "Entry name at offset {} claims length {} exceeding index bounds",
+                cursor, name_length
+            ))
+        } else {
+            None
+        };
+        match malformed {
+            Some(_) if permissive => break,
+            Some(message) => return Err(Error::Corrupted(message)),
+            None => {}
+        }
 
-        if cursor + name_length as usize + 12 > data.len() {
+        // The filesize table follows the entries; an entry reaching into
+        // the room it needs is read from the table, not a real entry
+        let entry_end = cursor + 4 + name_length + 12;
+        let filesize_table_size = 4 + (file_entries.len() + 2) * 8 + 4;
+        if index_end_pos - entry_end < filesize_table_size {
             break;
         }
+        cursor += 4;
 
-        let name_bytes = &data[cursor..cursor + name_length as usize];
-        let name = String::from_utf8(name_bytes.to_vec())?;
-        cursor += name_length as usize + 4; // Skip name and 4 zero bytes
+        let name_bytes = &data[cursor..cursor + name_length];
+        let name = encoding.decode(name_bytes)?;
+        if name_encoding == NameEncoding::Utf8 {
+            name_encoding = encoding.detect(name_bytes);
+        }
+        cursor += name_length + 4; // Skip name and 4 zero bytes
 
         let offset = read_u32_le(data, cursor)?;
         let size = read_u32_le(data, cursor + 4)?;
@@ -120,7 +219,7 @@ pub fn parse_entries(data: &[u8]) -> Result<(Vec<RawEntry>, ArchiveFormat)> {
         file_entries.push(RawEntry { name, offset, size });
     }
 
-    if file_entries.len() != index_count as usize {
+    if file_entries.len() != index_count as usize && !permissive {
         return Err(Error::Corrupted(format!(
             "Index count mismatch. Expected {}, found {}",
             index_count,
@@ -128,11 +227,118 @@ pub fn parse_entries(data: &[u8]) -> Result<(Vec<RawEntry>, ArchiveFormat)> {
         )));
     }
 
-    Ok((file_entries, format))
+    let relative = match offset_mode {
+        OffsetMode::Absolute => false,
+        OffsetMode::Relative => true,
+        OffsetMode::Auto => file_entries
+            .iter()
+            .find(|entry| entry.size > 0)
+            .is_some_and(|entry| entry.offset == 0),
+    };
+    for entry in file_entries.iter_mut().filter(|entry| entry.size > 0) {
+        if relative {
+            entry.offset = u32::try_from(entry.offset as u64 + data_start).map_err(|_| {
+                Error::Corrupted(format!(
+                    "Entry {} relative offset {} exceeds the 4 GiB format limit",
+                    entry.name, entry.offset
+                ))
+            })?;
+        }
+
+        if (entry.offset as u64) < data_start && !permissive {
+            return Err(Error::Corrupted(format!(
+                "Entry {} data offset {} lies inside the index",
+                entry.name, entry.offset
+            )));
+        }
+    }
+
+    Ok((file_entries, name_encoding))
+}
+
+/// Computes the `index_size` header field for entries with the given name lengths
+///
+/// The index covers the entry count, every entry (name length, name, reserved,
+/// offset and size), the filesize offsets table with its end marker, and the
+/// trailing filesize count offset.
+pub fn index_size_for<I: IntoIterator<Item = usize>>(name_lengths: I) -> usize {
+    let mut count = 0usize;
+    let mut fileentry_size = 0usize;
+
+    for name_length in name_lengths {
+        count += 1;
+        fileentry_size += name_length + 16; // name + padding + offset + size
+    }
+
+    4 + fileentry_size + 4 + (count + 1) * 8 + 4
+}
+
+/// Builds the header and index for entries given as encoded names and sizes
+///
+/// Entry data is laid out back to back in the given order, starting right
+/// after the index.
+pub fn build_header(magic: &[u8], entries: &[(&[u8], u32)]) -> Vec<u8> {
+    let index_size = index_size_for(entries.iter().map(|(name, _)| name.len())) as u32;
+    let mut file_offset = index_size + offsets::INDEX_DATA_START as u32;
+    let entries = entries
+        .iter()
+        .map(|&(name, size)| {
+            let offset = file_offset;
+            file_offset += size;
+            (name, offset, size)
+        })
+        .collect::<Vec<_>>();
+    build_header_with_offsets(magic, &entries)
 }
 
-/// Gets the index size from PF6/PF8 header
-pub fn get_index_size(data: &[u8]) -> Result<u32> {
-    validate_magic(data)?;
-    read_u32_le(data, offsets::INDEX_SIZE)
+/// Builds the header and index for entries given as encoded names, offsets
+/// and sizes
+///
+/// Offsets are stored as given; see [`index_size_for`] to compute where the
+/// data starts.
+pub fn build_header_with_offsets(magic: &[u8], entries: &[(&[u8], u32, u32)]) -> Vec<u8> {
+    let index_count = entries.len() as u32;
+    let index_size = index_size_for(entries.iter().map(|(name, _, _)| name.len())) as u32;
+
+    let mut header = Vec::with_capacity(offsets::INDEX_DATA_START + index_size as usize);
+    header.extend_from_slice(magic);
+    header.extend_from_slice(&index_size.to_le_bytes());
+    header.extend_from_slice(&index_count.to_le_bytes());
+
+    // Write file entries
+    let mut filesize_offsets = Vec::new();
+
+    for (name, file_offset, size) in entries {
+        // name_length
+        header.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        // name
+        header.extend_from_slice(name);
+        // reserved
+        header.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]); // padding
+        // offset
+        header.extend_from_slice(&file_offset.to_le_bytes());
+        // size
+        header.extend_from_slice(&size.to_le_bytes());
+
+        // Track the offset of the size field for later use
+        // offset from faddr 0xf
+        filesize_offsets.push((header.len() - 4 - offsets::FILESIZE_OFFSETS_START) as u64);
+    }
+
+    // Write filesize count and offsets
+    header.extend_from_slice(&(index_count + 1).to_le_bytes());
+
+    let filesize_count_offset = (header.len() - 4 - offsets::INDEX_DATA_START) as u32;
+
+    for offset in filesize_offsets {
+        header.extend_from_slice(&offset.to_le_bytes());
+    }
+
+    // End marker
+    header.extend_from_slice(&[0x00; 8]);
+
+    // Write filesize_count_offset
+    header.extend_from_slice(&filesize_count_offset.to_le_bytes());
+
+    header
 }
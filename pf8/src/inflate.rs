@@ -0,0 +1,53 @@
+//! Reading entries that carry their own compression.
+//!
+//! PF8 stores data uncompressed, but games commonly archive zlib or gzip
+//! blobs as entries. This module decrypts and decompresses such entries in
+//! one step.
+
+use crate::error::{Error, Result};
+use crate::reader::Pf8Reader;
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use std::io::Read;
+use std::path::Path;
+
+/// Compression applied to an entry's data before it was archived
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InnerCodec {
+    /// zlib stream (RFC 1950)
+    Zlib,
+    /// gzip stream (RFC 1952)
+    Gzip,
+    /// Raw deflate stream without header (RFC 1951)
+    Raw,
+}
+
+impl Pf8Reader {
+    /// Reads a file's data and decompresses it with the given codec
+    ///
+    /// The entry is decrypted and inflated while streaming, so only the
+    /// decompressed output is held in memory.
+    pub fn read_file_inflate<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        codec: InnerCodec,
+    ) -> Result<Vec<u8>> {
+        let path = path.as_ref();
+        let entry = self
+            .get_entry(path)
+            .cloned()
+            .ok_or_else(|| Error::FileNotFound(path.display().to_string()))?;
+        let data = self.entry_data(&entry)?;
+
+        let mut output = Vec::new();
+        let result = match codec {
+            InnerCodec::Zlib => ZlibDecoder::new(data).read_to_end(&mut output),
+            InnerCodec::Gzip => GzDecoder::new(data).read_to_end(&mut output),
+            InnerCodec::Raw => DeflateDecoder::new(data).read_to_end(&mut output),
+        };
+        result.map_err(|err| {
+            Error::Corrupted(format!("Failed to inflate {}: {}", path.display(), err))
+        })?;
+
+        Ok(output)
+    }
+}
@@ -85,9 +85,17 @@
 pub mod archive;
 pub mod builder;
 pub mod callbacks;
+pub mod diff;
+pub mod encoding;
 pub mod entry;
 pub mod error;
+pub mod extract;
+pub mod manifest;
 pub mod reader;
+pub mod repack;
+pub mod scan;
+pub mod verify;
+pub mod volumes;
 pub mod writer;
 
 mod constants;
@@ -96,19 +104,42 @@ mod format;
 mod utils;
 
 // Re-export main types for convenience
-pub use archive::Pf8Archive;
-pub use builder::Pf8Builder;
+pub use archive::{
+    AggregatingHandler, DirPolicy, ExtractStats, PackResult, Pf8Archive, UnpackResult,
+};
+pub use builder::{
+    EntryOrder, EstimatedSize, Pf8Builder, PlannedEntry, SortOrder, TOOL_MARKER_NAME, WriteOptions,
+};
 pub use callbacks::{
-    ArchiveError, ArchiveEvent, ArchiveHandler, ControlAction, OperationType, ProgressInfo,
+    ArchiveError, ArchiveEvent, ArchiveHandler, ControlAction, OperationPhase, OperationType,
+    ProgressInfo,
 };
-pub use entry::Pf8Entry;
+pub use diff::{ChangedEntry, DiffReport};
+pub use encoding::NameEncoding;
+pub use entry::{PathNormalization, Pf8Entry};
 pub use error::{Error, Result};
-pub use format::ArchiveFormat;
-pub use reader::Pf8Reader;
+pub use extract::{ExtractOptions, NameStyle, OverwritePolicy};
+pub use format::{ArchiveFormat, OffsetMode};
+pub use manifest::{Manifest, ManifestEntry, PathStyle, export_web_index};
+pub use reader::{
+    CaseSensitivity, EntryMeta, EntryReader, ExtractIter, ExtractedFile, Pf8Reader, ReaderOptions,
+    Strictness,
+};
+pub use repack::{ConflictPolicy, merge, patch};
+pub use scan::{ArchiveInfo, scan_dir};
+pub use utils::{archive_format, entry_count};
+pub use verify::{
+    ArchiveStats, EntryProblem, IntegrityReport, LayoutReport, ProblemKind, VerifyReport,
+    check_layout, decrypt_check, infer_unencrypted_filter, verify_extraction,
+};
+pub use volumes::{MultiVolumeReader, volume_paths};
 pub use writer::Pf8Writer;
 
 // Re-export convenience functions
-pub use archive::{create_from_dir, create_from_dir_with_progress, extract};
+pub use archive::{
+    create_from_dir, create_from_dir_with_progress, estimate_archive_size, extract,
+    extract_with_options,
+};
 
 #[cfg(feature = "display")]
 pub mod display;
@@ -116,3 +147,12 @@ pub mod display;
 // Re-export display functionality when feature is enabled
 #[cfg(feature = "display")]
 pub use display::list_archive;
+
+#[cfg(feature = "inflate")]
+pub mod inflate;
+
+#[cfg(feature = "inflate")]
+pub use inflate::InnerCodec;
+
+#[cfg(feature = "testing")]
+pub mod testing;
@@ -0,0 +1,191 @@
+//! Manifests describing the contents of an archive.
+//!
+//! With the `json` feature, [`Manifest`] implements `serde::Serialize` and
+//! can be written out with [`Manifest::to_json`].
+
+#[cfg(feature = "json")]
+use crate::error::{Error, Result};
+use crate::format::ArchiveFormat;
+use crate::reader::Pf8Reader;
+use crate::utils;
+use std::path::{Path, PathBuf};
+
+/// Selects which path of a [`ManifestEntry`] a report displays
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PathStyle {
+    /// Archive-relative path with forward slashes
+    #[default]
+    Archive,
+    /// Path the entry is (or would be) written to on extraction
+    Output,
+}
+
+/// A single file entry in a [`Manifest`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct ManifestEntry {
+    /// Archive-relative path, always using `/` as separator
+    #[cfg_attr(feature = "json", serde(rename = "path"))]
+    pub archive_path: String,
+    /// Path the entry is written to, if the manifest targets an output directory
+    #[cfg_attr(feature = "json", serde(skip_serializing_if = "Option::is_none"))]
+    pub output_path: Option<PathBuf>,
+    /// Offset of the entry data in the archive
+    pub offset: u32,
+    /// Size of the entry data in bytes
+    pub size: u32,
+    /// Whether the entry data is encrypted
+    pub encrypted: bool,
+}
+
+impl ManifestEntry {
+    /// Returns the path to display for the given style
+    ///
+    /// Falls back to the archive path when no output path is known.
+    pub fn display_path(&self, style: PathStyle) -> String {
+        match (style, &self.output_path) {
+            (PathStyle::Output, Some(output)) => output.display().to_string(),
+            _ => self.archive_path.clone(),
+        }
+    }
+}
+
+/// Machine-friendly description of an archive's contents
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct Manifest {
+    /// Archive format
+    pub format: ArchiveFormat,
+    /// Sum of all entry sizes in bytes
+    pub total_size: u64,
+    /// Entries in index order
+    pub entries: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    /// Builds a manifest from the entries of a reader
+    pub fn from_reader(reader: &Pf8Reader) -> Self {
+        let entries = reader
+            .entries()
+            .map(|entry| ManifestEntry {
+                archive_path: utils::normalize_lookup_path(entry.path()),
+                output_path: None,
+                offset: entry.offset(),
+                size: entry.size(),
+                encrypted: entry.is_encrypted(),
+            })
+            .collect::<Vec<_>>();
+
+        Self {
+            format: reader.format(),
+            total_size: entries.iter().map(|e| e.size as u64).sum(),
+            entries,
+        }
+    }
+
+    /// Fills in the output path of every entry for extraction into `output_dir`
+    pub fn with_output_dir<P: AsRef<Path>>(mut self, output_dir: P) -> Self {
+        let output_dir = output_dir.as_ref();
+        for entry in &mut self.entries {
+            let relative: PathBuf = entry.archive_path.split('/').collect();
+            entry.output_path = Some(output_dir.join(relative));
+        }
+        self
+    }
+
+    /// Serializes the manifest as CSV
+    ///
+    /// Writes a `path,size,encrypted,offset` header, one row per entry and a
+    /// final `Total` row holding [`Self::total_size`]. Fields are quoted per
+    /// RFC 4180 when needed; the result is plain UTF-8 without a BOM.
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("path,size,encrypted,offset\n");
+        for entry in &self.entries {
+            out.push_str(&format!(
+                "{},{},{},{}\n",
+                csv_field(&entry.archive_path),
+                entry.size,
+                entry.encrypted,
+                entry.offset
+            ));
+        }
+        out.push_str(&format!("Total,{},,\n", self.total_size));
+        out
+    }
+
+    /// Serializes the manifest as pretty-printed JSON
+    ///
+    /// ```json
+    /// {"format":"PF8","total_size":N,"entries":[{"path":"a/b.txt","offset":N,"size":N,"encrypted":true}]}
+    /// ```
+    ///
+    /// Output paths are included only when set by [`Self::with_output_dir`].
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| Error::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, e)))
+    }
+}
+
+/// Exports a static JSON index of the archive for serving over HTTP
+///
+/// Maps every entry path to the byte range of its data inside the archive, so
+/// a client can fetch single entries with range requests:
+///
+/// ```json
+/// {"archive_size":N,"entries":[{"path":"a/b.txt","offset":N,"size":N,"encrypted":true}]}
+/// ```
+///
+/// Ranges of encrypted entries cover the encrypted bytes; clients have to
+/// apply the archive key themselves.
+pub fn export_web_index(reader: &Pf8Reader) -> String {
+    let manifest = reader.manifest();
+    let entries = manifest
+        .entries
+        .iter()
+        .map(|entry| {
+            format!(
+                r#"{{"path":{},"offset":{},"size":{},"encrypted":{}}}"#,
+                json_string(&entry.archive_path),
+                entry.offset,
+                entry.size,
+                entry.encrypted
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+
+    format!(
+        r#"{{"archive_size":{},"entries":[{}]}}"#,
+        reader.archive_size(),
+        entries
+    )
+}
+
+/// Quotes a CSV field if it contains a separator, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Encodes a string as a JSON string literal
+fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -1,15 +1,102 @@
 //! High-level reader for PF6/PF8 archives.
 
-use crate::callbacks::{ArchiveHandler, ControlAction, NoOpHandler, OperationType, ProgressInfo};
+use crate::archive::UnpackResult;
+use crate::callbacks::{
+    ArchiveHandler, ControlAction, NoOpHandler, OperationPhase, OperationType, ProgressInfo,
+};
 use crate::constants::BUFFER_SIZE;
 use crate::crypto;
-use crate::entry::Pf8Entry;
+use crate::diff::{self, DiffReport};
+use crate::encoding::NameEncoding;
+use crate::entry::{PathNormalization, Pf8Entry};
 use crate::error::{Error, Result};
-use crate::format::{self, ArchiveFormat};
+use crate::extract::{ExtractOptions, OverwritePolicy};
+use crate::format::{self, ArchiveFormat, OffsetMode};
+use crate::manifest::Manifest;
+use crate::utils;
+use crate::verify::{self, ArchiveStats, IntegrityReport};
+use crate::volumes::MultiVolumeReader;
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::{Read, Seek, SeekFrom};
-use std::path::Path;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+/// How much [`Pf8Reader`] tolerates irregular indexes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strictness {
+    /// Accept irregular indexes; later duplicates shadow earlier entries
+    #[default]
+    Lenient,
+    /// Reject duplicate entry names and names with control characters
+    Strict,
+    /// Like [`Strictness::Lenient`], and also accept entries whose data lies
+    /// inside the index or beyond the end of the archive, and an entry count
+    /// differing from the header
+    ///
+    /// For inspecting damaged archives with [`Pf8Reader::verify`], which
+    /// reports these as problems; reading such entries fails.
+    Permissive,
+}
+
+/// Whether entry lookups by path distinguish letter case
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CaseSensitivity {
+    /// `A.txt` and `a.txt` are different entries
+    #[default]
+    Sensitive,
+    /// `A.txt` and `a.txt` refer to the same entry, like on Windows
+    Insensitive,
+}
+
+/// Options controlling how an archive is opened, see [`Pf8Reader::open_with`]
+#[derive(Debug, Clone)]
+pub struct ReaderOptions {
+    /// How irregular indexes are handled
+    pub strictness: Strictness,
+    /// Encoding of entry names in the index
+    pub name_encoding: NameEncoding,
+    /// How entry offsets in the index are interpreted
+    pub offset_mode: OffsetMode,
+    /// How entry names are split into path components
+    pub path_normalization: PathNormalization,
+    /// Patterns of entries stored unencrypted, replacing the defaults
+    ///
+    /// Must match the patterns the archive was built with, see
+    /// [`Pf8Builder::unencrypted_patterns`](crate::Pf8Builder::unencrypted_patterns).
+    pub unencrypted_patterns: Option<Vec<String>>,
+    /// Whether [`Pf8Reader::get_entry`] and friends ignore letter case
+    pub case_sensitivity: CaseSensitivity,
+    /// Whether a symlink at the archive path is followed
+    ///
+    /// When disabled, opening fails if the path is a symlink; see
+    /// [`Pf8Reader::open_no_follow`].
+    pub follow_symlinks: bool,
+    /// Size in bytes of the buffer used to stream entries during extraction
+    pub buffer_size: usize,
+    /// Key used to decrypt PF8 entries instead of the one derived from the index
+    ///
+    /// For archives whose key is derived differently or was recovered
+    /// separately; see [`Pf8Reader::open_with_key`]. Ignored for PF6.
+    pub key: Option<Vec<u8>>,
+}
+
+impl Default for ReaderOptions {
+    fn default() -> Self {
+        Self {
+            strictness: Strictness::default(),
+            name_encoding: NameEncoding::default(),
+            offset_mode: OffsetMode::default(),
+            path_normalization: PathNormalization::default(),
+            unencrypted_patterns: None,
+            case_sensitivity: CaseSensitivity::default(),
+            follow_symlinks: true,
+            buffer_size: BUFFER_SIZE,
+            key: None,
+        }
+    }
+}
 
 /// Optimized reader for PF6/PF8 archives with minimal memory usage
 ///
@@ -18,77 +105,371 @@ use std::path::Path;
 /// - Reading file data on-demand from disk
 /// - Supporting streaming operations with configurable buffers
 pub struct Pf8Reader {
-    /// File handle for reading archive data
-    file: File,
+    /// Backing storage for reading archive data
+    source: Source,
     /// List of file entries
     entries: Vec<Pf8Entry>,
     /// Lookup map for fast entry access by path
     entry_map: HashMap<String, usize>,
+    /// Lowercased lookup map for [`Self::get_entry_ci`], built on first use
+    entry_map_ci: OnceLock<HashMap<String, usize>>,
     /// Encryption key for the archive (None for PF6)
     encryption_key: Option<Vec<u8>>,
+    /// Key derived from the index, even if another one was supplied
+    index_key: Option<Vec<u8>>,
     /// Archive format
     format: ArchiveFormat,
+    /// Size of the archive file in bytes
+    archive_size: u64,
+    /// Offset where the index ends and entry data begins
+    data_start: u64,
+    /// Whether `extract_all` should visit entries in data offset order
+    offset_order: bool,
+    /// Whether control characters in entry names are replaced instead of rejected
+    sanitize_names: bool,
+    /// Whether sidecar entries are included in listings
+    show_sidecars: bool,
+    /// Whether entry lookups ignore letter case
+    case_sensitivity: CaseSensitivity,
+    /// Size of the buffer used to stream entries during extraction
+    buffer_size: usize,
+    /// Encoding the entry names are stored in
+    name_encoding: NameEncoding,
+    /// Patterns of entries stored unencrypted, if not the defaults
+    unencrypted_patterns: Option<Vec<String>>,
 }
 
 impl Pf8Reader {
     /// Opens a PF6/PF8 archive for reading with minimal memory usage
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let mut file = File::open(path)?;
+        Self::open_with(path, ReaderOptions::default())
+    }
+
+    /// Opens a PF6/PF8 archive with custom options
+    ///
+    /// This is the configurable entry point; the other `open_*` constructors
+    /// are shorthands for common [`ReaderOptions`].
+    pub fn open_with<P: AsRef<Path>>(path: P, options: ReaderOptions) -> Result<Self> {
+        let file = open_file(path.as_ref(), options.follow_symlinks)?;
+        let archive_size = file.metadata()?.len();
+        Self::from_source(Source::File(Mutex::new(file)), archive_size, options, None)
+    }
+
+    /// Opens a PF8 archive built with custom unencrypted patterns
+    ///
+    /// `patterns` must match those given to
+    /// [`Pf8Builder::unencrypted_patterns`](crate::Pf8Builder::unencrypted_patterns)
+    /// and [`Pf8Builder::unencrypted_extensions`](crate::Pf8Builder::unencrypted_extensions),
+    /// extensions written with a leading `.`.
+    pub fn open_with_patterns<P: AsRef<Path>>(path: P, patterns: &[&str]) -> Result<Self> {
+        Self::open_with(
+            path,
+            ReaderOptions {
+                unencrypted_patterns: Some(patterns.iter().map(|p| p.to_string()).collect()),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Opens a PF8 archive decrypting its entries with `key`
+    ///
+    /// The key normally derived from the index is not computed, so this also
+    /// opens archives whose key is derived some other way. [`Self::verify`]
+    /// reports a key mismatch unless `key` equals the derived key.
+    pub fn open_with_key<P: AsRef<Path>>(path: P, key: Vec<u8>) -> Result<Self> {
+        Self::open_with(
+            path,
+            ReaderOptions {
+                key: Some(key),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Opens an archive assuming `format`, without checking the magic bytes
+    ///
+    /// A recovery tool for archives whose header is damaged but whose index
+    /// is intact. If `index_size_hint` is given it replaces the header's
+    /// `index_size` field. The PF8 key is derived from the index only, so a
+    /// damaged magic does not affect decryption.
+    pub fn open_as<P: AsRef<Path>>(
+        path: P,
+        format: ArchiveFormat,
+        index_size_hint: Option<u32>,
+    ) -> Result<Self> {
+        let file = File::open(path)?;
+        let archive_size = file.metadata()?.len();
+        Self::from_source(
+            Source::File(Mutex::new(file)),
+            archive_size,
+            ReaderOptions::default(),
+            Some((format, index_size_hint)),
+        )
+    }
+
+    /// Opens an archive whose index stores offsets relative to the data start
+    ///
+    /// Entry offsets are converted to absolute positions while parsing, so
+    /// every other method works unchanged. Use [`OffsetMode::Auto`] through
+    /// [`Self::open_with`] if the dialect is not known in advance.
+    pub fn open_relative_offsets<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_with(
+            path,
+            ReaderOptions {
+                offset_mode: OffsetMode::Relative,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Opens a PF6/PF8 archive, refusing to follow a symlink at `path`
+    ///
+    /// On Unix the file is opened with `O_NOFOLLOW`, so the check and the
+    /// open are a single atomic step. Elsewhere the path is checked with
+    /// [`std::fs::symlink_metadata`] before opening.
+    pub fn open_no_follow<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_with(
+            path,
+            ReaderOptions {
+                follow_symlinks: false,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Opens an archive together with its numbered volumes
+    ///
+    /// `base` (e.g. `game.pfs`) is opened along with `game.pfs.000`,
+    /// `game.pfs.001`, ... and the entries of all volumes are presented as
+    /// one archive; see [`MultiVolumeReader`] for how they combine.
+    pub fn open_multi<P: AsRef<Path>>(base: P) -> Result<MultiVolumeReader> {
+        MultiVolumeReader::open(base)
+    }
+
+    /// Opens a PF6/PF8 archive held entirely in memory
+    ///
+    /// Entry data is served from the buffer without any file I/O, and
+    /// unencrypted entries can be borrowed directly via [`Self::entry_slice`].
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self> {
+        let archive_size = data.len() as u64;
+        Self::from_source(
+            Source::Memory(Cursor::new(data)),
+            archive_size,
+            ReaderOptions::default(),
+            None,
+        )
+    }
+
+    /// Opens a PF6/PF8 archive from any seekable byte source
+    ///
+    /// Useful for archives embedded in a larger container. The archive is
+    /// taken to span from the start of `reader` to its end; wrap the
+    /// container in an adapter to expose a sub-range. Reads are served on
+    /// demand, like [`Self::open`].
+    pub fn from_reader<R: Read + Seek + Send + 'static>(
+        mut reader: R,
+        options: ReaderOptions,
+    ) -> Result<Self> {
+        let archive_size = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
+        Self::from_source(
+            Source::Stream(Mutex::new(Box::new(reader))),
+            archive_size,
+            options,
+            None,
+        )
+    }
+
+    /// Parses the header and index from the given source
+    ///
+    /// `assumed` overrides the detected format and, optionally, the
+    /// `index_size` header field.
+    fn from_source(
+        mut source: Source,
+        archive_size: u64,
+        options: ReaderOptions,
+        assumed: Option<(ArchiveFormat, Option<u32>)>,
+    ) -> Result<Self> {
+        utils::check_buffer_size(options.buffer_size)?;
 
         // Read only the header and index data into memory
         let header_size = 11; // minimum header size
         let mut header_buffer = vec![0u8; header_size];
-        file.read_exact(&mut header_buffer)?;
+        source.read_exact(&mut header_buffer)?;
 
-        let _format = format::validate_magic(&header_buffer)?;
-        let index_size = format::read_u32_le(&header_buffer, format::offsets::INDEX_SIZE)?;
+        let (format, index_size_hint) = match assumed {
+            Some(assumed) => assumed,
+            None => (format::validate_magic(&header_buffer)?, None),
+        };
+        let index_size = match index_size_hint {
+            Some(index_size) => index_size,
+            None => format::read_u32_le(&header_buffer, format::offsets::INDEX_SIZE)?,
+        };
 
         // Read the entire index into memory
         let total_index_size = format::offsets::INDEX_DATA_START + index_size as usize;
+        if total_index_size as u64 > archive_size {
+            return Err(Error::Corrupted(format!(
+                "Index size {} exceeds archive size {}",
+                index_size, archive_size
+            )));
+        }
         let mut index_buffer = vec![0u8; total_index_size];
-        file.seek(SeekFrom::Start(0))?;
-        file.read_exact(&mut index_buffer)?;
+        source.seek(SeekFrom::Start(0))?;
+        source.read_exact(&mut index_buffer)?;
 
-        let (raw_entries, format) = format::parse_entries(&index_buffer)?;
+        let (raw_entries, name_encoding) = format::parse_entries(
+            &index_buffer,
+            index_size,
+            options.name_encoding,
+            options.offset_mode,
+            options.strictness == Strictness::Permissive,
+        )?;
 
-        // Generate encryption key only for PF8 format
-        let encryption_key = match format {
+        // Generate encryption key only for PF8 format, unless one was supplied
+        let index_key = match format {
             ArchiveFormat::Pf8 => Some(crypto::generate_key(&index_buffer, index_size)),
             ArchiveFormat::Pf6 => None,
         };
+        let encryption_key = match (format, options.key) {
+            (ArchiveFormat::Pf8, Some(key)) => {
+                crypto::check_key(&key)?;
+                Some(key)
+            }
+            _ => index_key.clone(),
+        };
 
         let mut entries = Vec::with_capacity(raw_entries.len());
         let mut entry_map = HashMap::new();
 
         for (index, raw_entry) in raw_entries.into_iter().enumerate() {
-            let entry = Pf8Entry::from_raw_with_format(raw_entry, format);
-            let path_string = entry.path().to_string_lossy().to_string();
-            entry_map.insert(path_string, index);
+            // Placeholder entries hold no data; their offset may point anywhere
+            if raw_entry.size > 0
+                && raw_entry.offset as u64 + raw_entry.size as u64 > archive_size
+                && options.strictness != Strictness::Permissive
+            {
+                return Err(Error::Corrupted(format!(
+                    "Entry {} data ({} bytes at offset {}) extends beyond archive size {}",
+                    raw_entry.name, raw_entry.size, raw_entry.offset, archive_size
+                )));
+            }
+            if options.strictness == Strictness::Strict && utils::has_control_chars(&raw_entry.name)
+            {
+                return Err(Error::UnsafePath(format!(
+                    "entry name contains control characters: {:?}",
+                    raw_entry.name
+                )));
+            }
+            let entry = Pf8Entry::from_raw_normalized(
+                raw_entry,
+                format,
+                options.path_normalization,
+                options.unencrypted_patterns.as_deref(),
+            );
+            let key = lookup_key(entry.path(), options.case_sensitivity);
+            if entry_map.insert(key, index).is_some() && options.strictness == Strictness::Strict {
+                return Err(Error::DuplicateEntry(entry.pf8_path().to_string()));
+            }
             entries.push(entry);
         }
 
         Ok(Self {
-            file,
+            source,
             entries,
             entry_map,
+            entry_map_ci: OnceLock::new(),
             encryption_key,
+            index_key,
             format,
+            archive_size,
+            data_start: total_index_size as u64,
+            offset_order: false,
+            sanitize_names: false,
+            show_sidecars: false,
+            case_sensitivity: options.case_sensitivity,
+            buffer_size: options.buffer_size,
+            name_encoding,
+            unencrypted_patterns: options.unencrypted_patterns,
         })
     }
 
     /// Returns an iterator over all file entries
+    ///
+    /// Sidecar entries (`.pfsmeta`, `.pfsinfo`, `.keep`) are left out unless
+    /// enabled with [`Self::show_sidecars`].
     pub fn entries(&self) -> impl Iterator<Item = &Pf8Entry> {
+        self.entries
+            .iter()
+            .filter(|entry| self.show_sidecars || !utils::is_sidecar(entry.path()))
+    }
+
+    /// Returns every entry in the index, including sidecars
+    pub(crate) fn all_entries(&self) -> impl Iterator<Item = &Pf8Entry> {
         self.entries.iter()
     }
 
+    /// Returns all file entries sorted by their data offset
+    ///
+    /// Reading entries in this order touches the archive sequentially, which
+    /// minimizes seeking on spinning disks and network storage. Sidecars are
+    /// filtered as in [`Self::entries`].
+    pub fn entries_by_offset(&self) -> Vec<&Pf8Entry> {
+        let mut entries: Vec<_> = self.entries().collect();
+        entries.sort_by_key(|entry| entry.offset());
+        entries
+    }
+
+    /// Sets whether sidecar entries are included in listings (default false)
+    ///
+    /// Sidecars hold internal bookkeeping such as metadata or markers that
+    /// keep empty directories. Hidden sidecars can still be looked up and
+    /// read by path, and are always extracted.
+    pub fn show_sidecars(&mut self, enabled: bool) -> &mut Self {
+        self.show_sidecars = enabled;
+        self
+    }
+
+    /// Sets the size in bytes of the buffer used to stream entries
+    ///
+    /// Overrides [`ReaderOptions::buffer_size`] (default 4 MiB). Larger
+    /// buffers mean fewer reads on slow disks; smaller ones bound the memory
+    /// used per operation. Sizes below 4 KiB are rejected.
+    pub fn set_buffer_size(&mut self, size: usize) -> Result<&mut Self> {
+        utils::check_buffer_size(size)?;
+        self.buffer_size = size;
+        Ok(self)
+    }
+
+    /// Sets whether `extract_all` visits entries in data offset order
+    /// instead of index order
+    pub fn set_offset_order(&mut self, enabled: bool) -> &mut Self {
+        self.offset_order = enabled;
+        self
+    }
+
+    /// Sets whether control characters in entry names are replaced with `_`
+    /// on extraction
+    ///
+    /// By default, entries whose names contain control characters (newlines,
+    /// embedded NULs, terminal escapes, ...) are rejected with
+    /// [`Error::UnsafePath`], since such names almost always indicate a
+    /// corrupted or malicious archive.
+    pub fn set_sanitize_names(&mut self, enabled: bool) -> &mut Self {
+        self.sanitize_names = enabled;
+        self
+    }
+
     /// Gets the number of files in the archive
+    ///
+    /// Sidecars are counted only if enabled with [`Self::show_sidecars`],
+    /// like every other listing and summary of the entries.
     pub fn len(&self) -> usize {
-        self.entries.len()
+        self.entries().count()
     }
 
-    /// Returns true if the archive is empty
+    /// Returns true if the archive has no files, see [`Self::len`]
     pub fn is_empty(&self) -> bool {
-        self.entries.is_empty()
+        self.entries().next().is_none()
     }
 
     /// Gets the archive format (PF6 or PF8)
@@ -96,16 +477,220 @@ impl Pf8Reader {
         self.format
     }
 
+    /// Returns the encoding the entry names are stored in
+    ///
+    /// For archives opened with [`NameEncoding::Auto`] this is the detected
+    /// encoding: Shift-JIS if any name is not valid UTF-8, UTF-8 otherwise.
+    pub fn name_encoding(&self) -> NameEncoding {
+        self.name_encoding
+    }
+
+    /// Returns whether an entry at `archive_path` would be read as encrypted,
+    /// following the format and unencrypted patterns the archive was opened
+    /// with
+    pub(crate) fn encrypts(&self, archive_path: &Path) -> bool {
+        let raw = format::RawEntry {
+            name: utils::pathbuf_to_pf8_path(archive_path),
+            offset: 0,
+            size: 0,
+        };
+        Pf8Entry::from_raw_normalized(
+            raw,
+            self.format,
+            PathNormalization::default(),
+            self.unencrypted_patterns.as_deref(),
+        )
+        .is_encrypted()
+    }
+
+    /// Returns true if any entry has one of the given extensions
+    ///
+    /// Extensions are matched case-insensitively, with or without a leading
+    /// dot (`"png"` and `".PNG"` are equivalent).
+    pub fn has_any_extension(&self, exts: &[&str]) -> bool {
+        let exts: Vec<String> = exts
+            .iter()
+            .map(|ext| ext.trim_start_matches('.').to_lowercase())
+            .collect();
+        self.entries()
+            .any(|entry| exts.contains(&utils::lowercase_extension(entry.path())))
+    }
+
+    /// Counts entries per lowercase extension
+    ///
+    /// Entries without an extension are counted under the empty string.
+    pub fn count_by_extension(&self) -> HashMap<String, usize> {
+        let mut counts = HashMap::new();
+        for entry in self.entries() {
+            *counts
+                .entry(utils::lowercase_extension(entry.path()))
+                .or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Sums entry sizes per lowercase extension
+    ///
+    /// Entries without an extension are counted under the empty string.
+    pub fn size_by_extension(&self) -> HashMap<String, u64> {
+        let mut sizes = HashMap::new();
+        for entry in self.entries() {
+            *sizes
+                .entry(utils::lowercase_extension(entry.path()))
+                .or_insert(0) += entry.size_u64();
+        }
+        sizes
+    }
+
+    /// Gets the sum of all entry sizes in bytes
+    ///
+    /// Covers the entries listed by [`Self::entries`]; the per-extension
+    /// summaries above count the same entries.
+    pub fn total_size(&self) -> u64 {
+        self.entries().map(|entry| entry.size_u64()).sum()
+    }
+
+    /// Gets the size of the archive file in bytes
+    pub fn archive_size(&self) -> u64 {
+        self.archive_size
+    }
+
     /// Returns true if the archive uses encryption (PF8 only)
     pub fn is_encrypted(&self) -> bool {
         self.encryption_key.is_some()
     }
 
+    /// Gets the encryption key derived from the archive index (None for PF6)
+    pub fn encryption_key(&self) -> Option<&[u8]> {
+        self.encryption_key.as_deref()
+    }
+
+    /// Returns the offset where the index ends and entry data begins
+    pub(crate) fn data_start(&self) -> u64 {
+        self.data_start
+    }
+
+    /// Summarizes how the archive file is split between index, entry data
+    /// and unused bytes, see [`ArchiveStats`]
+    pub fn statistics(&self) -> ArchiveStats {
+        verify::statistics(self)
+    }
+
+    /// Checks the archive for internal inconsistencies
+    ///
+    /// See [`IntegrityReport`] for what is checked. Nothing is decrypted or
+    /// extracted, so this is cheap even for very large archives.
+    ///
+    /// Opening normally already fails on entries outside the data region and
+    /// on a wrong entry count; open with [`Strictness::Permissive`] to have
+    /// them reported here instead.
+    pub fn verify(&self) -> Result<IntegrityReport> {
+        verify::check_integrity(self)
+    }
+
+    /// Checks whether the key derived from the index equals `expected_hex`
+    ///
+    /// The comparison ignores case and surrounding whitespace. Since the key
+    /// is the SHA1 of the index, a match proves the index is unmodified.
+    /// Always false for PF6 archives, which have no key.
+    ///
+    /// A key supplied with [`ReaderOptions::key`] is not compared; only the
+    /// key derived from the index is.
+    pub fn key_matches(&self, expected_hex: &str) -> bool {
+        let Some(key) = self.index_key.as_deref() else {
+            return false;
+        };
+        let expected = expected_hex.trim();
+        expected.len() == key.len() * 2
+            && key
+                .iter()
+                .zip(expected.as_bytes().chunks(2))
+                .all(|(byte, pair)| {
+                    std::str::from_utf8(pair)
+                        .ok()
+                        .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                        == Some(*byte)
+                })
+    }
+
+    /// Returns the data region of every entry, sorted by offset and size
+    ///
+    /// Each region is `(path, offset, size)` and covers the bytes
+    /// `offset..offset + size` of the archive. Overlapping regions are not
+    /// merged; see [`check_layout`](crate::check_layout) to detect them.
+    pub fn data_layout(&self) -> Vec<(String, u32, u32)> {
+        let mut regions = self
+            .all_entries()
+            .map(|entry| {
+                (
+                    entry.path().display().to_string(),
+                    entry.offset(),
+                    entry.size(),
+                )
+            })
+            .collect::<Vec<_>>();
+        regions.sort_by_key(|&(_, offset, size)| (offset, size));
+        regions
+    }
+
+    /// Builds a manifest describing the archive contents
+    pub fn manifest(&self) -> Manifest {
+        Manifest::from_reader(self)
+    }
+
+    /// Compares this archive against `other`
+    ///
+    /// Entries are matched by path. Entries present in both archives are
+    /// reported as changed if their sizes differ or, for equal sizes, if
+    /// their decrypted data differs, which reads every such pair in full.
+    pub fn diff(&self, other: &Pf8Reader) -> Result<DiffReport> {
+        diff::diff_content(self, other)
+    }
+
+    /// Compares the indexes of this archive and `other` by path and size
+    ///
+    /// Like [`Self::diff`] without reading any data, so entries whose content
+    /// changed but whose size did not are not reported.
+    pub fn diff_index(&self, other: &Pf8Reader) -> DiffReport {
+        diff::diff_index(self, other)
+    }
+
     /// Gets a file entry by path
+    ///
+    /// Stored names and `path` are compared with `\` replaced by `/`, so
+    /// `a/b.txt` and `a\b.txt` refer to the same entry on every platform.
+    /// Letter case matters unless [`CaseSensitivity::Insensitive`] was
+    /// requested when opening; see [`Self::get_entry_ci`] for a one-off
+    /// case-insensitive lookup.
     pub fn get_entry<P: AsRef<Path>>(&self, path: P) -> Option<&Pf8Entry> {
-        let path_string = path.as_ref().to_string_lossy().to_string();
         self.entry_map
-            .get(&path_string)
+            .get(&lookup_key(path.as_ref(), self.case_sensitivity))
+            .map(|&index| &self.entries[index])
+    }
+
+    /// Gets a file entry by path, ignoring letter case
+    ///
+    /// Like [`Self::get_entry`], with stored names and `path` also
+    /// lowercased, so `Script/Main.ast` finds `script\main.ast`. If several
+    /// entries differ only by case, the last one in the index wins. The
+    /// lowercased lookup map is built on the first call.
+    pub fn get_entry_ci<P: AsRef<Path>>(&self, path: P) -> Option<&Pf8Entry> {
+        let map = match self.case_sensitivity {
+            CaseSensitivity::Insensitive => &self.entry_map,
+            CaseSensitivity::Sensitive => self.entry_map_ci.get_or_init(|| {
+                self.entries
+                    .iter()
+                    .enumerate()
+                    .map(|(index, entry)| {
+                        (
+                            lookup_key(entry.path(), CaseSensitivity::Insensitive),
+                            index,
+                        )
+                    })
+                    .collect()
+            }),
+        };
+        map.get(&lookup_key(path.as_ref(), CaseSensitivity::Insensitive))
             .map(|&index| &self.entries[index])
     }
 
@@ -114,14 +699,209 @@ impl Pf8Reader {
         self.get_entry(path).is_some()
     }
 
+    /// Borrows an entry's data directly from an in-memory archive
+    ///
+    /// Returns `None` if the entry does not exist, is encrypted, or the
+    /// reader is file-backed. Otherwise the slice points into the buffer
+    /// passed to [`Self::from_bytes`], without copying.
+    pub fn entry_slice<P: AsRef<Path>>(&self, path: P) -> Option<&[u8]> {
+        let entry = self.get_entry(path)?;
+        if entry.is_encrypted() {
+            return None;
+        }
+
+        match &self.source {
+            Source::Memory(cursor) => {
+                let start = entry.offset() as usize;
+                cursor.get_ref().get(start..start + entry.size() as usize)
+            }
+            Source::File(_) | Source::Stream(_) => None,
+        }
+    }
+
+    /// Returns the backing buffer of an in-memory archive
+    ///
+    /// Returns `None` for file-backed and stream-backed readers.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match &self.source {
+            Source::Memory(cursor) => Some(cursor.get_ref()),
+            Source::File(_) | Source::Stream(_) => None,
+        }
+    }
+
+    /// Calls `f` with every entry and its decrypted data, in index order
+    ///
+    /// Each entry is read in chunks and assembled before `f` is called, so
+    /// memory use is bounded by the largest entry. An error returned by `f`
+    /// stops the iteration and is returned as is.
+    pub fn for_each_entry<F>(&mut self, mut f: F) -> Result<()>
+    where
+        F: FnMut(&Pf8Entry, &[u8]) -> Result<()>,
+    {
+        let mut data = Vec::new();
+        for index in 0..self.entries.len() {
+            let entry = self.entries[index].clone();
+            data.clear();
+            self.entry_data(&entry)?.read_to_end(&mut data)?;
+            f(&entry, &data)?;
+        }
+        Ok(())
+    }
+
+    /// Streams the decrypted data of an entry
+    pub(crate) fn entry_data(&self, entry: &Pf8Entry) -> Result<EntryReader<'_>> {
+        let key = entry_key(entry, self.encryption_key.as_deref())?;
+
+        Ok(EntryReader {
+            source: &self.source,
+            key,
+            offset: entry.offset_u64(),
+            position: 0,
+            size: entry.size_u64(),
+        })
+    }
+
+    /// Opens a file for streaming as a [`Read`] + [`Seek`] source
+    ///
+    /// Data is read and decrypted lazily as it is pulled, so the entry can be
+    /// handed to a decoder without buffering it. Each reader tracks its own
+    /// position, so several can be open at once, even across threads.
+    pub fn open_entry<P: AsRef<Path>>(&self, path: P) -> Result<EntryReader<'_>> {
+        let path = path.as_ref();
+        let entry = self
+            .get_entry(path)
+            .ok_or_else(|| Error::FileNotFound(path.display().to_string()))?;
+        self.entry_data(entry)
+    }
+
+    /// Opens a file for streaming together with its metadata
+    ///
+    /// The size is known before any data is read, which suits serving the
+    /// entry with a `Content-Length` header. Data is decrypted as it is read.
+    pub fn open_entry_with_meta<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+    ) -> Result<(EntryMeta, EntryReader<'_>)> {
+        let path = path.as_ref();
+        let entry = self
+            .get_entry(path)
+            .cloned()
+            .ok_or_else(|| Error::FileNotFound(path.display().to_string()))?;
+
+        let meta = EntryMeta {
+            size: entry.size_u64(),
+            content_type: utils::content_type(entry.path()),
+        };
+        Ok((meta, self.entry_data(&entry)?))
+    }
+
+    /// Reads at most `n` bytes from the start of a file
+    ///
+    /// Only the prefix is read and decrypted, which makes this cheap for
+    /// sniffing headers (e.g. PNG or JPEG magic) of large entries.
+    pub fn read_file_prefix<P: AsRef<Path>>(&mut self, path: P, n: usize) -> Result<Vec<u8>> {
+        let path = path.as_ref();
+        let entry = self
+            .get_entry(path)
+            .cloned()
+            .ok_or_else(|| Error::FileNotFound(path.display().to_string()))?;
+
+        let mut prefix = Vec::with_capacity(n.min(entry.size() as usize));
+        self.entry_data(&entry)?
+            .take(n as u64)
+            .read_to_end(&mut prefix)?;
+        Ok(prefix)
+    }
+
+    /// Copies the stored bytes of a file to `writer` without decrypting them
+    ///
+    /// The bytes are exactly those on disk, so an encrypted entry stays
+    /// encrypted under this archive's key. Returns the number of bytes copied.
+    pub fn copy_raw_to<P: AsRef<Path>, W: Write>(
+        &mut self,
+        path: P,
+        writer: &mut W,
+    ) -> Result<u64> {
+        let path = path.as_ref();
+        let entry = self
+            .get_entry(path)
+            .cloned()
+            .ok_or_else(|| Error::FileNotFound(path.display().to_string()))?;
+
+        if entry.size() == 0 {
+            return Ok(0);
+        }
+
+        self.source.seek(SeekFrom::Start(entry.offset_u64()))?;
+        let copied = std::io::copy(&mut (&mut self.source).take(entry.size_u64()), writer)?;
+        if copied != entry.size_u64() {
+            return Err(Error::Corrupted(format!(
+                "Entry {} is truncated: expected {} bytes, found {}",
+                entry.pf8_path(),
+                entry.size(),
+                copied
+            )));
+        }
+        Ok(copied)
+    }
+
+    /// Reads at most `n` stored bytes from the start of a file without
+    /// decrypting them
+    ///
+    /// The raw counterpart of [`Self::read_file_prefix`]: only the prefix is
+    /// read, so inspecting the start of a large entry stays cheap.
+    pub fn read_file_raw_prefix<P: AsRef<Path>>(&self, path: P, n: usize) -> Result<Vec<u8>> {
+        let path = path.as_ref();
+        let entry = self
+            .get_entry(path)
+            .ok_or_else(|| Error::FileNotFound(path.display().to_string()))?;
+
+        let mut prefix = vec![0u8; n.min(entry.size() as usize)];
+        if !prefix.is_empty() {
+            self.source.read_exact_at(entry.offset_u64(), &mut prefix)?;
+        }
+        Ok(prefix)
+    }
+
     /// Reads a file's data by path
-    pub fn read_file<P: AsRef<Path>>(&mut self, path: P) -> Result<Vec<u8>> {
-        let mut result = Vec::new();
-        self.read_file_streaming(path, |chunk| {
-            result.extend_from_slice(chunk);
-            Ok(())
-        })?;
-        Ok(result)
+    ///
+    /// Only `&self` is needed, so a reader shared between threads can serve
+    /// reads concurrently. In-memory archives are sliced without locking;
+    /// for file-backed archives the seek and read are serialized.
+    pub fn read_file<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>> {
+        let entry = self
+            .get_entry(path)
+            .ok_or_else(|| Error::FileNotFound("File not found".to_string()))?;
+        let key = entry_key(entry, self.encryption_key.as_deref())?;
+
+        let mut data = self.read_stored(entry)?;
+        if let Some(key) = key {
+            crypto::encrypt(&mut data, key, 0)?;
+        }
+        Ok(data)
+    }
+
+    /// Fills `buf` from the given absolute archive offset
+    pub(crate) fn read_exact_at(&self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        Ok(self.source.read_exact_at(offset, buf)?)
+    }
+
+    /// Reads the stored bytes of an entry without decrypting them
+    pub(crate) fn read_stored(&self, entry: &Pf8Entry) -> Result<Vec<u8>> {
+        let mut data = vec![0u8; entry.size() as usize];
+        // Placeholder entries carry no data; their offset may point anywhere
+        if !data.is_empty() {
+            self.source.read_exact_at(entry.offset_u64(), &mut data)?;
+        }
+        Ok(data)
+    }
+
+    /// Checks whether a file's decrypted data is valid UTF-8
+    ///
+    /// For text entries this is a cheap plausibility check of the key: data
+    /// decrypted with the wrong key is almost never valid UTF-8.
+    pub fn entry_is_valid_utf8<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        Ok(std::str::from_utf8(&self.read_file(path)?).is_ok())
     }
 
     /// Reads a file's data with streaming to minimize memory allocation
@@ -136,23 +916,26 @@ impl Pf8Reader {
                 .ok_or_else(|| Error::FileNotFound("File not found".to_string()))?;
             (
                 entry.size() as usize,
-                entry.offset() as u64,
+                entry.offset_u64(),
                 entry.is_encrypted(),
             )
         };
 
-        self.file.seek(SeekFrom::Start(start_offset))?;
+        // Placeholder entries carry no data; their offset may point anywhere
+        if file_size == 0 {
+            return Ok(());
+        }
 
-        if file_size <= BUFFER_SIZE {
+        self.source.seek(SeekFrom::Start(start_offset))?;
+
+        if file_size <= self.buffer_size {
             // Small file: read directly
             let mut data = vec![0u8; file_size];
-            self.file.read_exact(&mut data)?;
+            self.source.read_exact(&mut data)?;
 
             if is_encrypted {
                 if let Some(key) = self.encryption_key.as_deref() {
-                    for (i, byte) in data.iter_mut().enumerate() {
-                        *byte ^= key[i % key.len()];
-                    }
+                    crypto::encrypt(&mut data, key, 0)?;
                 } else {
                     return Err(Error::Crypto(
                         "File is encrypted but no key provided".to_string(),
@@ -163,19 +946,17 @@ impl Pf8Reader {
             callback(&data)?;
         } else {
             // Large file: stream in chunks
-            let mut buffer = vec![0u8; BUFFER_SIZE];
+            let mut buffer = vec![0u8; self.buffer_size];
             let mut bytes_read = 0;
 
             while bytes_read < file_size {
-                let chunk_size = (file_size - bytes_read).min(BUFFER_SIZE);
-                self.file.read_exact(&mut buffer[..chunk_size])?;
+                let chunk_size = (file_size - bytes_read).min(self.buffer_size);
+                self.source.read_exact(&mut buffer[..chunk_size])?;
 
                 if is_encrypted {
                     if let Some(key) = self.encryption_key.as_deref() {
                         // Decrypt chunk in-place
-                        for (i, byte) in buffer[..chunk_size].iter_mut().enumerate() {
-                            *byte ^= key[(bytes_read + i) % key.len()];
-                        }
+                        crypto::encrypt(&mut buffer[..chunk_size], key, bytes_read)?;
                     } else {
                         return Err(Error::Crypto(
                             "File is encrypted but no key provided".to_string(),
@@ -202,12 +983,179 @@ impl Pf8Reader {
         &mut self,
         output_dir: P,
         handler: &mut H,
+    ) -> Result<()> {
+        let mut buffer = vec![0u8; self.buffer_size];
+        self.extract_all_into(output_dir.as_ref(), &mut buffer, handler)
+    }
+
+    /// Extracts all files using `threads` worker threads
+    ///
+    /// Entries are handed out to the workers one at a time, and each worker
+    /// reads through its own handle to the archive, so decryption and writes
+    /// of different entries run concurrently. `threads == 0` uses the
+    /// available parallelism.
+    ///
+    /// Handler events from different workers are serialized but interleave:
+    /// `processed_bytes` in progress reports is the total across all workers.
+    /// An [`ControlAction::Abort`] or error stops every worker at its next
+    /// chunk; output files already written are left in place.
+    ///
+    /// Of several entries with the same output path only the last one in the
+    /// index is extracted, so the result matches [`Self::extract_all`].
+    pub fn extract_all_parallel<P: AsRef<Path>, H: ArchiveHandler>(
+        &self,
+        output_dir: P,
+        threads: usize,
+        handler: &mut H,
     ) -> Result<()> {
         let output_dir = output_dir.as_ref();
-        let mut buffer = vec![0u8; BUFFER_SIZE];
 
+        if handler.on_started(OperationType::Unpack) == ControlAction::Abort {
+            return Err(Error::Cancelled);
+        }
+        self.warn_overlaps(handler)?;
+
+        // Two workers must never write the same file at once
+        let mut jobs: Vec<(&Pf8Entry, PathBuf)> = Vec::with_capacity(self.entries.len());
+        let mut job_by_path = HashMap::new();
+        for entry in &self.entries {
+            let file_path = self.output_path_for(output_dir, entry)?;
+            match job_by_path.get(&file_path) {
+                Some(&job) => jobs[job] = (entry, file_path),
+                None => {
+                    job_by_path.insert(file_path.clone(), jobs.len());
+                    jobs.push((entry, file_path));
+                }
+            }
+        }
+        let total_bytes: u64 = jobs.iter().map(|(entry, _)| entry.size_u64()).sum();
+        let total_files = jobs.len();
+
+        let threads = match threads {
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        }
+        .clamp(1, total_files.max(1));
+
+        let state = ParallelState {
+            next_entry: AtomicUsize::new(0),
+            processed_files: AtomicUsize::new(0),
+            processed_bytes: AtomicU64::new(0),
+            total_files,
+            total_bytes,
+            stopped: AtomicBool::new(false),
+            error: Mutex::new(None),
+            handler: Mutex::new(handler),
+        };
+
+        std::thread::scope(|scope| {
+            for _ in 0..threads {
+                scope.spawn(|| {
+                    if let Err(err) = self.extract_parallel_worker(&jobs, &state) {
+                        state.stop(err);
+                    }
+                });
+            }
+        });
+
+        if let Some(err) = state.error.into_inner().unwrap_or_else(|e| e.into_inner()) {
+            return Err(err);
+        }
+        let handler = state
+            .handler
+            .into_inner()
+            .unwrap_or_else(|e| e.into_inner());
+        handler.on_finished();
+
+        Ok(())
+    }
+
+    /// Extracts the jobs handed out by `state` until none are left
+    fn extract_parallel_worker<H: ArchiveHandler>(
+        &self,
+        jobs: &[(&Pf8Entry, PathBuf)],
+        state: &ParallelState<'_, H>,
+    ) -> Result<()> {
+        let source = self.source.worker()?;
+        let mut buffer = vec![0u8; self.buffer_size];
+
+        while !state.stopped.load(Ordering::Relaxed) {
+            let index = state.next_entry.fetch_add(1, Ordering::Relaxed);
+            let Some((entry, file_path)) = jobs.get(index) else {
+                return Ok(());
+            };
+            let entry_name = entry.path().to_string_lossy().to_string();
+            let key = entry_key(entry, self.encryption_key.as_deref())?;
+
+            state.notify(|handler| handler.on_entry_started(&entry_name))?;
+
+            if let Some(parent) = file_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let mut output_file = File::create(file_path)?;
+
+            let file_size = entry.size() as usize;
+            let mut bytes_written = 0;
+            while bytes_written < file_size {
+                if state.stopped.load(Ordering::Relaxed) {
+                    return Ok(());
+                }
+                let chunk_size = (file_size - bytes_written).min(buffer.len());
+                let chunk = &mut buffer[..chunk_size];
+                source.read_exact_at(entry.offset_u64() + bytes_written as u64, chunk)?;
+                if let Some(key) = key {
+                    crypto::encrypt(chunk, key, bytes_written)?;
+                }
+                output_file.write_all(chunk)?;
+                bytes_written += chunk_size;
+
+                let processed_bytes = state
+                    .processed_bytes
+                    .fetch_add(chunk_size as u64, Ordering::Relaxed)
+                    + chunk_size as u64;
+                let progress = ProgressInfo {
+                    phase: OperationPhase::Writing,
+                    processed_bytes,
+                    total_bytes: Some(state.total_bytes),
+                    processed_files: state.processed_files.load(Ordering::Relaxed),
+                    total_files: Some(state.total_files),
+                    current_file: entry_name.clone(),
+                };
+                state.notify(|handler| handler.on_progress(&progress))?;
+            }
+
+            state.processed_files.fetch_add(1, Ordering::Relaxed);
+            state.notify(|handler| handler.on_entry_finished(&entry_name))?;
+        }
+
+        Ok(())
+    }
+
+    /// Extracts all files using a caller-provided scratch buffer
+    ///
+    /// A long-lived worker can pass the same buffer to every extraction
+    /// instead of allocating a fresh buffer each time. The buffer is grown to
+    /// [`ReaderOptions::buffer_size`] if smaller and never shrunk.
+    pub fn extract_all_with_buffer<P: AsRef<Path>>(
+        &mut self,
+        output_dir: P,
+        scratch: &mut Vec<u8>,
+    ) -> Result<()> {
+        if scratch.len() < self.buffer_size {
+            scratch.resize(self.buffer_size, 0);
+        }
+        self.extract_all_into(output_dir.as_ref(), scratch, &mut NoOpHandler)
+    }
+
+    /// Extracts all files through `buffer`, which must not be empty
+    fn extract_all_into<H: ArchiveHandler>(
+        &mut self,
+        output_dir: &Path,
+        buffer: &mut [u8],
+        handler: &mut H,
+    ) -> Result<()> {
         // Calculate total bytes
-        let total_bytes: u64 = self.entries.iter().map(|e| e.size() as u64).sum();
+        let total_bytes: u64 = self.entries.iter().map(|e| e.size_u64()).sum();
         let total_files = self.entries.len();
         let mut total_bytes_processed = 0u64;
 
@@ -215,9 +1163,15 @@ impl Pf8Reader {
         if handler.on_started(OperationType::Unpack) == ControlAction::Abort {
             return Err(Error::Cancelled);
         }
+        self.warn_overlaps(handler)?;
 
-        for (index, entry) in self.entries.clone().iter().enumerate() {
-            let file_path = output_dir.join(entry.path());
+        let mut entries = self.entries.clone();
+        if self.offset_order {
+            entries.sort_by_key(|entry| entry.offset());
+        }
+
+        for (index, entry) in entries.iter().enumerate() {
+            let file_path = self.output_path_for(output_dir, entry)?;
             let entry_name = entry.path().to_string_lossy().to_string();
 
             // Notify entry started
@@ -234,7 +1188,7 @@ impl Pf8Reader {
             let bytes_written = self.extract_entry_with_progress(
                 entry,
                 &file_path,
-                &mut buffer,
+                buffer,
                 index + 1,
                 total_files,
                 total_bytes_processed,
@@ -256,6 +1210,177 @@ impl Pf8Reader {
         Ok(())
     }
 
+    /// Returns an iterator extracting one entry per call to `next`
+    ///
+    /// Entries are visited in the same order as [`Self::extract_all`], and
+    /// each item describes the file just written. The caller drives the pace
+    /// and may stop at any point; entries not yet reached are not written.
+    pub fn extract_iter<P: AsRef<Path>>(&mut self, output_dir: P) -> ExtractIter<'_> {
+        let mut entries = self.entries.clone();
+        if self.offset_order {
+            entries.sort_by_key(|entry| entry.offset());
+        }
+
+        ExtractIter {
+            reader: self,
+            output_dir: output_dir.as_ref().to_path_buf(),
+            entries: entries.into_iter(),
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Resolves where each entry would be written by [`Self::extract_with_options`]
+    ///
+    /// Entries filtered out by the include/exclude patterns or
+    /// `strip_components` are omitted. Entries mapping to the same output
+    /// path are all listed, in extraction order, so the last one wins. Nothing
+    /// is written, and existing files are not checked against
+    /// [`ExtractOptions::overwrite`].
+    pub fn plan_extraction<P: AsRef<Path>>(
+        &self,
+        output_dir: P,
+        options: &ExtractOptions,
+    ) -> Result<Vec<(Pf8Entry, PathBuf)>> {
+        let output_dir = output_dir.as_ref();
+        let filter = options.compile()?;
+
+        let mut planned = Vec::new();
+        for entry in &self.entries {
+            if let Some(relative) = filter.output_relative(entry) {
+                planned.push((
+                    entry.clone(),
+                    self.output_path_under(output_dir, &relative)?,
+                ));
+            }
+        }
+        Ok(planned)
+    }
+
+    /// Extracts the entries whose archive path matches the glob `pattern`
+    ///
+    /// Paths are matched with `/` separators, e.g. `script/**/*.ast`; see
+    /// [`ExtractOptions`] for the glob rules. Fails with
+    /// [`Error::FileNotFound`] before creating anything if no entry matches.
+    pub fn extract_matching<P: AsRef<Path>, H: ArchiveHandler>(
+        &self,
+        output_dir: P,
+        pattern: &str,
+        handler: &mut H,
+    ) -> Result<UnpackResult> {
+        let options = ExtractOptions {
+            include: vec![pattern.to_string()],
+            ..Default::default()
+        };
+        if self.plan_extraction(&output_dir, &options)?.is_empty() {
+            return Err(Error::FileNotFound(format!("no entries match {pattern:?}")));
+        }
+        self.extract_with_options(output_dir, &options, handler)
+    }
+
+    /// Extracts the entries selected by `options` with progress reporting
+    ///
+    /// Entries filtered out by the include/exclude patterns or
+    /// `strip_components`, and existing files kept by
+    /// [`OverwritePolicy::Skip`], are counted in [`UnpackResult::skipped`].
+    pub fn extract_with_options<P: AsRef<Path>, H: ArchiveHandler>(
+        &self,
+        output_dir: P,
+        options: &ExtractOptions,
+        handler: &mut H,
+    ) -> Result<UnpackResult> {
+        let selected = self.plan_extraction(output_dir, options)?;
+        let filtered = self.entries.len() - selected.len();
+        let mut result = self.extract_planned(&selected, options.overwrite, handler)?;
+        result.skipped += filtered;
+        Ok(result)
+    }
+
+    /// Warns `handler` about every pair of entries with partially overlapping data
+    ///
+    /// Such entries share bytes they should not, so at least one of them is
+    /// extracted with wrong contents. Deduplicated entries, which share their
+    /// whole range, are not reported.
+    fn warn_overlaps<H: ArchiveHandler>(&self, handler: &mut H) -> Result<()> {
+        for (first, second) in verify::check_layout(self).overlaps {
+            let message = format!(
+                "Data of {} overlaps {}; extracted contents may be wrong",
+                second, first
+            );
+            if handler.on_warning(&message) == ControlAction::Abort {
+                return Err(Error::Cancelled);
+            }
+        }
+        Ok(())
+    }
+
+    /// Extracts entries to the paths planned by [`Self::plan_extraction`]
+    ///
+    /// Only existing files kept by [`OverwritePolicy::Skip`] are counted in
+    /// [`UnpackResult::skipped`].
+    pub(crate) fn extract_planned<H: ArchiveHandler>(
+        &self,
+        selected: &[(Pf8Entry, PathBuf)],
+        overwrite: OverwritePolicy,
+        handler: &mut H,
+    ) -> Result<UnpackResult> {
+        let mut result = UnpackResult::default();
+
+        let total_bytes: u64 = selected.iter().map(|(e, _)| e.size_u64()).sum();
+        let total_files = selected.len();
+        let mut buffer = vec![0u8; self.buffer_size];
+
+        if handler.on_started(OperationType::Unpack) == ControlAction::Abort {
+            return Err(Error::Cancelled);
+        }
+        self.warn_overlaps(handler)?;
+
+        for (index, (entry, file_path)) in selected.iter().enumerate() {
+            if file_path.exists() {
+                match overwrite {
+                    OverwritePolicy::Overwrite => {}
+                    OverwritePolicy::Skip => {
+                        result.skipped += 1;
+                        continue;
+                    }
+                    OverwritePolicy::Error => {
+                        return Err(Error::Io(std::io::Error::new(
+                            std::io::ErrorKind::AlreadyExists,
+                            format!("Output file already exists: {}", file_path.display()),
+                        )));
+                    }
+                }
+            }
+
+            let entry_name = entry.path().to_string_lossy().to_string();
+            if handler.on_entry_started(&entry_name) == ControlAction::Abort {
+                return Err(Error::Cancelled);
+            }
+
+            if let Some(parent) = file_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            result.bytes += self.extract_entry_with_progress(
+                entry,
+                file_path,
+                &mut buffer,
+                index + 1,
+                total_files,
+                result.bytes,
+                total_bytes,
+                handler,
+            )?;
+            result.files += 1;
+
+            if handler.on_entry_finished(&entry_name) == ControlAction::Abort {
+                return Err(Error::Cancelled);
+            }
+        }
+
+        handler.on_finished();
+        Ok(result)
+    }
+
     /// Extracts a single file with progress reporting
     pub fn extract_file_with_progress<P: AsRef<Path>, Q: AsRef<Path>, H: ArchiveHandler>(
         &mut self,
@@ -274,8 +1399,8 @@ impl Pf8Reader {
             .ok_or_else(|| Error::FileNotFound("File not found".to_string()))?
             .clone();
 
-        let mut buffer = vec![0u8; BUFFER_SIZE];
-        let total_bytes = entry.size() as u64;
+        let mut buffer = vec![0u8; self.buffer_size];
+        let total_bytes = entry.size_u64();
         let entry_name = entry.path().to_string_lossy().to_string();
 
         // Notify task started
@@ -311,10 +1436,83 @@ impl Pf8Reader {
         Ok(())
     }
 
+    /// Extracts a single file by decrypting directly into a memory map of
+    /// the output file
+    ///
+    /// The output is created at its final size up front and the OS writes
+    /// the mapped pages back, which avoids copying very large entries
+    /// through an intermediate buffer. An existing file at `output_path` is
+    /// replaced.
+    #[cfg(feature = "mmap")]
+    pub fn extract_file_mmap<P: AsRef<Path>, Q: AsRef<Path>>(
+        &mut self,
+        archive_path: P,
+        output_path: Q,
+    ) -> Result<()> {
+        let output_path = output_path.as_ref();
+        let entry = self
+            .get_entry(&archive_path)
+            .ok_or_else(|| Error::FileNotFound("File not found".to_string()))?;
+        let key = entry_key(entry, self.encryption_key.as_deref())?;
+
+        if let Some(parent) = output_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let output_file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(output_path)?;
+        output_file.set_len(entry.size_u64())?;
+
+        // Mapping an empty file fails, and there is nothing to write
+        if entry.size() == 0 {
+            return Ok(());
+        }
+
+        // SAFETY: the file was just created by us and is not resized while
+        // mapped; concurrent modification by other processes is the
+        // caller's responsibility, as with any memory-mapped file.
+        let mut map = unsafe { memmap2::MmapMut::map_mut(&output_file)? };
+        self.source.read_exact_at(entry.offset_u64(), &mut map)?;
+        if let Some(key) = key {
+            crypto::encrypt(&mut map, key, 0)?;
+        }
+        map.flush()?;
+
+        Ok(())
+    }
+
+    /// Resolves the output path of an entry, validating its name
+    pub(crate) fn output_path_for(&self, output_dir: &Path, entry: &Pf8Entry) -> Result<PathBuf> {
+        self.output_path_under(output_dir, entry.path())
+    }
+
+    /// Resolves an entry-relative output path, validating its name
+    fn output_path_under(&self, output_dir: &Path, relative: &Path) -> Result<PathBuf> {
+        let name = relative.to_string_lossy();
+        if !utils::has_control_chars(&name) {
+            return Ok(output_dir.join(relative));
+        }
+
+        if !self.sanitize_names {
+            return Err(Error::UnsafePath(format!(
+                "entry name contains control characters: {name:?}"
+            )));
+        }
+
+        let sanitized: PathBuf = relative
+            .iter()
+            .map(|component| utils::sanitize_control_chars(&component.to_string_lossy()))
+            .collect();
+        Ok(output_dir.join(sanitized))
+    }
+
     /// Extracts a single entry using streaming with progress reporting
     #[allow(clippy::too_many_arguments)]
     fn extract_entry_with_progress<P: AsRef<Path>, H: ArchiveHandler>(
-        &mut self,
+        &self,
         entry: &Pf8Entry,
         output_path: P,
         buffer: &mut [u8],
@@ -332,25 +1530,24 @@ impl Pf8Reader {
         let (file_size, start_offset, is_encrypted) = {
             (
                 entry.size() as usize,
-                entry.offset() as u64,
+                entry.offset_u64(),
                 entry.is_encrypted(),
             )
         };
 
-        self.file.seek(SeekFrom::Start(start_offset))?;
-
         let mut current_file_bytes = 0u64;
 
         if file_size <= buffer.len() {
-            // Small file: read directly into buffer
+            // Small file: read directly into buffer. Zero-size placeholder
+            // entries skip the seek and read, leaving an empty output file.
             let mut temp_buffer = vec![0u8; file_size];
-            self.file.read_exact(&mut temp_buffer)?;
+            if file_size > 0 {
+                self.source.read_exact_at(start_offset, &mut temp_buffer)?;
+            }
 
             if is_encrypted {
                 if let Some(key) = self.encryption_key.as_deref() {
-                    for (i, byte) in temp_buffer.iter_mut().enumerate() {
-                        *byte ^= key[i % key.len()];
-                    }
+                    crypto::encrypt(&mut temp_buffer, key, 0)?;
                 } else {
                     return Err(Error::Crypto(
                         "File is encrypted but no key provided".to_string(),
@@ -363,6 +1560,7 @@ impl Pf8Reader {
 
             // Report progress
             let progress = ProgressInfo {
+                phase: OperationPhase::Writing,
                 processed_bytes: total_bytes_processed + current_file_bytes,
                 total_bytes: Some(total_bytes),
                 processed_files,
@@ -379,13 +1577,14 @@ impl Pf8Reader {
 
             while bytes_written < file_size {
                 let chunk_size = (file_size - bytes_written).min(buffer_size);
-                self.file.read_exact(&mut buffer[..chunk_size])?;
+                self.source.read_exact_at(
+                    start_offset + bytes_written as u64,
+                    &mut buffer[..chunk_size],
+                )?;
 
                 if is_encrypted {
                     if let Some(key) = self.encryption_key.as_deref() {
-                        for (i, byte) in buffer[..chunk_size].iter_mut().enumerate() {
-                            *byte ^= key[(bytes_written + i) % key.len()];
-                        }
+                        crypto::encrypt(&mut buffer[..chunk_size], key, bytes_written)?;
                     } else {
                         return Err(Error::Crypto(
                             "File is encrypted but no key provided".to_string(),
@@ -399,6 +1598,7 @@ impl Pf8Reader {
 
                 // Report progress
                 let progress = ProgressInfo {
+                    phase: OperationPhase::Writing,
                     processed_bytes: total_bytes_processed + current_file_bytes,
                     total_bytes: Some(total_bytes),
                     processed_files,
@@ -414,3 +1614,337 @@ impl Pf8Reader {
         Ok(current_file_bytes)
     }
 }
+
+/// Opens an archive file, optionally refusing to follow a symlink at `path`
+///
+/// On Unix the no-follow open uses `O_NOFOLLOW`, so the check and the open
+/// are a single atomic step. Elsewhere the path is checked with
+/// [`std::fs::symlink_metadata`] before opening.
+fn open_file(path: &Path, follow_symlinks: bool) -> Result<File> {
+    if follow_symlinks {
+        return Ok(File::open(path)?);
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        Ok(std::fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NOFOLLOW)
+            .open(path)?)
+    }
+
+    #[cfg(not(unix))]
+    {
+        if std::fs::symlink_metadata(path)?.file_type().is_symlink() {
+            return Err(Error::UnsafePath(format!(
+                "Refusing to open symlinked archive: {}",
+                path.display()
+            )));
+        }
+        Ok(File::open(path)?)
+    }
+}
+
+/// Returns the `entry_map` key for `path`
+fn lookup_key(path: &Path, case_sensitivity: CaseSensitivity) -> String {
+    let key = utils::normalize_lookup_path(path);
+    match case_sensitivity {
+        CaseSensitivity::Sensitive => key,
+        CaseSensitivity::Insensitive => key.to_lowercase(),
+    }
+}
+
+/// Returns the key needed to decrypt an entry, if any
+fn entry_key<'k>(entry: &Pf8Entry, key: Option<&'k [u8]>) -> Result<Option<&'k [u8]>> {
+    match (entry.is_encrypted(), key) {
+        (true, Some(key)) => crypto::check_key(key).map(|()| Some(key)),
+        (true, None) => Err(Error::Crypto(
+            "File is encrypted but no key provided".to_string(),
+        )),
+        (false, _) => Ok(None),
+    }
+}
+
+/// State shared by the workers of [`Pf8Reader::extract_all_parallel`]
+struct ParallelState<'h, H> {
+    /// Index of the next job to hand out
+    next_entry: AtomicUsize,
+    processed_files: AtomicUsize,
+    processed_bytes: AtomicU64,
+    total_files: usize,
+    total_bytes: u64,
+    /// Set once any worker fails or the handler aborts
+    stopped: AtomicBool,
+    /// First error reported by a worker
+    error: Mutex<Option<Error>>,
+    handler: Mutex<&'h mut H>,
+}
+
+impl<H: ArchiveHandler> ParallelState<'_, H> {
+    /// Calls the handler, turning an abort into [`Error::Cancelled`]
+    fn notify(&self, event: impl FnOnce(&mut H) -> ControlAction) -> Result<()> {
+        let mut handler = self.handler.lock().unwrap_or_else(|e| e.into_inner());
+        match event(&mut handler) {
+            ControlAction::Continue => Ok(()),
+            ControlAction::Abort => Err(Error::Cancelled),
+        }
+    }
+
+    /// Stops all workers, keeping the first error
+    fn stop(&self, err: Error) {
+        self.stopped.store(true, Ordering::Relaxed);
+        self.error
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_or_insert(err);
+    }
+}
+
+/// Per-worker view of a [`Source`]
+enum WorkerSource<'a> {
+    /// A separate handle to the archive file, read with positional reads
+    #[cfg(any(unix, windows))]
+    File(File),
+    /// The reader's own source
+    Shared(&'a Source),
+}
+
+impl WorkerSource<'_> {
+    /// Fills `buf` from the given absolute offset
+    fn read_exact_at(&self, offset: u64, buf: &mut [u8]) -> std::io::Result<()> {
+        match self {
+            #[cfg(unix)]
+            WorkerSource::File(file) => {
+                std::os::unix::fs::FileExt::read_exact_at(file, buf, offset)
+            }
+            #[cfg(windows)]
+            WorkerSource::File(file) => {
+                use std::os::windows::fs::FileExt;
+                let mut filled = 0;
+                while filled < buf.len() {
+                    match file.seek_read(&mut buf[filled..], offset + filled as u64)? {
+                        0 => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+                        n => filled += n,
+                    }
+                }
+                Ok(())
+            }
+            WorkerSource::Shared(source) => source.read_exact_at(offset, buf),
+        }
+    }
+}
+
+/// Storage an archive is read from
+enum Source {
+    /// Archive file on disk, read on demand
+    ///
+    /// The lock is only taken by `&self` reads; `&mut self` access goes
+    /// through [`Mutex::get_mut`].
+    File(Mutex<File>),
+    /// Archive held entirely in memory
+    Memory(Cursor<Vec<u8>>),
+    /// Archive read from a caller-provided source, locked like `File`
+    Stream(Mutex<Box<dyn ReadSeek>>),
+}
+
+/// Byte sources accepted by [`Pf8Reader::from_reader`]
+trait ReadSeek: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> ReadSeek for T {}
+
+impl Source {
+    /// Fills `buf` from the given absolute offset without moving the shared cursor
+    fn read_exact_at(&self, offset: u64, buf: &mut [u8]) -> std::io::Result<()> {
+        match self {
+            Source::File(file) => {
+                // The file position is always set before reading, so a
+                // poisoned lock leaves nothing inconsistent behind
+                let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+                file.seek(SeekFrom::Start(offset))?;
+                file.read_exact(buf)
+            }
+            Source::Stream(stream) => {
+                let mut stream = stream.lock().unwrap_or_else(|e| e.into_inner());
+                stream.seek(SeekFrom::Start(offset))?;
+                stream.read_exact(buf)
+            }
+            Source::Memory(cursor) => {
+                let data = cursor.get_ref();
+                let start = usize::try_from(offset).unwrap_or(usize::MAX);
+                let bytes = start
+                    .checked_add(buf.len())
+                    .and_then(|end| data.get(start..end))
+                    .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::UnexpectedEof))?;
+                buf.copy_from_slice(bytes);
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns a view for one worker of a parallel extraction
+    ///
+    /// File sources get a duplicated handle so workers do not contend for
+    /// the lock; memory sources need no locking, and caller-provided
+    /// streams cannot be duplicated, so both are shared.
+    fn worker(&self) -> std::io::Result<WorkerSource<'_>> {
+        match self {
+            #[cfg(any(unix, windows))]
+            Source::File(file) => {
+                let file = file.lock().unwrap_or_else(|e| e.into_inner());
+                Ok(WorkerSource::File(file.try_clone()?))
+            }
+            _ => Ok(WorkerSource::Shared(self)),
+        }
+    }
+
+    fn file_mut(file: &mut Mutex<File>) -> &mut File {
+        file.get_mut().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn stream_mut(stream: &mut Mutex<Box<dyn ReadSeek>>) -> &mut dyn ReadSeek {
+        stream.get_mut().unwrap_or_else(|e| e.into_inner()).as_mut()
+    }
+}
+
+impl Read for Source {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Source::File(file) => Source::file_mut(file).read(buf),
+            Source::Memory(cursor) => cursor.read(buf),
+            Source::Stream(stream) => Source::stream_mut(stream).read(buf),
+        }
+    }
+}
+
+impl Seek for Source {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            Source::File(file) => Source::file_mut(file).seek(pos),
+            Source::Memory(cursor) => cursor.seek(pos),
+            Source::Stream(stream) => Source::stream_mut(stream).seek(pos),
+        }
+    }
+}
+
+/// A file written by [`ExtractIter`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtractedFile {
+    /// The extracted entry
+    pub entry: Pf8Entry,
+    /// Path the entry was written to
+    pub path: PathBuf,
+    /// Number of bytes written
+    pub bytes: u64,
+}
+
+/// Iterator returned by [`Pf8Reader::extract_iter`]
+pub struct ExtractIter<'a> {
+    reader: &'a mut Pf8Reader,
+    output_dir: PathBuf,
+    entries: std::vec::IntoIter<Pf8Entry>,
+    /// Scratch buffer, allocated on the first entry
+    buffer: Vec<u8>,
+}
+
+impl ExtractIter<'_> {
+    fn extract(&mut self, entry: Pf8Entry) -> Result<ExtractedFile> {
+        let path = self.reader.output_path_for(&self.output_dir, &entry)?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        if self.buffer.is_empty() {
+            self.buffer = vec![0u8; self.reader.buffer_size];
+        }
+        let bytes = self.reader.extract_entry_with_progress(
+            &entry,
+            &path,
+            &mut self.buffer,
+            0,
+            0,
+            0,
+            0,
+            &mut NoOpHandler,
+        )?;
+
+        Ok(ExtractedFile { entry, path, bytes })
+    }
+}
+
+impl Iterator for ExtractIter<'_> {
+    type Item = Result<ExtractedFile>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.entries.next()?;
+        Some(self.extract(entry))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+/// Metadata returned by [`Pf8Reader::open_entry_with_meta`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryMeta {
+    /// Size of the entry data in bytes
+    pub size: u64,
+    /// MIME type guessed from the file extension
+    pub content_type: &'static str,
+}
+
+/// Reader over the decrypted data of a single entry
+///
+/// Returned by [`Pf8Reader::open_entry`] and
+/// [`Pf8Reader::open_entry_with_meta`]; it borrows the reader until dropped.
+/// Seeking moves within the entry, with position 0 at its first byte.
+pub struct EntryReader<'a> {
+    source: &'a Source,
+    /// Key for encrypted entries
+    key: Option<&'a [u8]>,
+    /// Offset of the entry data in the archive
+    offset: u64,
+    /// Current position within the entry
+    position: u64,
+    /// Size of the entry
+    size: u64,
+}
+
+impl Read for EntryReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let remaining = self.size.saturating_sub(self.position);
+        let len = (buf.len() as u64).min(remaining) as usize;
+        if len == 0 {
+            return Ok(0);
+        }
+
+        self.source
+            .read_exact_at(self.offset + self.position, &mut buf[..len])?;
+        if let Some(key) = self.key {
+            // XOR is symmetric, so encrypting at the same position decrypts
+            crypto::encrypt(&mut buf[..len], key, self.position as usize)
+                .map_err(std::io::Error::other)?;
+        }
+        self.position += len as u64;
+        Ok(len)
+    }
+}
+
+impl Seek for EntryReader<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.size.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta),
+        };
+        // Like files, seeking past the end is allowed and reads nothing
+        self.position = target.ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.position)
+    }
+}
@@ -0,0 +1,347 @@
+//! Rewriting archives from the entries of existing archives.
+
+use crate::archive::PackResult;
+use crate::builder::Pf8Builder;
+use crate::encoding::NameEncoding;
+use crate::entry::Pf8Entry;
+use crate::error::{Error, Result};
+use crate::format::{self, ArchiveFormat};
+use crate::reader::Pf8Reader;
+use crate::utils;
+use crate::writer::Pf8Writer;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How [`merge`] resolves an entry path present in more than one source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictPolicy {
+    /// Keep the entry from the earliest source
+    Skip,
+    /// Keep the entry from the latest source
+    Overwrite,
+    /// Fail with [`Error::DuplicateEntry`]
+    #[default]
+    Error,
+}
+
+/// Merges several archives into a single PF8 archive
+///
+/// Entries are streamed decrypted from each source and written under the key
+/// of the new archive, so sources may be PF6 or PF8. Encryption follows the
+/// default extension filter, as when packing. Entries whose path appears in
+/// more than one source are resolved by `on_conflict`.
+pub fn merge<P: AsRef<Path>, Q: AsRef<Path>>(
+    sources: &[P],
+    output_path: Q,
+    on_conflict: ConflictPolicy,
+) -> Result<PackResult> {
+    let mut readers = sources
+        .iter()
+        .map(Pf8Reader::open)
+        .collect::<Result<Vec<_>>>()?;
+
+    // Data origin and source entry per output entry
+    let mut selected: Vec<(Origin, Pf8Entry)> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+
+    for (reader_index, reader) in readers.iter().enumerate() {
+        for entry in reader.all_entries() {
+            let key = utils::normalize_lookup_path(entry.path());
+            match positions.get(&key) {
+                None => {
+                    positions.insert(key, selected.len());
+                    selected.push((Origin::Archive(reader_index), entry.clone()));
+                }
+                Some(&position) => match on_conflict {
+                    ConflictPolicy::Skip => {}
+                    ConflictPolicy::Overwrite => {
+                        selected[position] = (Origin::Archive(reader_index), entry.clone())
+                    }
+                    ConflictPolicy::Error => {
+                        return Err(Error::DuplicateEntry(format!(
+                            "{} (in {})",
+                            key,
+                            sources[reader_index].as_ref().display()
+                        )));
+                    }
+                },
+            }
+        }
+    }
+
+    write_entries(
+        &mut readers,
+        selected,
+        Layout::fresh(),
+        output_path.as_ref(),
+    )
+}
+
+/// Replaces several entries of an archive in a single rewrite
+///
+/// Every entry named in `replacements` takes its data from the given file;
+/// all other entries are streamed decrypted from `input_path`. The output is
+/// written once, under the key of the new index, so replacements may change
+/// entry sizes. The format, name encoding and encryption of each entry are
+/// kept. Names are matched like [`Pf8Reader::get_entry`] and must exist in
+/// the archive; `output_path` must differ from `input_path`.
+pub fn patch<P: AsRef<Path>, Q: AsRef<Path>>(
+    input_path: P,
+    output_path: Q,
+    replacements: &[(&str, &Path)],
+) -> Result<PackResult> {
+    let input_path = input_path.as_ref();
+    let output_path = output_path.as_ref();
+    if output_path.exists() && fs::canonicalize(input_path)? == fs::canonicalize(output_path)? {
+        return Err(Error::InvalidFormat(format!(
+            "Cannot patch {} in place",
+            input_path.display()
+        )));
+    }
+
+    let reader = Pf8Reader::open(input_path)?;
+    let mut sources: HashMap<String, &Path> = HashMap::new();
+    for &(name, source_path) in replacements {
+        let entry = reader
+            .get_entry(name)
+            .ok_or_else(|| Error::FileNotFound(name.to_string()))?;
+        sources.insert(utils::normalize_lookup_path(entry.path()), source_path);
+    }
+
+    let mut selected = Vec::with_capacity(reader.len());
+    for entry in reader.all_entries() {
+        match sources.get(&utils::normalize_lookup_path(entry.path())) {
+            Some(source_path) => selected.push(file_source(
+                entry.path(),
+                source_path,
+                entry.is_encrypted(),
+            )?),
+            None => selected.push((Origin::Archive(0), entry.clone())),
+        }
+    }
+
+    let layout = Layout::of(&reader);
+    write_entries(&mut [reader], selected, layout, output_path)
+}
+
+/// Writes the entries of `reader` except those named in `paths` as a new
+/// archive
+///
+/// Names are matched like [`Pf8Reader::get_entry`] and must exist in the
+/// archive. The format, name encoding and encryption of each entry are kept.
+/// The archive is written to `<output_path>.tmp` and renamed into place, so
+/// `output_path` may be the archive `reader` was opened from.
+pub(crate) fn remove_entries<S: AsRef<Path>>(
+    reader: &mut Pf8Reader,
+    paths: &[S],
+    output_path: &Path,
+) -> Result<PackResult> {
+    let mut removed = HashSet::new();
+    for path in paths {
+        let path = path.as_ref();
+        let entry = reader
+            .get_entry(path)
+            .ok_or_else(|| Error::FileNotFound(path.display().to_string()))?;
+        removed.insert(utils::normalize_lookup_path(entry.path()));
+    }
+
+    let selected = reader
+        .all_entries()
+        .filter(|entry| !removed.contains(&utils::normalize_lookup_path(entry.path())))
+        .map(|entry| (Origin::Archive(0), entry.clone()))
+        .collect();
+
+    write_entries_atomic(reader, selected, output_path)
+}
+
+/// Writes the entries of `reader` as a new archive, replacing and adding
+/// the files found under `dir`
+///
+/// Files are matched to entries by their path relative to `dir`, like
+/// [`Pf8Reader::get_entry`]. The format and name encoding are kept, as is
+/// the encryption of existing entries; new entries are encrypted following
+/// the patterns `reader` was opened with. The archive is written like
+/// [`remove_entries`], so `output_path` may be the archive being read.
+pub(crate) fn update_from_dir(
+    reader: &mut Pf8Reader,
+    dir: &Path,
+    output_path: &Path,
+) -> Result<PackResult> {
+    let mut files = Pf8Builder::new();
+    files.error_on_empty_dir(true).add_dir(dir)?;
+
+    let mut sources: HashMap<String, (&Path, &Path)> = files
+        .files()
+        .map(|(source_path, archive_path)| {
+            (
+                utils::normalize_lookup_path(archive_path),
+                (source_path, archive_path),
+            )
+        })
+        .collect();
+
+    let mut selected = Vec::with_capacity(reader.len() + sources.len());
+    for entry in reader.all_entries() {
+        match sources.remove(&utils::normalize_lookup_path(entry.path())) {
+            Some((source_path, _)) => selected.push(file_source(
+                entry.path(),
+                source_path,
+                entry.is_encrypted(),
+            )?),
+            None => selected.push((Origin::Archive(0), entry.clone())),
+        }
+    }
+    for (source_path, archive_path) in sources.into_values() {
+        let encrypted = reader.encrypts(archive_path);
+        selected.push(file_source(archive_path, source_path, encrypted)?);
+    }
+
+    write_entries_atomic(reader, selected, output_path)
+}
+
+/// Where the data of an output entry comes from
+enum Origin {
+    /// The source entry in the reader at this index
+    Archive(usize),
+    /// A file on disk
+    File(PathBuf),
+}
+
+/// How a rewritten archive stores its entries
+struct Layout {
+    format: ArchiveFormat,
+    name_encoding: NameEncoding,
+    /// Whether each entry keeps the encryption of its source entry, instead
+    /// of following the default extension filter
+    keep_encryption: bool,
+}
+
+impl Layout {
+    /// Layout of an archive rewritten from `reader`
+    fn of(reader: &Pf8Reader) -> Self {
+        Self {
+            format: reader.format(),
+            name_encoding: reader.name_encoding(),
+            keep_encryption: true,
+        }
+    }
+
+    /// Layout of a new PF8 archive, as written by [`Pf8Builder`]
+    fn fresh() -> Self {
+        Self {
+            format: ArchiveFormat::Pf8,
+            name_encoding: NameEncoding::default(),
+            keep_encryption: false,
+        }
+    }
+}
+
+/// Returns a file on disk as the data of an entry at `archive_path`
+fn file_source(
+    archive_path: &Path,
+    source_path: &Path,
+    encrypted: bool,
+) -> Result<(Origin, Pf8Entry)> {
+    let size = fs::metadata(source_path)?.len();
+    let size = u32::try_from(size).map_err(|_| {
+        Error::InvalidFormat(format!(
+            "File too large: {} bytes (max: {} bytes)",
+            size,
+            u32::MAX
+        ))
+    })?;
+    Ok((
+        Origin::File(source_path.to_path_buf()),
+        Pf8Entry::new(archive_path, 0, size).with_encryption(encrypted),
+    ))
+}
+
+/// Writes the selected entries of `reader` to `<output_path>.tmp`, then
+/// renames it into place
+fn write_entries_atomic(
+    reader: &mut Pf8Reader,
+    selected: Vec<(Origin, Pf8Entry)>,
+    output_path: &Path,
+) -> Result<PackResult> {
+    let mut temp_name = output_path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    let temp_path = output_path.with_file_name(temp_name);
+
+    let layout = Layout::of(reader);
+    let result = write_entries(std::slice::from_mut(reader), selected, layout, &temp_path);
+    match result.and_then(|result| Ok(fs::rename(&temp_path, output_path).map(|()| result)?)) {
+        Ok(result) => Ok(result),
+        Err(e) => {
+            let _ = fs::remove_file(&temp_path);
+            Err(e)
+        }
+    }
+}
+
+/// Writes the selected source entries as a new archive, in path order
+fn write_entries(
+    readers: &mut [Pf8Reader],
+    mut selected: Vec<(Origin, Pf8Entry)>,
+    layout: Layout,
+    output_path: &Path,
+) -> Result<PackResult> {
+    if selected.is_empty() {
+        return Err(Error::InvalidFormat("No files to archive".to_string()));
+    }
+
+    selected.sort_by(|(_, a), (_, b)| a.path().cmp(b.path()));
+
+    let data_start = format::offsets::INDEX_DATA_START
+        + format::index_size_for(
+            selected
+                .iter()
+                .map(|(_, entry)| {
+                    let name = utils::pathbuf_to_pf8_path(entry.path());
+                    layout.name_encoding.encoded_len(&name)
+                })
+                .collect::<Result<Vec<_>>>()?,
+        );
+    let mut entries = Vec::with_capacity(selected.len());
+    let mut offset = data_start as u64;
+    for (_, source_entry) in &selected {
+        let new_offset = u32::try_from(offset)
+            .ok()
+            .filter(|o| o.checked_add(source_entry.size()).is_some())
+            .ok_or_else(|| {
+                Error::InvalidFormat("Archive exceeds the 4 GiB format limit".to_string())
+            })?;
+        let entry = Pf8Entry::new(source_entry.path(), new_offset, source_entry.size());
+        if layout.keep_encryption {
+            entries.push(entry.with_encryption(source_entry.is_encrypted()));
+        } else {
+            entries.push(entry);
+        }
+        offset += source_entry.size_u64();
+    }
+
+    let mut writer = Pf8Writer::create(output_path)?;
+    writer
+        .set_format(layout.format)
+        .set_name_encoding(layout.name_encoding);
+    writer.write_header(&entries.iter().collect::<Vec<_>>())?;
+
+    let mut bytes = 0u64;
+    for ((origin, source_entry), entry) in selected.iter().zip(&entries) {
+        match origin {
+            Origin::Archive(reader_index) => {
+                let data = readers[*reader_index].entry_data(source_entry)?;
+                writer.write_file_data_from(entry, data)?;
+            }
+            Origin::File(source_path) => writer.write_file_data(entry, source_path)?,
+        }
+        bytes += entry.size_u64();
+    }
+    writer.finalize()?;
+
+    Ok(PackResult {
+        files: entries.len(),
+        bytes,
+        archive_size: fs::metadata(output_path)?.len(),
+    })
+}
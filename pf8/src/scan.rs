@@ -0,0 +1,71 @@
+//! Scanning directories for PF6/PF8 archives.
+
+use crate::error::{Error, Result};
+use crate::format::ArchiveFormat;
+use crate::reader::Pf8Reader;
+use crate::utils;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Lightweight summary of an archive found by [`scan_dir`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveInfo {
+    /// Path of the archive file
+    pub path: PathBuf,
+    /// Archive format (PF6 or PF8)
+    pub format: ArchiveFormat,
+    /// Number of entries in the archive
+    pub entry_count: usize,
+    /// Number of encrypted entries
+    pub encrypted_count: usize,
+    /// Sum of all entry sizes in bytes
+    pub total_size: u64,
+    /// Size of the archive file in bytes
+    pub file_size: u64,
+}
+
+/// Scans a directory (non-recursively) for PF6/PF8 archives
+///
+/// Files are detected by their magic number rather than their extension and
+/// visited in path order. Each archive is opened, summarized and closed before
+/// moving on to the next one, so only a single archive handle is open at a time.
+pub fn scan_dir<P: AsRef<Path>>(dir: P) -> impl Iterator<Item = Result<ArchiveInfo>> {
+    let candidates = fs::read_dir(dir.as_ref()).and_then(|read_dir| {
+        let mut paths = read_dir
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        paths.sort();
+        Ok(paths)
+    });
+
+    let (paths, error) = match candidates {
+        Ok(paths) => (paths, None),
+        Err(err) => (Vec::new(), Some(Error::from(err))),
+    };
+
+    error.into_iter().map(Err).chain(
+        paths
+            .into_iter()
+            .filter(|path| path.is_file())
+            .filter_map(|path| read_archive_info(&path).transpose()),
+    )
+}
+
+/// Reads the summary of a single archive, returning `None` for non-archive files
+fn read_archive_info(path: &Path) -> Result<Option<ArchiveInfo>> {
+    match utils::archive_format(path) {
+        Ok(_) => {}
+        Err(Error::InvalidFormat(_)) => return Ok(None),
+        Err(e) => return Err(e),
+    }
+
+    let reader = Pf8Reader::open(path)?;
+    Ok(Some(ArchiveInfo {
+        path: path.to_path_buf(),
+        format: reader.format(),
+        entry_count: reader.len(),
+        encrypted_count: reader.entries().filter(|e| e.is_encrypted()).count(),
+        total_size: reader.entries().map(|e| e.size_u64()).sum(),
+        file_size: fs::metadata(path)?.len(),
+    }))
+}
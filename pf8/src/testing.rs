@@ -0,0 +1,131 @@
+//! Helpers for constructing synthetic archives in tests.
+//!
+//! These build archives entirely in memory so fixtures with unusual layouts
+//! (truncated data, forged counts, overlapping entries, non-zero reserved
+//! bytes) can be created programmatically instead of being committed as
+//! binary blobs. Pair them with [`Pf8Reader::from_bytes`].
+//!
+//! [`Pf8Reader::from_bytes`]: crate::Pf8Reader::from_bytes
+
+use crate::crypto;
+use crate::entry::Pf8Entry;
+use crate::format::{self, offsets};
+
+/// Builds a PF8 archive from `(path, data)` pairs
+///
+/// Entries are stored in the given order and encrypted following the same
+/// extension rules as [`Pf8Builder`](crate::Pf8Builder).
+pub fn build_archive(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    let index_entries = entries
+        .iter()
+        .map(|(path, data)| (path.as_bytes(), data.len() as u32))
+        .collect::<Vec<_>>();
+    let mut archive = format::build_header(format::PF8_MAGIC, &index_entries);
+
+    let index_size = format::read_u32_le(&archive, offsets::INDEX_SIZE).unwrap();
+    let key = crypto::generate_key(&archive, index_size);
+
+    for (path, data) in entries {
+        let mut data = data.to_vec();
+        if Pf8Entry::new(path, 0, 0).is_encrypted() {
+            crypto::encrypt(&mut data, &key, 0).unwrap();
+        }
+        archive.extend_from_slice(&data);
+    }
+
+    archive
+}
+
+/// Builds a PF6 (unencrypted) archive from `(path, data)` pairs
+pub fn build_pf6_archive(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    let index_entries = entries
+        .iter()
+        .map(|(path, data)| (path.as_bytes(), data.len() as u32))
+        .collect::<Vec<_>>();
+    let mut archive = format::build_header(format::PF6_MAGIC, &index_entries);
+
+    for (_, data) in entries {
+        archive.extend_from_slice(data);
+    }
+
+    archive
+}
+
+/// A modification to apply to an archive's header or index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Corruption {
+    /// Overwrite the `index_size` header field
+    IndexSize(u32),
+    /// Overwrite the `index_count` header field
+    IndexCount(u32),
+    /// Overwrite the offset of the entry at `entry`
+    Offset { entry: usize, offset: u32 },
+    /// Overwrite the size of the entry at `entry`
+    Size { entry: usize, size: u32 },
+    /// Overwrite the reserved bytes of the entry at `entry`
+    Reserved { entry: usize, value: u32 },
+    /// Overwrite the name length of the entry at `entry`
+    NameLength { entry: usize, length: u32 },
+    /// Truncate the archive to `len` bytes
+    Truncate(usize),
+}
+
+/// Applies `corruption` to an archive built by [`build_archive`]
+///
+/// Index fields are located by walking the well-formed index, so apply
+/// [`Corruption::IndexCount`], [`Corruption::NameLength`] and
+/// [`Corruption::Truncate`] last when combining several corruptions.
+///
+/// # Panics
+///
+/// Panics if `entry` is out of range for the archive.
+pub fn corrupt_index(archive: &mut Vec<u8>, corruption: Corruption) {
+    match corruption {
+        Corruption::IndexSize(value) => write_u32(archive, offsets::INDEX_SIZE, value),
+        Corruption::IndexCount(value) => write_u32(archive, offsets::INDEX_COUNT, value),
+        Corruption::Offset { entry, offset } => {
+            let pos = entry_fields(archive, entry) + 4;
+            write_u32(archive, pos, offset);
+        }
+        Corruption::Size { entry, size } => {
+            let pos = entry_fields(archive, entry) + 8;
+            write_u32(archive, pos, size);
+        }
+        Corruption::Reserved { entry, value } => {
+            let pos = entry_fields(archive, entry);
+            write_u32(archive, pos, value);
+        }
+        Corruption::NameLength { entry, length } => {
+            let pos = entry_start(archive, entry);
+            write_u32(archive, pos, length);
+        }
+        Corruption::Truncate(len) => archive.truncate(len),
+    }
+}
+
+/// Returns the position of the name length field of entry `entry`
+fn entry_start(archive: &[u8], entry: usize) -> usize {
+    let count = format::read_u32_le(archive, offsets::INDEX_COUNT).unwrap() as usize;
+    assert!(
+        entry < count,
+        "entry {entry} out of range ({count} entries)"
+    );
+
+    let mut pos = offsets::ENTRIES_START;
+    for _ in 0..entry {
+        let name_length = format::read_u32_le(archive, pos).unwrap() as usize;
+        pos += 4 + name_length + 12;
+    }
+    pos
+}
+
+/// Returns the position of the reserved field of entry `entry`
+fn entry_fields(archive: &[u8], entry: usize) -> usize {
+    let pos = entry_start(archive, entry);
+    let name_length = format::read_u32_le(archive, pos).unwrap() as usize;
+    pos + 4 + name_length
+}
+
+fn write_u32(archive: &mut [u8], pos: usize, value: u32) {
+    archive[pos..pos + 4].copy_from_slice(&value.to_le_bytes());
+}
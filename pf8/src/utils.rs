@@ -1,12 +1,54 @@
 //! Utility functions for path handling and string operations.
 
+use std::fs::File;
+use std::io::Read;
 use std::path::{Path, PathBuf};
 
-use crate::constants::UNENCRYPTED_FILTER;
+use crate::constants::{MIN_BUFFER_SIZE, SIDECAR_NAMES};
+use crate::error::{Error, Result};
+use crate::format::{self, ArchiveFormat};
 
-/// Converts a PF8-style filename (backslash-separated) to a PathBuf
+/// Detects the format of an archive file by reading only its 3-byte magic
+///
+/// Unlike [`Pf8Reader::open`](crate::Pf8Reader::open) the index is not
+/// parsed, so this is cheap enough to call on every file in a directory.
+/// Returns [`Error::InvalidFormat`](crate::Error::InvalidFormat) for files
+/// that are not PF6/PF8 archives.
+pub fn archive_format<P: AsRef<Path>>(path: P) -> Result<ArchiveFormat> {
+    let mut magic = Vec::with_capacity(3);
+    File::open(path)?.take(3).read_to_end(&mut magic)?;
+    format::validate_magic(&magic)
+}
+
+/// Reads the number of entries of an archive from its 11-byte header
+///
+/// Entry names are not parsed, so this is O(1) regardless of archive size.
+/// The magic is validated first.
+pub fn entry_count<P: AsRef<Path>>(path: P) -> Result<u32> {
+    let mut header = Vec::with_capacity(format::offsets::ENTRIES_START);
+    File::open(path)?
+        .take(format::offsets::ENTRIES_START as u64)
+        .read_to_end(&mut header)?;
+    format::validate_magic(&header)?;
+    format::read_u32_le(&header, format::offsets::INDEX_COUNT)
+}
+
+/// Converts a PF8-style filename to a PathBuf
+///
+/// Both `\\` and `/` are treated as separators, as some packers write
+/// forward slashes.
 pub fn pf8_path_to_pathbuf(pf8_path: &str) -> PathBuf {
-    pf8_path.split('\\').collect()
+    pf8_path.split(['\\', '/']).collect()
+}
+
+/// Converts a PF8-style filename to a PathBuf, splitting only on `\\`
+///
+/// `/` cannot appear in a file name on disk, so it is replaced with `_`.
+pub fn pf8_path_to_pathbuf_strict(pf8_path: &str) -> PathBuf {
+    pf8_path
+        .split('\\')
+        .map(|component| component.replace('/', "_"))
+        .collect()
 }
 
 /// Converts a PathBuf to a PF8-style filename (backslash-separated)
@@ -17,9 +59,85 @@ pub fn pathbuf_to_pf8_path(path: &Path) -> String {
         .join("\\")
 }
 
+/// Returns the lowercase extension of a path, or an empty string if it has none
+pub fn lowercase_extension(path: &Path) -> String {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// Guesses the MIME type of a file from its extension
+///
+/// Unknown extensions map to `application/octet-stream`.
+pub fn content_type(path: &Path) -> &'static str {
+    match lowercase_extension(path).as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "webp" => "image/webp",
+        "bmp" => "image/bmp",
+        "ogg" => "audio/ogg",
+        "wav" => "audio/wav",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "flv" => "video/x-flv",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "csv" => "text/csv",
+        "txt" | "ini" | "ast" | "lua" => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Checks if a path names a sidecar entry (`.pfsmeta`, `.keep`, ...)
+pub fn is_sidecar(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| SIDECAR_NAMES.iter().any(|sidecar| name == *sidecar))
+}
+
+/// Removes trailing NUL terminators from an entry name
+pub fn strip_nul_terminator(name: &mut String) {
+    let len = name.trim_end_matches('\0').len();
+    name.truncate(len);
+}
+
+/// Normalizes a path to the forward-slash form used for entry lookups
+pub fn normalize_lookup_path(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+/// Rejects buffer sizes below 4 KiB
+pub fn check_buffer_size(size: usize) -> Result<()> {
+    if size < MIN_BUFFER_SIZE {
+        return Err(Error::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("buffer size must be at least {MIN_BUFFER_SIZE} bytes, got {size}"),
+        )));
+    }
+    Ok(())
+}
+
+/// Checks if a name contains control characters (newlines, NUL, escapes, ...)
+pub fn has_control_chars(name: &str) -> bool {
+    name.chars().any(char::is_control)
+}
+
+/// Replaces every control character in a name with `_`
+pub fn sanitize_control_chars(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_control() { '_' } else { c })
+        .collect()
+}
+
 /// Checks if a file path matches any of the given patterns
-pub fn matches_any_pattern(path: &str) -> bool {
-    UNENCRYPTED_FILTER.to_vec().iter().any(|&pattern| {
+///
+/// Patterns starting with `.` match the end of the path; others match any
+/// part of it.
+pub fn matches_any_pattern<S: AsRef<str>>(path: &str, patterns: &[S]) -> bool {
+    patterns.iter().any(|pattern| {
+        let pattern = pattern.as_ref();
         if pattern.starts_with('.') {
             // Extension pattern
             path.ends_with(pattern)
@@ -44,4 +162,25 @@ mod tests {
         let converted_back = pathbuf_to_pf8_path(&pathbuf);
         assert_eq!(converted_back, pf8_path);
     }
+
+    #[test]
+    fn test_control_chars() {
+        assert!(has_control_chars("bad\nname.txt"));
+        assert!(has_control_chars("nul\0inside"));
+        assert!(!has_control_chars("fine name.txt"));
+        assert_eq!(sanitize_control_chars("a\tb\nc"), "a_b_c");
+    }
+
+    #[test]
+    fn test_normalize_lookup_path() {
+        assert_eq!(normalize_lookup_path(Path::new("a\\b.txt")), "a/b.txt");
+        assert_eq!(normalize_lookup_path(Path::new("a/b.txt")), "a/b.txt");
+    }
+
+    #[test]
+    fn test_strip_nul_terminator() {
+        let mut name = String::from("dir\\file.txt\0\0");
+        strip_nul_terminator(&mut name);
+        assert_eq!(name, "dir\\file.txt");
+    }
 }
@@ -0,0 +1,443 @@
+//! Consistency checks for archives.
+
+use crate::crypto;
+use crate::entry::Pf8Entry;
+use crate::error::Result;
+use crate::format::{self, offsets};
+use crate::reader::Pf8Reader;
+use crate::utils;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// Entries per extension examined by [`infer_unencrypted_filter`]
+const INFER_SAMPLE: usize = 16;
+
+/// Chunk size for streamed content comparison
+const COMPARE_CHUNK: usize = 64 * 1024;
+
+/// Extensions of entries expected to hold UTF-8 text
+const TEXT_EXTENSIONS: [&str; 7] = ["txt", "ini", "ast", "lua", "csv", "json", "xml"];
+
+/// Result of [`check_layout`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LayoutReport {
+    /// Number of entries whose data range is exactly that of another entry
+    ///
+    /// Archives built with content deduplication point several entries at
+    /// the same bytes; this is intentional and not an error.
+    pub shared_entries: usize,
+    /// Pairs of entries whose data ranges partially overlap
+    pub overlaps: Vec<(String, String)>,
+}
+
+impl LayoutReport {
+    /// Returns true if no entries partially overlap
+    pub fn is_ok(&self) -> bool {
+        self.overlaps.is_empty()
+    }
+}
+
+/// Checks how entry data ranges relate to each other
+///
+/// Entries sharing the exact same offset and size are counted as
+/// deduplicated; any other overlap of data ranges indicates corruption.
+/// Zero-size entries occupy no data and are ignored.
+pub fn check_layout(reader: &Pf8Reader) -> LayoutReport {
+    let mut entries: Vec<&Pf8Entry> = reader.all_entries().filter(|e| e.size() > 0).collect();
+    entries.sort_by_key(|e| (e.offset(), e.size()));
+
+    let mut report = LayoutReport::default();
+    // Entry reaching furthest into the archive so far
+    let mut furthest: Option<&Pf8Entry> = None;
+    let mut group_len = 0usize;
+
+    for (index, entry) in entries.iter().enumerate() {
+        let same_range = index > 0
+            && entries[index - 1].offset() == entry.offset()
+            && entries[index - 1].size() == entry.size();
+
+        if same_range {
+            group_len += 1;
+            continue;
+        }
+        if group_len > 1 {
+            report.shared_entries += group_len;
+        }
+        group_len = 1;
+
+        if let Some(prev) = furthest
+            && entry.offset_u64() < end_of(prev)
+        {
+            report.overlaps.push((
+                prev.path().display().to_string(),
+                entry.path().display().to_string(),
+            ));
+        }
+        if furthest.is_none_or(|prev| end_of(entry) > end_of(prev)) {
+            furthest = Some(entry);
+        }
+    }
+    if group_len > 1 {
+        report.shared_entries += group_len;
+    }
+
+    report
+}
+
+fn end_of(entry: &Pf8Entry) -> u64 {
+    entry.offset_u64() + entry.size_u64()
+}
+
+/// Decrypts a sample of encrypted text entries and returns those that are
+/// not valid UTF-8
+///
+/// At most `sample` entries with a text extension are checked, in index
+/// order. A non-empty result suggests the index was altered after packing,
+/// so the key derived from it no longer matches the data.
+pub fn decrypt_check(reader: &Pf8Reader, sample: usize) -> Result<Vec<String>> {
+    let mut suspicious = Vec::new();
+    let candidates = reader.all_entries().filter(|entry| {
+        entry.is_encrypted()
+            && TEXT_EXTENSIONS.contains(&utils::lowercase_extension(entry.path()).as_str())
+    });
+
+    for entry in candidates.take(sample) {
+        if !reader.entry_is_valid_utf8(entry.path())? {
+            suspicious.push(entry.path().display().to_string());
+        }
+    }
+    Ok(suspicious)
+}
+
+/// Suggests which extensions were left unencrypted when the archive was packed
+///
+/// For each extension, up to 16 entries are read both as stored and
+/// decrypted with the archive key. An extension is suggested when more of
+/// its entries look like text as stored than after decryption. Extensions
+/// whose entries are binary either way give no signal and are never
+/// suggested, so the result is a lower bound on the original filter.
+///
+/// Returns the extensions in lowercase and sorted; PF6 archives have no
+/// encryption and always yield an empty list.
+pub fn infer_unencrypted_filter(reader: &Pf8Reader) -> Result<Vec<String>> {
+    let Some(key) = reader.encryption_key() else {
+        return Ok(Vec::new());
+    };
+
+    let mut groups: BTreeMap<String, Vec<&Pf8Entry>> = BTreeMap::new();
+    for entry in reader.all_entries().filter(|e| e.size() > 0) {
+        let entries = groups
+            .entry(utils::lowercase_extension(entry.path()))
+            .or_default();
+        if entries.len() < INFER_SAMPLE {
+            entries.push(entry);
+        }
+    }
+
+    let mut filter = Vec::new();
+    for (extension, entries) in groups {
+        let (mut stored_text, mut decrypted_text) = (0usize, 0usize);
+        for entry in entries {
+            let mut data = reader.read_stored(entry)?;
+            stored_text += is_text_like(&data) as usize;
+            crypto::encrypt(&mut data, key, 0)?;
+            decrypted_text += is_text_like(&data) as usize;
+        }
+        if stored_text > decrypted_text {
+            filter.push(extension);
+        }
+    }
+    Ok(filter)
+}
+
+/// Returns true if `data` is UTF-8 without control characters other than
+/// whitespace
+fn is_text_like(data: &[u8]) -> bool {
+    std::str::from_utf8(data).is_ok_and(|text| {
+        text.chars()
+            .all(|c| !c.is_control() || matches!(c, '\t' | '\n' | '\r'))
+    })
+}
+
+/// Result of [`verify_extraction`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Number of entries checked
+    pub checked: usize,
+    /// Entries with no output file
+    pub missing: Vec<String>,
+    /// Entries whose output file has a different size
+    pub size_mismatches: Vec<String>,
+    /// Entries whose output file has the right size but different content
+    pub content_mismatches: Vec<String>,
+}
+
+impl VerifyReport {
+    /// Returns true if every entry matched its output file
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty()
+            && self.size_mismatches.is_empty()
+            && self.content_mismatches.is_empty()
+    }
+}
+
+/// Checks that `output_dir` holds an extraction of every entry
+///
+/// Each entry is expected at the path [`Pf8Reader::extract_all`] writes it
+/// to, with the entry's size. With `compare_content`, the decrypted entry
+/// data is also streamed and compared against the file. Files in
+/// `output_dir` that do not belong to any entry are ignored.
+pub fn verify_extraction<P: AsRef<Path>>(
+    reader: &mut Pf8Reader,
+    output_dir: P,
+    compare_content: bool,
+) -> Result<VerifyReport> {
+    let output_dir = output_dir.as_ref();
+    let mut report = VerifyReport::default();
+
+    let entries = reader.all_entries().cloned().collect::<Vec<_>>();
+    for entry in entries {
+        report.checked += 1;
+        let name = entry.path().display().to_string();
+        let path = reader.output_path_for(output_dir, &entry)?;
+
+        let metadata = match fs::metadata(&path) {
+            Ok(metadata) if metadata.is_file() => metadata,
+            _ => {
+                report.missing.push(name);
+                continue;
+            }
+        };
+        if metadata.len() != entry.size_u64() {
+            report.size_mismatches.push(name);
+            continue;
+        }
+
+        if compare_content {
+            let expected = reader.entry_data(&entry)?;
+            let actual = BufReader::new(File::open(&path)?);
+            if !streams_equal(expected, actual)? {
+                report.content_mismatches.push(name);
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Compares two streams of the same length chunk by chunk
+pub(crate) fn streams_equal<A: Read, B: Read>(mut a: A, mut b: B) -> Result<bool> {
+    let mut a_buf = vec![0u8; COMPARE_CHUNK];
+    let mut b_buf = vec![0u8; COMPARE_CHUNK];
+    loop {
+        let n = a.read(&mut a_buf)?;
+        if n == 0 {
+            return Ok(b.read(&mut b_buf[..1])? == 0);
+        }
+        b.read_exact(&mut b_buf[..n])?;
+        if a_buf[..n] != b_buf[..n] {
+            return Ok(false);
+        }
+    }
+}
+
+/// Size breakdown of an archive file, see [`Pf8Reader::statistics`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArchiveStats {
+    /// Size of the archive file in bytes
+    pub total_file_size: u64,
+    /// Bytes taken by the header and index, up to where entry data begins
+    pub index_size: u64,
+    /// Bytes of the data region holding entry data
+    ///
+    /// Data shared by several entries is counted once, so this is the
+    /// physical size; see `logical_size` for the sum of entry sizes.
+    pub data_size: u64,
+    /// Sum of all entry sizes in bytes
+    pub logical_size: u64,
+    /// Number of entries, including sidecars
+    pub entry_count: usize,
+    /// Number of encrypted entries
+    pub encrypted_count: usize,
+    /// Bytes of the file that belong to neither the index nor any entry
+    ///
+    /// `total_file_size - (index_size + data_size)`: gaps between entries
+    /// and trailing padding. Data of truncated entries extending beyond the
+    /// end of the file is not counted in `data_size`.
+    pub slack_bytes: u64,
+}
+
+/// Computes the [`ArchiveStats`] of an open archive
+pub(crate) fn statistics(reader: &Pf8Reader) -> ArchiveStats {
+    let total_file_size = reader.archive_size();
+    let index_size = reader.data_start().min(total_file_size);
+
+    let mut ranges: Vec<(u64, u64)> = reader
+        .all_entries()
+        .map(|e| {
+            (
+                e.offset_u64().max(index_size),
+                end_of(e).min(total_file_size),
+            )
+        })
+        .filter(|(start, end)| start < end)
+        .collect();
+    ranges.sort_unstable();
+    let mut data_size = 0;
+    let mut covered = index_size;
+    for (start, end) in ranges {
+        if end > covered {
+            data_size += end - start.max(covered);
+            covered = end;
+        }
+    }
+
+    ArchiveStats {
+        total_file_size,
+        index_size,
+        data_size,
+        logical_size: reader.all_entries().map(Pf8Entry::size_u64).sum(),
+        entry_count: reader.all_entries().count(),
+        encrypted_count: reader.all_entries().filter(|e| e.is_encrypted()).count(),
+        slack_bytes: total_file_size - index_size - data_size,
+    }
+}
+
+/// Result of [`Pf8Reader::verify`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntegrityReport {
+    /// Number of entries parsed from the index
+    pub entries: usize,
+    /// Entry count declared in the header
+    pub declared_count: u32,
+    /// Whether the key derived from the header region on disk equals the
+    /// key used for decryption (always true for PF6)
+    pub key_matches: bool,
+    /// Problems found with individual entries, in index order
+    pub problems: Vec<EntryProblem>,
+    /// Number of unused byte ranges in the data region
+    pub gaps: usize,
+    /// Total size of the unused byte ranges in the data region
+    pub gap_bytes: u64,
+}
+
+/// A problem with one entry, reported by [`Pf8Reader::verify`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryProblem {
+    /// Archive path of the entry
+    pub name: String,
+    /// Offset of the entry data
+    pub offset: u32,
+    /// Size of the entry data
+    pub size: u32,
+    /// What is wrong with the entry
+    pub kind: ProblemKind,
+}
+
+/// Kinds of [`EntryProblem`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProblemKind {
+    /// The entry data extends beyond the end of the archive
+    OutOfBounds,
+    /// The entry data starts inside the header or index
+    OverlapsIndex,
+    /// The entry data partially overlaps that of the named entry
+    Overlaps(String),
+}
+
+impl IntegrityReport {
+    /// Returns true if no problem was found
+    ///
+    /// Gaps are reported but not treated as problems; the format does not
+    /// forbid unused bytes.
+    pub fn is_ok(&self) -> bool {
+        self.key_matches && self.declared_count as usize == self.entries && self.problems.is_empty()
+    }
+
+    /// Returns the number of entries without problems
+    pub fn entries_ok(&self) -> usize {
+        let mut bad: Vec<&str> = self.problems.iter().map(|p| p.name.as_str()).collect();
+        bad.sort_unstable();
+        bad.dedup();
+        self.entries - bad.len()
+    }
+
+    /// Returns the lowest offset of an entry with a problem
+    pub fn first_bad_offset(&self) -> Option<u32> {
+        self.problems.iter().map(|p| p.offset).min()
+    }
+}
+
+/// Checks the header, index and data layout of an open archive
+pub(crate) fn check_integrity(reader: &Pf8Reader) -> Result<IntegrityReport> {
+    let archive_size = reader.archive_size();
+    let mut header = [0u8; offsets::ENTRIES_START];
+    reader.read_exact_at(0, &mut header)?;
+    let index_size = format::read_u32_le(&header, offsets::INDEX_SIZE)?;
+    let data_start = offsets::INDEX_DATA_START as u64 + index_size as u64;
+
+    let key_matches = match reader.encryption_key() {
+        None => true,
+        Some(_) if data_start > archive_size => false,
+        Some(key) => {
+            let mut index = vec![0u8; data_start as usize];
+            reader.read_exact_at(0, &mut index)?;
+            crypto::generate_key(&index, index_size) == key
+        }
+    };
+
+    let mut report = IntegrityReport {
+        entries: reader.all_entries().count(),
+        declared_count: format::read_u32_le(&header, offsets::INDEX_COUNT)?,
+        key_matches,
+        ..Default::default()
+    };
+
+    let problem = |entry: &Pf8Entry, kind| EntryProblem {
+        name: entry.path().display().to_string(),
+        offset: entry.offset(),
+        size: entry.size(),
+        kind,
+    };
+    for entry in reader.all_entries().filter(|e| e.size() > 0) {
+        if end_of(entry) > archive_size {
+            report
+                .problems
+                .push(problem(entry, ProblemKind::OutOfBounds));
+        } else if entry.offset_u64() < data_start {
+            report
+                .problems
+                .push(problem(entry, ProblemKind::OverlapsIndex));
+        }
+    }
+    for (first, second) in check_layout(reader).overlaps {
+        if let Some(entry) = reader.get_entry(&second) {
+            report
+                .problems
+                .push(problem(entry, ProblemKind::Overlaps(first)));
+        }
+    }
+
+    let mut ranges: Vec<(u64, u64)> = reader
+        .all_entries()
+        .filter(|e| e.size() > 0)
+        .map(|e| (e.offset_u64(), end_of(e).min(archive_size)))
+        .collect();
+    ranges.sort_unstable();
+    let mut covered = data_start;
+    for (start, end) in ranges {
+        if start > covered {
+            report.gaps += 1;
+            report.gap_bytes += start - covered;
+        }
+        covered = covered.max(end);
+    }
+    if archive_size > covered {
+        report.gaps += 1;
+        report.gap_bytes += archive_size - covered;
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,226 @@
+//! Reading archives split into numbered volumes.
+//!
+//! Games ship large archives as `root.pfs` followed by `root.pfs.000`,
+//! `root.pfs.001` and so on. Every volume is a complete archive with its own
+//! index, offsets and key, as written by [`Pf8Builder::write_split`]; the
+//! engine loads them in order and a path found in a later volume replaces
+//! the same path from an earlier one. [`MultiVolumeReader`] presents the
+//! volumes the same way, as a single set of entries.
+//!
+//! There is no global offset space spanning the volumes: no index refers to
+//! data in another file, and each volume's offsets and key only make sense
+//! within that volume. Resolving a path to its volume and reading it there
+//! is all a lookup needs, and it also covers volumes added later as
+//! patches, which a fixed offset map over a single split would not.
+//!
+//! Volumes are found by number alone, so a stale `<base>.NNN` from an
+//! unrelated split is loaded like any other and its entries take
+//! precedence. [`Pf8Builder::write_split`] removes such leftovers when it
+//! writes fewer volumes than before.
+//!
+//! [`Pf8Builder::write_split`]: crate::Pf8Builder::write_split
+
+use crate::archive::UnpackResult;
+use crate::callbacks::ArchiveHandler;
+use crate::entry::Pf8Entry;
+use crate::error::{Error, Result};
+use crate::extract::ExtractOptions;
+use crate::reader::{EntryReader, Pf8Reader, ReaderOptions};
+use crate::utils;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Returns `base` followed by its numbered volumes that exist on disk
+///
+/// Volumes are `<base>.000`, `<base>.001`, ... and the list stops at the
+/// first missing number. `base` itself is always listed first, whether it
+/// exists or not.
+pub fn volume_paths<P: AsRef<Path>>(base: P) -> Vec<PathBuf> {
+    let base = base.as_ref();
+    let mut paths = vec![base.to_path_buf()];
+    for number in 0.. {
+        let mut name = base.as_os_str().to_os_string();
+        name.push(format!(".{number:03}"));
+        let path = PathBuf::from(name);
+        if !path.is_file() {
+            break;
+        }
+        paths.push(path);
+    }
+    paths
+}
+
+/// Reader over an archive and its numbered volumes, see the module docs
+pub struct MultiVolumeReader {
+    /// Path and reader of every volume, in load order
+    volumes: Vec<(PathBuf, Pf8Reader)>,
+    /// Visible entry for each path, with the index of its volume
+    entries: Vec<(usize, Pf8Entry)>,
+    /// Lookup map from normalized path to position in `entries`
+    entry_map: HashMap<String, usize>,
+}
+
+impl MultiVolumeReader {
+    /// Opens `base` and every numbered volume after it
+    pub fn open<P: AsRef<Path>>(base: P) -> Result<Self> {
+        Self::open_with(base, ReaderOptions::default())
+    }
+
+    /// Opens `base` and every numbered volume after it with custom options
+    pub fn open_with<P: AsRef<Path>>(base: P, options: ReaderOptions) -> Result<Self> {
+        let volumes = volume_paths(base)
+            .into_iter()
+            .map(|path| {
+                let reader = Pf8Reader::open_with(&path, options.clone())?;
+                Ok((path, reader))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut entries: Vec<(usize, Pf8Entry)> = Vec::new();
+        let mut entry_map = HashMap::new();
+        for (volume, (_, reader)) in volumes.iter().enumerate() {
+            for entry in reader.all_entries() {
+                let key = utils::normalize_lookup_path(entry.path());
+                match entry_map.get(&key) {
+                    Some(&position) => entries[position] = (volume, entry.clone()),
+                    None => {
+                        entry_map.insert(key, entries.len());
+                        entries.push((volume, entry.clone()));
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            volumes,
+            entries,
+            entry_map,
+        })
+    }
+
+    /// Returns the paths of the opened volumes, in load order
+    pub fn volumes(&self) -> impl Iterator<Item = &Path> {
+        self.volumes.iter().map(|(path, _)| path.as_path())
+    }
+
+    /// Returns the reader of each volume, in load order
+    pub fn readers(&self) -> impl Iterator<Item = &Pf8Reader> {
+        self.volumes.iter().map(|(_, reader)| reader)
+    }
+
+    /// Returns the visible entries, excluding sidecars
+    ///
+    /// Entries keep the order in which their path first appeared; an entry
+    /// replaced by a later volume is listed at the position of the original.
+    pub fn entries(&self) -> impl Iterator<Item = &Pf8Entry> {
+        self.entries
+            .iter()
+            .map(|(_, entry)| entry)
+            .filter(|entry| !utils::is_sidecar(entry.path()))
+    }
+
+    /// Returns the number of visible entries, excluding sidecars
+    pub fn len(&self) -> usize {
+        self.entries().count()
+    }
+
+    /// Returns true if no volume has any entry
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Gets the visible entry at `path`
+    pub fn get_entry<P: AsRef<Path>>(&self, path: P) -> Option<&Pf8Entry> {
+        self.lookup(path.as_ref()).map(|(_, entry)| entry)
+    }
+
+    /// Returns the path of the volume holding the visible entry at `path`
+    pub fn volume_of<P: AsRef<Path>>(&self, path: P) -> Option<&Path> {
+        self.lookup(path.as_ref())
+            .map(|&(volume, _)| self.volumes[volume].0.as_path())
+    }
+
+    /// Reads a file's data from the volume holding it
+    pub fn read_file<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>> {
+        let (reader, entry) = self.resolve(path.as_ref())?;
+        reader.read_file(entry.path())
+    }
+
+    /// Opens a file for streaming from the volume holding it, see
+    /// [`Pf8Reader::open_entry`]
+    pub fn open_entry<P: AsRef<Path>>(&self, path: P) -> Result<EntryReader<'_>> {
+        let (reader, entry) = self.resolve(path.as_ref())?;
+        reader.open_entry(entry.path())
+    }
+
+    /// Extracts the visible entries selected by `options`
+    ///
+    /// Volumes are extracted one after the other, each reported to `handler`
+    /// as its own operation. Entries replaced by a later volume are counted
+    /// in [`UnpackResult::skipped`], like those filtered out by `options`.
+    pub fn extract_with_options<P: AsRef<Path>, H: ArchiveHandler>(
+        &mut self,
+        output_dir: P,
+        options: &ExtractOptions,
+        handler: &mut H,
+    ) -> Result<UnpackResult> {
+        let output_dir = output_dir.as_ref();
+        let mut result = UnpackResult::default();
+
+        for volume in 0..self.volumes.len() {
+            let planned = self.plan_volume(volume, output_dir, options)?;
+            let reader = &mut self.volumes[volume].1;
+            result.skipped += reader.all_entries().count() - planned.len();
+
+            let extracted = reader.extract_planned(&planned, options.overwrite, handler)?;
+            result.files += extracted.files;
+            result.bytes += extracted.bytes;
+            result.skipped += extracted.skipped;
+        }
+        Ok(result)
+    }
+
+    /// Resolves where each visible entry would be written by
+    /// [`Self::extract_with_options`]
+    pub fn plan_extraction<P: AsRef<Path>>(
+        &self,
+        output_dir: P,
+        options: &ExtractOptions,
+    ) -> Result<Vec<(Pf8Entry, PathBuf)>> {
+        let mut planned = Vec::new();
+        for volume in 0..self.volumes.len() {
+            planned.extend(self.plan_volume(volume, output_dir.as_ref(), options)?);
+        }
+        Ok(planned)
+    }
+
+    /// Plans the extraction of the entries of `volume` that are visible
+    fn plan_volume(
+        &self,
+        volume: usize,
+        output_dir: &Path,
+        options: &ExtractOptions,
+    ) -> Result<Vec<(Pf8Entry, PathBuf)>> {
+        let mut planned = self.volumes[volume]
+            .1
+            .plan_extraction(output_dir, options)?;
+        planned.retain(|(entry, _)| {
+            self.lookup(entry.path())
+                .is_some_and(|&(owner, _)| owner == volume)
+        });
+        Ok(planned)
+    }
+
+    fn lookup(&self, path: &Path) -> Option<&(usize, Pf8Entry)> {
+        self.entry_map
+            .get(&utils::normalize_lookup_path(path))
+            .map(|&position| &self.entries[position])
+    }
+
+    fn resolve(&self, path: &Path) -> Result<(&Pf8Reader, &Pf8Entry)> {
+        let (volume, entry) = self
+            .lookup(path)
+            .ok_or_else(|| Error::FileNotFound(path.display().to_string()))?;
+        Ok((&self.volumes[*volume].1, entry))
+    }
+}
@@ -2,17 +2,23 @@
 
 use crate::constants::BUFFER_SIZE;
 use crate::crypto;
+use crate::encoding::NameEncoding;
 use crate::entry::Pf8Entry;
 use crate::error::{Error, Result};
-use crate::format;
+use crate::format::{self, ArchiveFormat};
+use crate::utils;
+use sha1::{Digest, Sha1};
+use std::borrow::Cow;
 use std::fs::{File, OpenOptions};
-use std::io::{Seek, Write};
+use std::io::{Read, Write};
 use std::path::Path;
 
 /// A writer for creating PF8 archives
 pub struct Pf8Writer {
-    /// The output file
-    output: File,
+    /// Destination of the archive bytes
+    output: Output,
+    /// Number of bytes written so far
+    written: u64,
     /// Header buffer (only stores header data)
     header_data: Vec<u8>,
     /// Current state of the writer
@@ -21,6 +27,40 @@ pub struct Pf8Writer {
     data_start_pos: u64,
     /// Cached encryption key (computed once after header is written)
     encryption_key: Option<Vec<u8>>,
+    /// Encoding used for entry names in the index
+    name_encoding: NameEncoding,
+    /// Format written; PF6 archives are never encrypted
+    format: ArchiveFormat,
+    /// Hasher fed with every byte written, if hashing is enabled
+    hasher: Option<Sha1>,
+    /// SHA1 of the complete archive, available after finalizing
+    archive_hash: Option<[u8; 20]>,
+    /// Size of the buffer used to stream file data
+    buffer_size: usize,
+}
+
+/// Destination an archive is written to
+enum Output {
+    /// Archive file on disk
+    File(File),
+    /// Caller-provided sink, see [`Pf8Writer::from_writer`]
+    Stream(Box<dyn Write + Send>),
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Output::File(file) => file.write(buf),
+            Output::Stream(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Output::File(file) => file.flush(),
+            Output::Stream(stream) => stream.flush(),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -41,95 +81,200 @@ impl Pf8Writer {
             .truncate(true)
             .open(output_path)?;
 
-        Ok(Self {
+        Ok(Self::with_output(Output::File(output)))
+    }
+
+    /// Creates a new writer streaming the archive to `writer`
+    ///
+    /// The archive is written strictly front to back, so `writer` needs no
+    /// seeking: a pipe, socket or in-memory buffer works as well as a file.
+    /// Entry data is streamed through in chunks, never held in full.
+    pub fn from_writer<W: Write + Send + 'static>(writer: W) -> Self {
+        Self::with_output(Output::Stream(Box::new(writer)))
+    }
+
+    fn with_output(output: Output) -> Self {
+        Self {
             output,
+            written: 0,
             header_data: Vec::new(),
             state: WriterState::Created,
             data_start_pos: 0,
             encryption_key: None,
-        })
+            name_encoding: NameEncoding::default(),
+            format: ArchiveFormat::Pf8,
+            hasher: None,
+            archive_hash: None,
+            buffer_size: BUFFER_SIZE,
+        }
     }
 
-    /// Writes the archive header with file entries
-    pub fn write_header(&mut self, entries: &[&Pf8Entry]) -> Result<()> {
+    /// Sets the size in bytes of the buffer used to stream file data
+    ///
+    /// Files up to this size are read in one piece; larger ones are streamed
+    /// through a buffer of this size (default 4 MiB). Sizes below 4 KiB are
+    /// rejected.
+    pub fn set_buffer_size(&mut self, size: usize) -> Result<&mut Self> {
+        utils::check_buffer_size(size)?;
+        self.buffer_size = size;
+        Ok(self)
+    }
+
+    /// Sets the encoding used for entry names (default UTF-8)
+    ///
+    /// The encoded names determine `index_size` and therefore the key, so
+    /// this must be set before [`Self::write_header`].
+    pub fn set_name_encoding(&mut self, encoding: NameEncoding) -> &mut Self {
+        self.name_encoding = encoding;
+        self
+    }
+
+    /// Sets the archive format (default PF8)
+    ///
+    /// PF6 archives carry the `pf6` magic and no encryption: entry data is
+    /// stored as is, whatever the entries' encryption flags. Must be set
+    /// before [`Self::write_header`].
+    pub fn set_format(&mut self, format: ArchiveFormat) -> &mut Self {
+        self.format = format;
+        self
+    }
+
+    /// Computes the SHA1 of the archive while it is written
+    ///
+    /// Every byte is fed to the hasher on its way to the output, so the hash
+    /// of a multi-GB archive is available from [`Self::archive_hash`] after
+    /// [`Self::finalize`] without reading the file back. Must be enabled
+    /// before [`Self::write_header`].
+    pub fn enable_hashing(&mut self) -> Result<&mut Self> {
         if self.state != WriterState::Created {
-            return Err(Error::InvalidFormat("Header already written".to_string()));
+            return Err(Error::InvalidFormat(
+                "Hashing must be enabled before the header is written".to_string(),
+            ));
         }
+        self.hasher = Some(Sha1::new());
+        Ok(self)
+    }
 
-        // Calculate sizes
-        let index_count = entries.len() as u32;
-        let mut fileentry_size = 0usize;
+    /// Returns the SHA1 of the written archive
+    ///
+    /// Available once the writer is finalized with hashing enabled.
+    pub fn archive_hash(&self) -> Option<[u8; 20]> {
+        self.archive_hash
+    }
 
-        for entry in entries {
-            fileentry_size += entry.pf8_path().len() + 16; // name + padding + offset + size
+    /// Writes bytes to the output, feeding the hasher if enabled
+    fn write_output(&mut self, data: &[u8]) -> Result<()> {
+        self.output.write_all(data)?;
+        self.written += data.len() as u64;
+        if let Some(hasher) = &mut self.hasher {
+            hasher.update(data);
         }
+        Ok(())
+    }
 
-        let index_size = (4 + fileentry_size + 4 + (index_count as usize + 1) * 8 + 4) as u32;
-
-        // Build header in memory (only header data, not file content)
-        self.header_data.clear();
-        self.header_data.extend_from_slice(format::PF8_MAGIC);
-        self.header_data
-            .extend_from_slice(&index_size.to_le_bytes());
-        self.header_data
-            .extend_from_slice(&index_count.to_le_bytes());
+    /// Writes the archive header with file entries
+    ///
+    /// Entry offsets are ignored: data is laid out back to back in the given
+    /// order, which is also the order it must be written in.
+    pub fn write_header(&mut self, entries: &[&Pf8Entry]) -> Result<()> {
+        if self.state != WriterState::Created {
+            return Err(Error::InvalidFormat("Header already written".to_string()));
+        }
 
-        // Write file entries
-        let mut file_offset = index_size + format::offsets::INDEX_DATA_START as u32;
-        let mut filesize_offsets = Vec::new();
+        let names = self.encode_names(entries)?;
 
+        // Offsets are 32-bit, so every entry must end within the first 4 GiB
+        let mut end = format::offsets::INDEX_DATA_START as u64
+            + format::index_size_for(names.iter().map(|name| name.len())) as u64;
         for entry in entries {
-            let name_bytes = entry.pf8_path().as_bytes();
-            let name_length = name_bytes.len() as u32;
-
-            // name_length
-            self.header_data
-                .extend_from_slice(&name_length.to_le_bytes());
-            // name
-            self.header_data.extend_from_slice(name_bytes);
-            // reserved
-            self.header_data
-                .extend_from_slice(&[0x00, 0x00, 0x00, 0x00]); // padding
-            // offset
-            self.header_data
-                .extend_from_slice(&file_offset.to_le_bytes());
-            // size
-            self.header_data
-                .extend_from_slice(&entry.size().to_le_bytes());
-
-            // Track the offset of the size field for later use
-            // offset from faddr 0xf
-            filesize_offsets.push(
-                (self.header_data.len() - 4 - format::offsets::FILESIZE_OFFSETS_START) as u64,
-            );
-            file_offset += entry.size();
+            end += entry.size_u64();
+            if end > u32::MAX as u64 {
+                return Err(Error::InvalidFormat(format!(
+                    "Entry {} would end at offset {}, beyond the 4 GiB format limit",
+                    entry.pf8_path(),
+                    end
+                )));
+            }
         }
 
-        // Write filesize count and offsets
-        self.header_data
-            .extend_from_slice(&(index_count + 1).to_le_bytes());
+        let index_entries = names
+            .iter()
+            .zip(entries)
+            .map(|(name, entry)| (name.as_ref(), entry.size()))
+            .collect::<Vec<_>>();
+        let header = format::build_header(self.format.magic(), &index_entries);
+        self.start_data(header)
+    }
 
-        let filesize_count_offset =
-            (self.header_data.len() - 4 - format::offsets::INDEX_DATA_START) as u32;
+    /// Writes the archive header, storing the offset of each entry as given
+    ///
+    /// The index keeps the given entry order while the data may follow a
+    /// different one. Offsets must cover the data region without gaps or
+    /// overlaps, starting right after the index, and data must be written in
+    /// offset order. Entries with the same offset and size share their data,
+    /// which is written once.
+    pub fn write_header_with_offsets(&mut self, entries: &[&Pf8Entry]) -> Result<()> {
+        if self.state != WriterState::Created {
+            return Err(Error::InvalidFormat("Header already written".to_string()));
+        }
 
-        for offset in filesize_offsets {
-            self.header_data.extend_from_slice(&offset.to_le_bytes());
+        let names = self.encode_names(entries)?;
+        let data_start = format::offsets::INDEX_DATA_START
+            + format::index_size_for(names.iter().map(|name| name.len()));
+        let mut by_offset = entries.to_vec();
+        by_offset.sort_by_key(|entry| (entry.offset(), entry.size()));
+        let mut expected = data_start as u64;
+        let mut previous: Option<&Pf8Entry> = None;
+        for entry in by_offset {
+            // Entries sharing the data of the previous one add nothing
+            if entry.size() > 0
+                && previous
+                    .is_some_and(|p| (p.offset(), p.size()) == (entry.offset(), entry.size()))
+            {
+                continue;
+            }
+            previous = Some(entry);
+            if entry.offset_u64() != expected {
+                return Err(Error::InvalidFormat(format!(
+                    "Entry {} at offset {} leaves a gap or overlap; expected offset {}",
+                    entry.pf8_path(),
+                    entry.offset(),
+                    expected
+                )));
+            }
+            expected += entry.size_u64();
         }
 
-        // End marker
-        self.header_data.extend_from_slice(&[0x00; 8]);
+        let index_entries = names
+            .iter()
+            .zip(entries)
+            .map(|(name, entry)| (name.as_ref(), entry.offset(), entry.size()))
+            .collect::<Vec<_>>();
+        let header = format::build_header_with_offsets(self.format.magic(), &index_entries);
+        self.start_data(header)
+    }
 
-        // Write filesize_count_offset
-        self.header_data
-            .extend_from_slice(&filesize_count_offset.to_le_bytes());
+    /// Encodes entry names for the index
+    fn encode_names<'a>(&self, entries: &[&'a Pf8Entry]) -> Result<Vec<Cow<'a, [u8]>>> {
+        entries
+            .iter()
+            .map(|entry| self.name_encoding.encode(entry.pf8_path()))
+            .collect()
+    }
 
+    /// Writes the header and derives the key, moving on to the data
+    fn start_data(&mut self, header: Vec<u8>) -> Result<()> {
         // Write header to file immediately
-        self.output.write_all(&self.header_data)?;
-        self.data_start_pos = self.output.stream_position()?;
-
-        // Generate and cache encryption key once
-        let index_size = format::get_index_size(&self.header_data)?;
-        self.encryption_key = Some(crypto::generate_key(&self.header_data, index_size));
+        self.write_output(&header)?;
+        self.header_data = header;
+        self.data_start_pos = self.written;
+
+        // Generate and cache encryption key once, straight from the header
+        // bytes: the index is everything after the `index_size` field
+        if self.format == ArchiveFormat::Pf8 {
+            let index_size = (self.header_data.len() - format::offsets::INDEX_DATA_START) as u32;
+            self.encryption_key = Some(crypto::generate_key(&self.header_data, index_size));
+        }
 
         self.state = WriterState::HeaderWritten;
         Ok(())
@@ -161,7 +306,7 @@ impl Pf8Writer {
         }
 
         // Write data directly to file instead of buffering
-        self.output.write_all(data)?;
+        self.write_output(data)?;
         self.state = WriterState::WritingData;
 
         Ok(())
@@ -189,51 +334,84 @@ impl Pf8Writer {
             return Err(Error::InvalidFormat("Writer is finalized".to_string()));
         }
 
-        use std::io::Read;
-        let mut source_file = std::fs::File::open(source_path)?;
-        let expected_size = entry.size() as u64;
+        let source_file = std::fs::File::open(source_path)?;
+        self.write_file_data_from(entry, source_file)
+    }
+
+    /// Writes file data streamed from any reader
+    ///
+    /// Exactly `entry.size()` bytes are read from `source` and encrypted
+    /// on-the-fly if the entry requires it, like [`Self::write_file_data`].
+    pub fn write_file_data_from<R: Read>(&mut self, entry: &Pf8Entry, source: R) -> Result<()> {
+        self.write_file_data_chunked(entry, source, |_| Ok(()))
+    }
+
+    /// Streams file data like [`Self::write_file_data_from`], calling
+    /// `on_chunk` with the number of bytes of the entry written so far after
+    /// each chunk
+    ///
+    /// An error returned by `on_chunk` stops the write and is returned as is.
+    pub(crate) fn write_file_data_chunked<R: Read, F: FnMut(u64) -> Result<()>>(
+        &mut self,
+        entry: &Pf8Entry,
+        mut source: R,
+        mut on_chunk: F,
+    ) -> Result<()> {
+        if self.state == WriterState::Created {
+            return Err(Error::InvalidFormat(
+                "Header must be written first".to_string(),
+            ));
+        }
+
+        if self.state == WriterState::Finalized {
+            return Err(Error::InvalidFormat("Writer is finalized".to_string()));
+        }
+
+        let expected_size = entry.size_u64();
         let use_encryption = entry.is_encrypted();
         let mut total_written = 0u64;
 
         // For small files, read entirely to minimize overhead
-        if expected_size <= BUFFER_SIZE as u64 {
+        if expected_size <= self.buffer_size as u64 {
             let mut data = vec![0u8; expected_size as usize];
-            source_file.read_exact(&mut data)?;
+            source.read_exact(&mut data)?;
 
             // Apply encryption if needed
             if use_encryption
                 && self.encryption_key.is_some()
                 && let Some(ref key) = self.encryption_key
             {
-                crypto::encrypt(&mut data, key, 0);
+                crypto::encrypt(&mut data, key, 0)?;
             }
 
             // Write all at once
-            self.output.write_all(&data)?;
+            self.write_output(&data)?;
             total_written = expected_size;
+            on_chunk(total_written)?;
         } else {
             // For large files, use streaming with optimized buffer reuse
-            let mut buffer = vec![0u8; BUFFER_SIZE];
+            let mut buffer = vec![0u8; self.buffer_size];
 
             while total_written < expected_size {
                 let remaining = expected_size - total_written;
-                let chunk_size = std::cmp::min(BUFFER_SIZE as u64, remaining) as usize;
+                let chunk_size = std::cmp::min(self.buffer_size as u64, remaining) as usize;
 
-                // Read chunk from source file
-                source_file.read_exact(&mut buffer[..chunk_size])?;
+                // Read chunk from source
+                source.read_exact(&mut buffer[..chunk_size])?;
 
                 // Apply encryption if needed, using cached key
                 if use_encryption
                     && self.encryption_key.is_some()
                     && let Some(ref key) = self.encryption_key
                 {
-                    crypto::encrypt(&mut buffer[..chunk_size], key, total_written as usize);
+                    crypto::encrypt(&mut buffer[..chunk_size], key, total_written as usize)?;
                 }
 
                 // Write chunk to output (already encrypted if needed)
-                self.output.write_all(&buffer[..chunk_size])?;
+                self.write_output(&buffer[..chunk_size])?;
 
                 total_written += chunk_size as u64;
+                on_chunk(total_written)?;
             }
         }
 
@@ -263,6 +441,9 @@ impl Pf8Writer {
 
         // Ensure all data is written to disk
         self.output.flush()?;
+        if let Some(hasher) = self.hasher.take() {
+            self.archive_hash = Some(hasher.finalize().into());
+        }
 
         self.state = WriterState::Finalized;
         Ok(())
@@ -270,8 +451,23 @@ impl Pf8Writer {
 
     /// Gets the current size of the archive
     pub fn size(&mut self) -> usize {
-        // Return current file position
-        self.output.stream_position().unwrap_or(0) as usize
+        self.written as usize
+    }
+
+    /// Flushes the written archive to disk
+    ///
+    /// Writers created with [`Self::from_writer`] are only flushed.
+    pub fn sync_all(&mut self) -> Result<()> {
+        match &mut self.output {
+            Output::File(file) => file.sync_all()?,
+            Output::Stream(stream) => stream.flush()?,
+        }
+        Ok(())
+    }
+
+    /// Gets the encryption key, available once the header has been written
+    pub fn encryption_key(&self) -> Option<&[u8]> {
+        self.encryption_key.as_deref()
     }
 
     /// Checks if the writer is finalized
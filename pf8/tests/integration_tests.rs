@@ -5,7 +5,7 @@ use pf8::{
     *,
 };
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tempfile::TempDir;
 
 #[test]
@@ -104,7 +104,7 @@ fn test_builder_add_file_as() {
         .unwrap();
     builder.write_to_file(&archive_path).unwrap();
 
-    let mut archive = Pf8Archive::open(&archive_path).unwrap();
+    let archive = Pf8Archive::open(&archive_path).unwrap();
     assert!(archive.contains("custom/path/file.txt"));
 
     let content = archive.read_file("custom/path/file.txt").unwrap();
@@ -145,7 +145,7 @@ fn test_reader_low_level_api() {
     create_from_dir(&input_dir, &archive_path).unwrap();
 
     // Test low-level reader API
-    let mut reader = Pf8Reader::open(&archive_path).unwrap();
+    let reader = Pf8Reader::open(&archive_path).unwrap();
 
     assert_eq!(reader.len(), 1);
     assert!(!reader.is_empty());
@@ -321,3 +321,3930 @@ fn test_pack_unpack_integrity() {
         .unwrap();
     assert_eq!(nested_content, b"Deep nested content");
 }
+
+#[test]
+fn test_entries_by_offset_extraction() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+
+    fs::create_dir_all(input_dir.join("sub")).unwrap();
+    fs::write(input_dir.join("b.txt"), b"second file").unwrap();
+    fs::write(input_dir.join("a.bin"), [7u8; 300]).unwrap();
+    fs::write(input_dir.join("sub").join("c.dat"), b"nested data").unwrap();
+    create_from_dir(&input_dir, &archive_path).unwrap();
+
+    let mut archive = Pf8Archive::open(&archive_path).unwrap();
+
+    let offsets: Vec<u32> = archive
+        .entries_by_offset()
+        .iter()
+        .map(|e| e.offset())
+        .collect();
+    assert_eq!(offsets.len(), 3);
+    assert!(offsets.windows(2).all(|w| w[0] <= w[1]));
+
+    let index_dir = temp_dir.path().join("index_order");
+    archive.extract_all(&index_dir).unwrap();
+
+    let offset_dir = temp_dir.path().join("offset_order");
+    archive.set_offset_order(true);
+    archive.extract_all(&offset_dir).unwrap();
+
+    for name in ["a.bin", "b.txt", "sub/c.dat"] {
+        assert_eq!(
+            fs::read(index_dir.join(name)).unwrap(),
+            fs::read(offset_dir.join(name)).unwrap()
+        );
+    }
+}
+
+#[test]
+fn test_pack_and_unpack_results() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+    let output_dir = temp_dir.path().join("output");
+
+    fs::create_dir_all(input_dir.join("sub")).unwrap();
+    fs::write(input_dir.join("one.txt"), b"12345").unwrap();
+    fs::write(input_dir.join("two.bin"), [0u8; 100]).unwrap();
+    fs::write(input_dir.join("sub").join("three.txt"), b"abc").unwrap();
+
+    let packed = create_from_dir(&input_dir, &archive_path).unwrap();
+    assert_eq!(packed.files, 3);
+    assert_eq!(packed.bytes, 108);
+    assert_eq!(
+        packed.archive_size,
+        fs::metadata(&archive_path).unwrap().len()
+    );
+
+    let unpacked = extract(&archive_path, &output_dir).unwrap();
+    assert_eq!(
+        unpacked,
+        UnpackResult {
+            files: 3,
+            bytes: 108,
+            skipped: 0,
+        }
+    );
+}
+
+#[test]
+fn test_lookup_with_either_separator() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_file = temp_dir.path().join("input.txt");
+    let archive_path = temp_dir.path().join("test.pfs");
+
+    fs::write(&input_file, b"nested").unwrap();
+
+    let mut builder = Pf8Builder::new();
+    builder.add_file_as(&input_file, "a/b/c.txt").unwrap();
+    builder.write_to_file(&archive_path).unwrap();
+
+    let archive = Pf8Archive::open(&archive_path).unwrap();
+    assert_eq!(archive.entries().next().unwrap().pf8_path(), "a\\b\\c.txt");
+
+    assert_eq!(archive.read_file("a/b/c.txt").unwrap(), b"nested");
+    assert_eq!(archive.read_file("a\\b\\c.txt").unwrap(), b"nested");
+    assert!(archive.contains("a/b\\c.txt"));
+}
+
+#[test]
+fn test_scan_dir_reports_all_archives() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archives_dir = temp_dir.path().join("archives");
+
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::create_dir_all(&archives_dir).unwrap();
+    fs::write(input_dir.join("file.txt"), b"content").unwrap();
+
+    for name in ["a.pfs", "b.pfs", "b.pfs.000"] {
+        create_from_dir(&input_dir, archives_dir.join(name)).unwrap();
+    }
+    fs::write(archives_dir.join("notes.txt"), b"not an archive").unwrap();
+
+    let infos: Vec<ArchiveInfo> = scan_dir(&archives_dir).collect::<Result<Vec<_>>>().unwrap();
+
+    let names: Vec<_> = infos
+        .iter()
+        .map(|info| info.path.file_name().unwrap().to_string_lossy().to_string())
+        .collect();
+    assert_eq!(names, ["a.pfs", "b.pfs", "b.pfs.000"]);
+
+    for info in &infos {
+        assert_eq!(info.format, ArchiveFormat::Pf8);
+        assert_eq!(info.entry_count, 1);
+        assert_eq!(info.total_size, 7);
+        assert_eq!(info.file_size, fs::metadata(&info.path).unwrap().len());
+    }
+}
+
+#[test]
+fn test_manifest_paths() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+    let output_dir = temp_dir.path().join("output");
+
+    fs::create_dir_all(input_dir.join("scripts")).unwrap();
+    fs::write(input_dir.join("scripts").join("main.ast"), b"main").unwrap();
+    create_from_dir(&input_dir, &archive_path).unwrap();
+
+    let archive = Pf8Archive::open(&archive_path).unwrap();
+    let manifest = archive.manifest().with_output_dir(&output_dir);
+
+    assert_eq!(manifest.entries.len(), 1);
+    let entry = &manifest.entries[0];
+    assert_eq!(entry.archive_path, "scripts/main.ast");
+    assert_eq!(
+        entry.output_path.as_deref(),
+        Some(output_dir.join("scripts").join("main.ast").as_path())
+    );
+    assert_eq!(entry.display_path(PathStyle::Archive), "scripts/main.ast");
+    assert_eq!(
+        entry.display_path(PathStyle::Output),
+        output_dir
+            .join("scripts")
+            .join("main.ast")
+            .display()
+            .to_string()
+    );
+}
+
+#[test]
+fn test_control_characters_in_names() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_file = temp_dir.path().join("input.txt");
+    let archive_path = temp_dir.path().join("test.pfs");
+
+    fs::write(&input_file, b"payload").unwrap();
+
+    let mut builder = Pf8Builder::new();
+    builder.add_file_as(&input_file, "bad\nname.txt").unwrap();
+    builder.write_to_file(&archive_path).unwrap();
+
+    let mut archive = Pf8Archive::open(&archive_path).unwrap();
+
+    // Rejected by default
+    let rejected_dir = temp_dir.path().join("rejected");
+    let result = archive.extract_all(&rejected_dir);
+    assert!(matches!(result, Err(Error::UnsafePath(_))));
+    assert!(!rejected_dir.join("bad\nname.txt").exists());
+
+    // Sanitized when requested
+    let sanitized_dir = temp_dir.path().join("sanitized");
+    archive.set_sanitize_names(true);
+    archive.extract_all(&sanitized_dir).unwrap();
+    assert_eq!(
+        fs::read(sanitized_dir.join("bad_name.txt")).unwrap(),
+        b"payload"
+    );
+}
+
+#[test]
+fn test_pack_reports_scanning_before_writing() {
+    struct PhaseRecorder {
+        phases: Vec<OperationPhase>,
+    }
+
+    impl ArchiveHandler for PhaseRecorder {
+        fn on_progress(&mut self, info: &ProgressInfo) -> ControlAction {
+            self.phases.push(info.phase);
+            ControlAction::Continue
+        }
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("a.txt"), b"a").unwrap();
+    fs::write(input_dir.join("b.txt"), b"bb").unwrap();
+
+    let mut builder = Pf8Builder::new();
+    builder.add_dir(&input_dir).unwrap();
+
+    let mut handler = PhaseRecorder { phases: Vec::new() };
+    builder
+        .write_to_file_with_progress(&archive_path, &mut handler)
+        .unwrap();
+
+    assert_eq!(
+        handler.phases,
+        [
+            OperationPhase::Scanning,
+            OperationPhase::Scanning,
+            // Totals announced before the first entry
+            OperationPhase::Writing,
+            OperationPhase::Writing,
+            OperationPhase::Writing,
+        ]
+    );
+}
+
+#[test]
+fn test_estimate_archive_size_matches_packed_size() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("game");
+    let archive_path = temp_dir.path().join("test.pfs");
+
+    fs::create_dir_all(input_dir.join("scripts")).unwrap();
+    fs::write(input_dir.join("system.ini"), b"[system]").unwrap();
+    fs::write(input_dir.join("scripts").join("main.ast"), [1u8; 1000]).unwrap();
+
+    let estimate = estimate_archive_size(&input_dir, DirPolicy::Contents).unwrap();
+    create_from_dir(&input_dir, &archive_path).unwrap();
+    assert_eq!(estimate.entry_count, 2);
+    assert_eq!(estimate.data_size, 1008);
+    assert_eq!(
+        estimate.total_size,
+        fs::metadata(&archive_path).unwrap().len()
+    );
+
+    let estimate = estimate_archive_size(&input_dir, DirPolicy::PreserveName).unwrap();
+    let mut builder = Pf8Builder::new();
+    builder.add_dir_as(&input_dir, "game").unwrap();
+    builder.write_to_file(&archive_path).unwrap();
+    assert_eq!(
+        estimate.total_size,
+        fs::metadata(&archive_path).unwrap().len()
+    );
+}
+
+#[test]
+fn test_zero_size_entry_between_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+    let output_dir = temp_dir.path().join("output");
+
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("a.bin"), [1u8; 64]).unwrap();
+    fs::write(input_dir.join("b.bin"), b"").unwrap();
+    fs::write(input_dir.join("c.bin"), [3u8; 64]).unwrap();
+    create_from_dir(&input_dir, &archive_path).unwrap();
+
+    let mut archive = Pf8Archive::open(&archive_path).unwrap();
+    let empty = archive.get_entry("b.bin").unwrap();
+    assert_eq!(empty.size(), 0);
+    assert_eq!(empty.offset(), archive.get_entry("c.bin").unwrap().offset());
+    assert_eq!(archive.read_file("b.bin").unwrap(), b"");
+
+    archive.extract_all(&output_dir).unwrap();
+    assert_eq!(fs::read(output_dir.join("a.bin")).unwrap(), [1u8; 64]);
+    assert_eq!(fs::read(output_dir.join("b.bin")).unwrap(), b"");
+    assert_eq!(fs::read(output_dir.join("c.bin")).unwrap(), [3u8; 64]);
+}
+
+#[test]
+fn test_builder_chained_by_value() -> Result<()> {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let extra_file = temp_dir.path().join("extra.txt");
+    let archive_path = temp_dir.path().join("test.pfs");
+
+    fs::create_dir_all(&input_dir)?;
+    fs::write(input_dir.join("a.txt"), b"a")?;
+    fs::write(&extra_file, b"extra")?;
+
+    Pf8Builder::new()
+        .with_dir_as(&input_dir, "data")?
+        .with_file_as(&extra_file, "misc/extra.txt")?
+        .build_to(&archive_path)?;
+
+    let archive = Pf8Archive::open(&archive_path)?;
+    assert_eq!(archive.len(), 2);
+    assert_eq!(archive.read_file("data/a.txt")?, b"a");
+    assert_eq!(archive.read_file("misc/extra.txt")?, b"extra");
+    Ok(())
+}
+
+#[test]
+fn test_export_web_index_ranges() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+
+    fs::create_dir_all(input_dir.join("images")).unwrap();
+    fs::write(input_dir.join("images").join("bg.png"), [9u8; 200]).unwrap();
+    fs::write(input_dir.join("movie.mp4"), b"plain movie bytes").unwrap();
+    create_from_dir(&input_dir, &archive_path).unwrap();
+
+    let reader = Pf8Reader::open(&archive_path).unwrap();
+    let index = export_web_index(&reader);
+
+    assert!(index.starts_with(&format!(
+        r#"{{"archive_size":{},"entries":["#,
+        fs::metadata(&archive_path).unwrap().len()
+    )));
+
+    let key = reader.encryption_key().unwrap().to_vec();
+    let raw = fs::read(&archive_path).unwrap();
+    let entries: Vec<Pf8Entry> = reader.entries().cloned().collect();
+    for entry in entries {
+        let path = entry.path().to_string_lossy().replace('\\', "/");
+        assert!(index.contains(&format!(
+            r#"{{"path":"{}","offset":{},"size":{},"encrypted":{}}}"#,
+            path,
+            entry.offset(),
+            entry.size(),
+            entry.is_encrypted()
+        )));
+
+        // A range read of the advertised bytes yields the entry data
+        let start = entry.offset() as usize;
+        let mut data = raw[start..start + entry.size() as usize].to_vec();
+        if entry.is_encrypted() {
+            for (i, byte) in data.iter_mut().enumerate() {
+                *byte ^= key[i % key.len()];
+            }
+        }
+        assert_eq!(data, reader.read_file(entry.path()).unwrap());
+    }
+}
+
+#[test]
+fn test_planned_offsets_match_parsed_offsets() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+
+    fs::create_dir_all(input_dir.join("nested").join("deep")).unwrap();
+    fs::write(input_dir.join("a.txt"), b"first").unwrap();
+    fs::write(input_dir.join("empty.dat"), b"").unwrap();
+    fs::write(input_dir.join("nested").join("b.bin"), [5u8; 4096]).unwrap();
+    fs::write(
+        input_dir.join("nested").join("deep").join("c.txt"),
+        b"third",
+    )
+    .unwrap();
+
+    let mut builder = Pf8Builder::new();
+    builder.add_dir(&input_dir).unwrap();
+    let planned = builder.planned_entries().unwrap();
+    builder.write_to_file_verified(&archive_path).unwrap();
+
+    let reader = Pf8Reader::open(&archive_path).unwrap();
+    assert_eq!(planned.len(), 4);
+    for (planned, parsed) in planned.iter().zip(reader.entries()) {
+        assert_eq!(planned.pf8_path(), parsed.pf8_path());
+        assert_eq!(planned.offset(), parsed.offset());
+        assert_eq!(planned.size(), parsed.size());
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_skip_unreadable_files() {
+    use std::os::unix::fs::PermissionsExt;
+
+    struct WarningRecorder {
+        warnings: Vec<String>,
+    }
+
+    impl ArchiveHandler for WarningRecorder {
+        fn on_warning(&mut self, message: &str) -> ControlAction {
+            self.warnings.push(message.to_string());
+            ControlAction::Continue
+        }
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("good.txt"), b"readable").unwrap();
+    let locked = input_dir.join("locked.txt");
+    fs::write(&locked, b"secret").unwrap();
+
+    let mut builder = Pf8Builder::new();
+    builder.add_dir(&input_dir).unwrap();
+
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+    if fs::File::open(&locked).is_ok() {
+        // Permissions are not enforced for privileged users; a vanished
+        // source is just as unreadable
+        fs::remove_file(&locked).unwrap();
+    }
+
+    // Aborts by default
+    assert!(builder.write_to_file(&archive_path).is_err());
+
+    let mut handler = WarningRecorder {
+        warnings: Vec::new(),
+    };
+    builder.skip_unreadable(true);
+    builder
+        .write_to_file_with_progress(&archive_path, &mut handler)
+        .unwrap();
+
+    assert_eq!(handler.warnings.len(), 1);
+    assert!(handler.warnings[0].contains("locked.txt"));
+
+    let archive = Pf8Archive::open(&archive_path).unwrap();
+    assert_eq!(archive.len(), 1);
+    assert!(!archive.contains("locked.txt"));
+    assert_eq!(archive.read_file("good.txt").unwrap(), b"readable");
+
+    let _ = fs::set_permissions(&locked, fs::Permissions::from_mode(0o644));
+}
+
+#[test]
+fn test_entry_slice_borrows_backing_buffer() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("intro.mp4"), b"plain video data").unwrap();
+    fs::write(input_dir.join("script.txt"), b"encrypted text").unwrap();
+    create_from_dir(&input_dir, &archive_path).unwrap();
+
+    let reader = Pf8Reader::from_bytes(fs::read(&archive_path).unwrap()).unwrap();
+    assert_eq!(reader.read_file("script.txt").unwrap(), b"encrypted text");
+
+    let backing = reader.as_bytes().unwrap().as_ptr_range();
+    let slice = reader.entry_slice("intro.mp4").unwrap();
+    assert_eq!(slice, b"plain video data");
+    assert!(backing.contains(&slice.as_ptr()));
+
+    assert!(reader.entry_slice("script.txt").is_none());
+    assert!(reader.entry_slice("missing.txt").is_none());
+
+    let file_reader = Pf8Reader::open(&archive_path).unwrap();
+    assert!(file_reader.entry_slice("intro.mp4").is_none());
+}
+
+#[test]
+#[cfg(feature = "inflate")]
+fn test_read_file_inflate() {
+    use flate2::{Compression, write::ZlibEncoder};
+    use std::io::Write;
+
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+
+    let plaintext = "compressed script line\n".repeat(200);
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(plaintext.as_bytes()).unwrap();
+    let compressed = encoder.finish().unwrap();
+
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("script.z"), &compressed).unwrap();
+    create_from_dir(&input_dir, &archive_path).unwrap();
+
+    let mut reader = Pf8Reader::open(&archive_path).unwrap();
+    assert!(reader.get_entry("script.z").unwrap().is_encrypted());
+    assert_eq!(reader.read_file("script.z").unwrap(), compressed);
+    assert_eq!(
+        reader
+            .read_file_inflate("script.z", InnerCodec::Zlib)
+            .unwrap(),
+        plaintext.as_bytes()
+    );
+    assert!(matches!(
+        reader.read_file_inflate("script.z", InnerCodec::Gzip),
+        Err(Error::Corrupted(_))
+    ));
+}
+
+#[test]
+fn test_names_and_key_stable_across_round_trip() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let packed_path = temp_dir.path().join("packed.pfs");
+    let repacked_path = temp_dir.path().join("repacked.pfs");
+
+    fs::create_dir_all(input_dir.join("dir")).unwrap();
+    fs::write(input_dir.join("dir").join("a.txt"), b"alpha").unwrap();
+    fs::write(input_dir.join("b.bin"), b"beta").unwrap();
+    create_from_dir(&input_dir, &packed_path).unwrap();
+    merge(&[&packed_path], &repacked_path, ConflictPolicy::Error).unwrap();
+
+    let packed = Pf8Reader::open(&packed_path).unwrap();
+    let repacked = Pf8Reader::open(&repacked_path).unwrap();
+    let names = |reader: &Pf8Reader| {
+        reader
+            .entries()
+            .map(|e| e.pf8_path().to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names(&packed), ["b.bin", "dir\\a.txt"]);
+    assert_eq!(names(&packed), names(&repacked));
+    assert_eq!(packed.encryption_key(), repacked.encryption_key());
+    assert_eq!(
+        fs::read(&packed_path).unwrap(),
+        fs::read(&repacked_path).unwrap()
+    );
+
+    // NUL-terminated names written by other tools are read without the
+    // terminator and rewritten without it
+    let name = b"b.bin\0";
+    let index_size = 4 + (4 + name.len() + 12) + 4 + 2 * 8 + 4;
+    let mut pf6 = b"pf6".to_vec();
+    pf6.extend_from_slice(&(index_size as u32).to_le_bytes());
+    pf6.extend_from_slice(&1u32.to_le_bytes());
+    pf6.extend_from_slice(&(name.len() as u32).to_le_bytes());
+    pf6.extend_from_slice(name);
+    pf6.extend_from_slice(&[0; 4]);
+    pf6.extend_from_slice(&((7 + index_size) as u32).to_le_bytes());
+    pf6.extend_from_slice(&4u32.to_le_bytes());
+    let size_field_offset = pf6.len() - 4 - 0x0F;
+    pf6.extend_from_slice(&2u32.to_le_bytes());
+    let filesize_count_offset = pf6.len() - 4 - 0x07;
+    pf6.extend_from_slice(&(size_field_offset as u64).to_le_bytes());
+    pf6.extend_from_slice(&[0; 8]);
+    pf6.extend_from_slice(&(filesize_count_offset as u32).to_le_bytes());
+    pf6.extend_from_slice(b"beta");
+    let pf6_path = temp_dir.path().join("terminated.pfs");
+    fs::write(&pf6_path, &pf6).unwrap();
+
+    let terminated = Pf8Reader::open(&pf6_path).unwrap();
+    assert_eq!(names(&terminated), ["b.bin"]);
+    assert_eq!(terminated.read_file("b.bin").unwrap(), b"beta");
+
+    let rewritten_path = temp_dir.path().join("rewritten.pfs");
+    merge(&[&pf6_path], &rewritten_path, ConflictPolicy::Error).unwrap();
+    let rewritten = Pf8Reader::open(&rewritten_path).unwrap();
+    assert_eq!(names(&rewritten), ["b.bin"]);
+    assert_eq!(rewritten.read_file("b.bin").unwrap(), b"beta");
+}
+
+#[test]
+fn test_for_each_entry_streams_all_entries() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+
+    fs::create_dir_all(input_dir.join("text")).unwrap();
+    fs::write(input_dir.join("text").join("a.txt"), b"searchable words").unwrap();
+    fs::write(input_dir.join("text").join("b.txt"), b"more words").unwrap();
+    fs::write(input_dir.join("movie.mp4"), [0u8; 64]).unwrap();
+    create_from_dir(&input_dir, &archive_path).unwrap();
+
+    let mut reader = Pf8Reader::open(&archive_path).unwrap();
+    let mut seen = 0u64;
+    let mut texts = Vec::new();
+    reader
+        .for_each_entry(|entry, data| {
+            assert_eq!(data.len(), entry.size() as usize);
+            seen += data.len() as u64;
+            if entry.path().extension().is_some_and(|ext| ext == "txt") {
+                texts.push(String::from_utf8_lossy(data).to_string());
+            }
+            Ok(())
+        })
+        .unwrap();
+
+    assert_eq!(seen, reader.total_size());
+    assert_eq!(texts, ["searchable words", "more words"]);
+
+    // An error from the callback aborts the iteration
+    let mut calls = 0;
+    let result = reader.for_each_entry(|_, _| {
+        calls += 1;
+        Err(Error::Cancelled)
+    });
+    assert!(matches!(result, Err(Error::Cancelled)));
+    assert_eq!(calls, 1);
+}
+
+#[test]
+#[cfg(feature = "encoding")]
+fn test_shift_jis_name_round_trip() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_file = temp_dir.path().join("input.txt");
+    let utf8_path = temp_dir.path().join("utf8.pfs");
+    let sjis_path = temp_dir.path().join("sjis.pfs");
+
+    fs::write(&input_file, b"scenario").unwrap();
+
+    let mut builder = Pf8Builder::new();
+    builder
+        .add_file_as(&input_file, "シナリオ/開始.txt")
+        .unwrap();
+    builder.write_to_file(&utf8_path).unwrap();
+    builder.name_encoding(NameEncoding::ShiftJis);
+    builder.write_to_file_verified(&sjis_path).unwrap();
+
+    // Two bytes per character instead of three
+    let utf8_size = fs::metadata(&utf8_path).unwrap().len();
+    let sjis_size = fs::metadata(&sjis_path).unwrap().len();
+    assert_eq!(utf8_size - sjis_size, 6);
+
+    let options = ReaderOptions {
+        name_encoding: NameEncoding::ShiftJis,
+        ..Default::default()
+    };
+    let reader = Pf8Reader::open_with(&sjis_path, options).unwrap();
+    assert_eq!(
+        reader.entries().next().unwrap().pf8_path(),
+        "シナリオ\\開始.txt"
+    );
+    assert_eq!(reader.read_file("シナリオ/開始.txt").unwrap(), b"scenario");
+    assert_ne!(
+        reader.encryption_key(),
+        Pf8Reader::open(&utf8_path).unwrap().encryption_key()
+    );
+
+    // Shift-JIS names are not valid UTF-8
+    assert!(Pf8Reader::open(&sjis_path).is_err());
+}
+
+#[test]
+fn test_extension_queries() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+
+    // Same file set as the integrity fixture
+    let files = [
+        "readme.txt",
+        "config.ini",
+        "data.bin",
+        "image.jpg",
+        "empty.txt",
+        "large.txt",
+        "nested/deep/structure/nested_file.txt",
+        "nested/deep/structure/binary.dat",
+        "special/file with spaces.txt",
+        "special/file-with-dashes.txt",
+        "special/file_with_underscores.txt",
+    ];
+    for file in files {
+        let path = input_dir.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, b"x").unwrap();
+    }
+    create_from_dir(&input_dir, &archive_path).unwrap();
+
+    let reader = Pf8Reader::open(&archive_path).unwrap();
+    let histogram = reader.count_by_extension();
+    let expected: std::collections::HashMap<String, usize> =
+        [("txt", 7), ("ini", 1), ("bin", 1), ("jpg", 1), ("dat", 1)]
+            .into_iter()
+            .map(|(ext, count)| (ext.to_string(), count))
+            .collect();
+    assert_eq!(histogram, expected);
+
+    assert!(reader.has_any_extension(&["png", ".JPG"]));
+    assert!(!reader.has_any_extension(&["png", "ogg"]));
+}
+
+#[test]
+fn test_read_file_prefix() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    png.extend((0..5000u32).map(|i| (i % 251) as u8));
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("image.png"), &png).unwrap();
+    fs::write(input_dir.join("tiny.txt"), b"abc").unwrap();
+    create_from_dir(&input_dir, &archive_path).unwrap();
+
+    let mut reader = Pf8Reader::open(&archive_path).unwrap();
+    assert!(reader.get_entry("image.png").unwrap().is_encrypted());
+
+    let full = reader.read_file("image.png").unwrap();
+    for n in [0, 8, 16, 100] {
+        assert_eq!(reader.read_file_prefix("image.png", n).unwrap(), full[..n]);
+    }
+    assert!(
+        reader
+            .read_file_prefix("image.png", 8)
+            .unwrap()
+            .starts_with(b"\x89PNG")
+    );
+
+    // Clamped to the entry size
+    assert_eq!(reader.read_file_prefix("tiny.txt", 16).unwrap(), b"abc");
+}
+
+#[test]
+fn test_reproducible_packs_are_identical() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+
+    fs::create_dir_all(input_dir.join("b")).unwrap();
+    fs::write(input_dir.join("b").join("z.txt"), b"last").unwrap();
+    fs::write(input_dir.join("a.bin"), [1u8; 300]).unwrap();
+    fs::write(input_dir.join("c.mp4"), b"video").unwrap();
+
+    let pack = |name: &str| {
+        let archive_path = temp_dir.path().join(name);
+        let mut builder = Pf8Builder::new();
+        builder.reproducible();
+        assert!(builder.is_reproducible());
+        builder.add_dir(&input_dir).unwrap();
+        builder.write_to_file(&archive_path).unwrap();
+
+        let reader = Pf8Reader::open(&archive_path).unwrap();
+        (fs::read(&archive_path).unwrap(), export_web_index(&reader))
+    };
+
+    let (first_archive, first_manifest) = pack("first.pfs");
+    // Touch a source so its modification time differs between packs
+    fs::write(input_dir.join("a.bin"), [1u8; 300]).unwrap();
+    let (second_archive, second_manifest) = pack("second.pfs");
+
+    assert_eq!(first_archive, second_archive);
+    assert_eq!(first_manifest.as_bytes(), second_manifest.as_bytes());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_open_no_follow_refuses_symlinks() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+    let link_path = temp_dir.path().join("link.pfs");
+
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("file.txt"), b"content").unwrap();
+    create_from_dir(&input_dir, &archive_path).unwrap();
+    std::os::unix::fs::symlink(&archive_path, &link_path).unwrap();
+
+    assert_eq!(Pf8Reader::open(&link_path).unwrap().len(), 1);
+    assert_eq!(Pf8Reader::open_no_follow(&archive_path).unwrap().len(), 1);
+    assert!(Pf8Reader::open_no_follow(&link_path).is_err());
+}
+
+#[test]
+fn test_size_by_extension() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+
+    fs::create_dir_all(input_dir.join("sound")).unwrap();
+    fs::write(input_dir.join("sound").join("bgm.ogg"), [0u8; 800]).unwrap();
+    fs::write(input_dir.join("sound").join("se.OGG"), [0u8; 100]).unwrap();
+    fs::write(input_dir.join("script.txt"), [0u8; 90]).unwrap();
+    fs::write(input_dir.join("LICENSE"), [0u8; 10]).unwrap();
+    create_from_dir(&input_dir, &archive_path).unwrap();
+
+    let reader = Pf8Reader::open(&archive_path).unwrap();
+    let sizes = reader.size_by_extension();
+    assert_eq!(sizes.len(), 3);
+    assert_eq!(sizes["ogg"], 900);
+    assert_eq!(sizes["txt"], 90);
+    assert_eq!(sizes[""], 10);
+    assert_eq!(sizes.values().sum::<u64>(), reader.total_size());
+}
+
+#[test]
+fn test_forged_index_count_is_corruption() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("a.txt"), b"first").unwrap();
+    fs::write(input_dir.join("b.txt"), b"second").unwrap();
+    create_from_dir(&input_dir, &archive_path).unwrap();
+    let original = fs::read(&archive_path).unwrap();
+
+    let forge = |patch: &dyn Fn(&mut Vec<u8>)| {
+        let mut data = original.clone();
+        patch(&mut data);
+        Pf8Reader::from_bytes(data)
+    };
+
+    // Oversized entry count
+    let result = forge(&|data| data[7..11].copy_from_slice(&1_000_000u32.to_le_bytes()));
+    assert!(matches!(result, Err(Error::Corrupted(_))));
+
+    // One entry more than stored, read from the filesize table
+    let result = forge(&|data| data[7..11].copy_from_slice(&3u32.to_le_bytes()));
+    assert!(matches!(result, Err(Error::Corrupted(_))));
+
+    // Index larger than the archive
+    let result = forge(&|data| data[3..7].copy_from_slice(&0x7fff_0000u32.to_le_bytes()));
+    assert!(matches!(result, Err(Error::Corrupted(_))));
+
+    // Entry data beyond the end of the archive
+    let result = forge(&|data| {
+        data.truncate(data.len() - 1);
+    });
+    assert!(matches!(result, Err(Error::Corrupted(_))));
+}
+
+#[test]
+fn test_extract_with_options() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+    let output_dir = temp_dir.path().join("output");
+
+    fs::create_dir_all(input_dir.join("game").join("script").join("debug")).unwrap();
+    fs::create_dir_all(input_dir.join("game").join("image")).unwrap();
+    fs::write(
+        input_dir.join("game").join("script").join("main.txt"),
+        b"main",
+    )
+    .unwrap();
+    fs::write(
+        input_dir.join("game").join("script").join("sub.txt"),
+        b"sub",
+    )
+    .unwrap();
+    fs::write(
+        input_dir
+            .join("game")
+            .join("script")
+            .join("debug")
+            .join("dbg.txt"),
+        b"debug",
+    )
+    .unwrap();
+    fs::write(input_dir.join("game").join("image").join("bg.png"), b"png").unwrap();
+    fs::write(input_dir.join("game").join("readme.txt"), b"readme").unwrap();
+    create_from_dir(&input_dir, &archive_path).unwrap();
+
+    let options = ExtractOptions {
+        include: vec!["game/script/**".to_string(), "game/*.txt".to_string()],
+        exclude: vec!["**/debug/*".to_string()],
+        strip_components: 1,
+        ..Default::default()
+    };
+    let result = extract_with_options(&archive_path, &output_dir, &options).unwrap();
+
+    assert_eq!(
+        result,
+        UnpackResult {
+            files: 3,
+            bytes: 13,
+            skipped: 2,
+        }
+    );
+    assert_eq!(
+        fs::read(output_dir.join("script").join("main.txt")).unwrap(),
+        b"main"
+    );
+    assert_eq!(
+        fs::read(output_dir.join("script").join("sub.txt")).unwrap(),
+        b"sub"
+    );
+    assert_eq!(fs::read(output_dir.join("readme.txt")).unwrap(), b"readme");
+    assert!(!output_dir.join("script").join("debug").exists());
+    assert!(!output_dir.join("image").exists());
+
+    // Existing files are kept or rejected depending on the policy
+    fs::write(output_dir.join("readme.txt"), b"local edit").unwrap();
+    let skip = ExtractOptions {
+        overwrite: OverwritePolicy::Skip,
+        ..options.clone()
+    };
+    let result = extract_with_options(&archive_path, &output_dir, &skip).unwrap();
+    assert_eq!(result.files, 0);
+    assert_eq!(result.skipped, 5);
+    assert_eq!(
+        fs::read(output_dir.join("readme.txt")).unwrap(),
+        b"local edit"
+    );
+
+    let error = ExtractOptions {
+        overwrite: OverwritePolicy::Error,
+        ..options
+    };
+    assert!(extract_with_options(&archive_path, &output_dir, &error).is_err());
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn test_synthetic_archive_from_bytes() {
+    use pf8::testing::{Corruption, build_archive, corrupt_index};
+
+    let archive = build_archive(&[("script/main.txt", b"hello"), ("movie/op.mp4", b"video")]);
+
+    let reader = Pf8Reader::from_bytes(archive.clone()).unwrap();
+    assert_eq!(reader.len(), 2);
+    assert!(reader.get_entry("script/main.txt").unwrap().is_encrypted());
+    assert!(!reader.get_entry("movie/op.mp4").unwrap().is_encrypted());
+    assert_eq!(reader.read_file("script/main.txt").unwrap(), b"hello");
+    assert_eq!(reader.read_file("movie/op.mp4").unwrap(), b"video");
+
+    let mut oversized = archive.clone();
+    corrupt_index(
+        &mut oversized,
+        Corruption::Size {
+            entry: 1,
+            size: 1024,
+        },
+    );
+    assert!(matches!(
+        Pf8Reader::from_bytes(oversized),
+        Err(Error::Corrupted(_))
+    ));
+
+    let mut truncated = archive;
+    corrupt_index(&mut truncated, Corruption::Truncate(20));
+    assert!(Pf8Reader::from_bytes(truncated).is_err());
+}
+
+#[test]
+fn test_packed_bytes_golden() {
+    use sha1::{Digest, Sha1};
+
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("golden.pfs");
+
+    fs::create_dir_all(input_dir.join("script")).unwrap();
+    fs::write(input_dir.join("script").join("main.txt"), b"golden script").unwrap();
+    fs::write(input_dir.join("op.mp4"), b"golden movie").unwrap();
+    fs::write(input_dir.join("system.ini"), vec![0xA5; 300]).unwrap();
+    create_from_dir(&input_dir, &archive_path).unwrap();
+
+    // Output must stay byte-identical across changes to key derivation
+    let digest = Sha1::digest(fs::read(&archive_path).unwrap());
+    let hex: String = digest.iter().map(|b| format!("{b:02x}")).collect();
+    assert_eq!(hex, "321d3e023cbc5829a1fd3ecd7f133c7da4a53506");
+}
+
+#[test]
+fn test_write_to_file_atomic_leaves_no_partial_output() {
+    // Truncates a source file after it has been planned, so reading it fails mid-pack
+    struct ShrinkSource {
+        victim: std::path::PathBuf,
+    }
+
+    impl ArchiveHandler for ShrinkSource {
+        fn on_entry_started(&mut self, name: &str) -> ControlAction {
+            if name == "b.txt" {
+                fs::write(&self.victim, b"").unwrap();
+            }
+            ControlAction::Continue
+        }
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("a.txt"), b"first file").unwrap();
+    fs::write(input_dir.join("b.txt"), b"second file").unwrap();
+
+    let mut builder = Pf8Builder::new();
+    builder.add_dir(&input_dir).unwrap();
+
+    let mut handler = ShrinkSource {
+        victim: input_dir.join("b.txt"),
+    };
+    assert!(
+        builder
+            .write_to_file_atomic_with_progress(&archive_path, &mut handler)
+            .is_err()
+    );
+    assert!(!archive_path.exists());
+    assert!(!temp_dir.path().join("test.pfs.tmp").exists());
+
+    // A successful atomic write leaves only the final archive
+    fs::write(input_dir.join("b.txt"), b"second file").unwrap();
+    builder.write_to_file_atomic(&archive_path).unwrap();
+    assert!(!temp_dir.path().join("test.pfs.tmp").exists());
+    let archive = Pf8Archive::open(&archive_path).unwrap();
+    assert_eq!(archive.read_file("b.txt").unwrap(), b"second file");
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn test_archive_format_reads_only_magic() {
+    use pf8::testing::{build_archive, build_pf6_archive};
+
+    let temp_dir = TempDir::new().unwrap();
+    let pf8_path = temp_dir.path().join("encrypted.pfs");
+    let pf6_path = temp_dir.path().join("plain.pfs");
+    fs::write(&pf8_path, build_archive(&[("a.txt", b"data")])).unwrap();
+    fs::write(&pf6_path, build_pf6_archive(&[("a.txt", b"data")])).unwrap();
+
+    assert_eq!(archive_format(&pf8_path).unwrap(), ArchiveFormat::Pf8);
+    assert_eq!(archive_format(&pf6_path).unwrap(), ArchiveFormat::Pf6);
+
+    // Only the magic is inspected, so a bare magic with no index still detects
+    let magic_only = temp_dir.path().join("magic_only.pfs");
+    fs::write(&magic_only, b"pf8").unwrap();
+    assert_eq!(archive_format(&magic_only).unwrap(), ArchiveFormat::Pf8);
+    assert!(Pf8Reader::open(&magic_only).is_err());
+
+    let not_archive = temp_dir.path().join("readme.txt");
+    fs::write(&not_archive, b"hello").unwrap();
+    assert!(matches!(
+        archive_format(&not_archive),
+        Err(Error::InvalidFormat(_))
+    ));
+}
+
+#[test]
+fn test_max_archive_size() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("a.txt"), vec![b'a'; 100]).unwrap();
+    fs::write(input_dir.join("b.txt"), vec![b'b'; 200]).unwrap();
+
+    let mut builder = Pf8Builder::new();
+    builder.add_dir(&input_dir).unwrap();
+    let projected = builder.estimated_size().unwrap().total_size;
+
+    builder.max_archive_size(projected - 1);
+    let err = builder.write_to_file(&archive_path).unwrap_err();
+    match err {
+        Error::InvalidFormat(message) => {
+            assert!(message.contains(&projected.to_string()));
+            assert!(message.contains(&(projected - 1).to_string()));
+        }
+        other => panic!("unexpected error: {other}"),
+    }
+    // Planning fails before the header is written
+    assert_eq!(fs::metadata(&archive_path).unwrap().len(), 0);
+
+    builder.max_archive_size(projected);
+    builder.write_to_file(&archive_path).unwrap();
+    assert_eq!(fs::metadata(&archive_path).unwrap().len(), projected);
+}
+
+#[test]
+fn test_copy_raw_to() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("script.txt"), b"encrypted on disk").unwrap();
+    fs::write(input_dir.join("empty.txt"), b"").unwrap();
+    create_from_dir(&input_dir, &archive_path).unwrap();
+
+    let mut reader = Pf8Reader::open(&archive_path).unwrap();
+    let entry = reader.get_entry("script.txt").unwrap().clone();
+    assert!(entry.is_encrypted());
+
+    let mut raw = Vec::new();
+    let copied = reader.copy_raw_to("script.txt", &mut raw).unwrap();
+    assert_eq!(copied, entry.size() as u64);
+
+    let file = fs::read(&archive_path).unwrap();
+    let start = entry.offset() as usize;
+    assert_eq!(raw, &file[start..start + entry.size() as usize]);
+    assert_ne!(raw, b"encrypted on disk");
+
+    // Only the requested prefix of the stored bytes is read
+    assert_eq!(
+        reader.read_file_raw_prefix("script.txt", 4).unwrap(),
+        raw[..4]
+    );
+    assert_eq!(
+        reader.read_file_raw_prefix("script.txt", 1000).unwrap(),
+        raw
+    );
+    assert!(
+        reader
+            .read_file_raw_prefix("empty.txt", 4)
+            .unwrap()
+            .is_empty()
+    );
+
+    let mut empty = Vec::new();
+    assert_eq!(reader.copy_raw_to("empty.txt", &mut empty).unwrap(), 0);
+    assert!(empty.is_empty());
+    assert!(matches!(
+        reader.copy_raw_to("missing.txt", &mut empty),
+        Err(Error::FileNotFound(_))
+    ));
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn test_check_layout_dedup_and_overlap() {
+    use pf8::testing::{Corruption, build_archive, corrupt_index};
+
+    let archive = build_archive(&[
+        ("a.txt", b"shared"),
+        ("b.txt", b"shared"),
+        ("c.txt", b"unique data"),
+    ]);
+    let first_offset = Pf8Reader::from_bytes(archive.clone())
+        .unwrap()
+        .get_entry("a.txt")
+        .unwrap()
+        .offset();
+
+    // b.txt points at the exact range of a.txt: deduplicated, not corrupt
+    let mut deduped = archive.clone();
+    corrupt_index(
+        &mut deduped,
+        Corruption::Offset {
+            entry: 1,
+            offset: first_offset,
+        },
+    );
+    let report = check_layout(&Pf8Reader::from_bytes(deduped).unwrap());
+    assert!(report.is_ok());
+    assert_eq!(report.shared_entries, 2);
+
+    // c.txt starting inside a.txt also runs into b.txt
+    let mut overlapping = archive;
+    corrupt_index(
+        &mut overlapping,
+        Corruption::Offset {
+            entry: 2,
+            offset: first_offset + 2,
+        },
+    );
+    let report = check_layout(&Pf8Reader::from_bytes(overlapping).unwrap());
+    assert!(!report.is_ok());
+    assert_eq!(report.shared_entries, 0);
+    assert_eq!(
+        report.overlaps,
+        vec![
+            ("a.txt".to_string(), "c.txt".to_string()),
+            ("c.txt".to_string(), "b.txt".to_string()),
+        ]
+    );
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn test_concurrent_reads_from_shared_reader() {
+    use pf8::testing::build_archive;
+
+    fn assert_sync<T: Sync>(_: &T) {}
+
+    let contents: Vec<(String, Vec<u8>)> = (0..8)
+        .map(|i| {
+            (
+                format!("data/file{i}.txt"),
+                format!("content {i}").repeat(i + 1).into_bytes(),
+            )
+        })
+        .collect();
+    let entries: Vec<(&str, &[u8])> = contents
+        .iter()
+        .map(|(name, data)| (name.as_str(), data.as_slice()))
+        .collect();
+    let reader = Pf8Reader::from_bytes(build_archive(&entries)).unwrap();
+    assert_sync(&reader);
+
+    std::thread::scope(|scope| {
+        for (name, expected) in &contents {
+            let reader = &reader;
+            scope.spawn(move || {
+                assert_eq!(&reader.read_file(name).unwrap(), expected);
+            });
+        }
+    });
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn test_relative_offset_dialect() {
+    use pf8::testing::{Corruption, build_archive, corrupt_index};
+
+    // Unencrypted entries keep their data readable after the index changes
+    let mut archive = build_archive(&[("movie/op.mp4", b"opening"), ("movie/ed.mp4", b"ending")]);
+    let reader = Pf8Reader::from_bytes(archive.clone()).unwrap();
+    let data_start = reader.entries().map(|e| e.offset()).min().unwrap();
+    let offsets: Vec<u32> = reader.entries().map(|e| e.offset()).collect();
+    for (entry, offset) in offsets.into_iter().enumerate() {
+        corrupt_index(
+            &mut archive,
+            Corruption::Offset {
+                entry,
+                offset: offset - data_start,
+            },
+        );
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let archive_path = temp_dir.path().join("relative.pfs");
+    fs::write(&archive_path, &archive).unwrap();
+
+    // Read as absolute, the first offset points into the index
+    assert!(matches!(
+        Pf8Reader::open(&archive_path),
+        Err(Error::Corrupted(_))
+    ));
+
+    let reader = Pf8Reader::open_relative_offsets(&archive_path).unwrap();
+    assert_eq!(
+        reader.get_entry("movie/op.mp4").unwrap().offset(),
+        data_start
+    );
+    assert_eq!(reader.read_file("movie/op.mp4").unwrap(), b"opening");
+    assert_eq!(reader.read_file("movie/ed.mp4").unwrap(), b"ending");
+
+    // Auto detects both dialects
+    let auto = ReaderOptions {
+        offset_mode: OffsetMode::Auto,
+        ..Default::default()
+    };
+    let reader = Pf8Reader::open_with(&archive_path, auto.clone()).unwrap();
+    assert_eq!(reader.read_file("movie/ed.mp4").unwrap(), b"ending");
+    let absolute_path = temp_dir.path().join("absolute.pfs");
+    fs::write(
+        &absolute_path,
+        build_archive(&[("movie/op.mp4", b"opening")]),
+    )
+    .unwrap();
+    let reader = Pf8Reader::open_with(&absolute_path, auto).unwrap();
+    assert_eq!(reader.read_file("movie/op.mp4").unwrap(), b"opening");
+}
+
+#[test]
+fn test_extract_all_counting() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+    let output_dir = temp_dir.path().join("output");
+
+    fs::create_dir_all(input_dir.join("sub")).unwrap();
+    fs::write(input_dir.join("a.txt"), vec![b'a'; 1000]).unwrap();
+    fs::write(input_dir.join("sub").join("b.bin"), vec![b'b'; 2345]).unwrap();
+    fs::write(input_dir.join("empty.txt"), b"").unwrap();
+    create_from_dir(&input_dir, &archive_path).unwrap();
+
+    let mut archive = Pf8Archive::open(&archive_path).unwrap();
+    let stats = archive.extract_all_counting(&output_dir).unwrap();
+    assert_eq!(stats.files, 3);
+    assert_eq!(stats.bytes, 3345);
+    assert_eq!(stats.bytes, archive.total_size());
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn test_sidecars_hidden_from_listings() {
+    use pf8::testing::build_archive;
+
+    let mut reader = Pf8Reader::from_bytes(build_archive(&[
+        (".pfsmeta", b"meta"),
+        ("a.txt", b"a"),
+        ("empty\\.keep", b""),
+    ]))
+    .unwrap();
+
+    let listed: Vec<_> = reader.entries().map(|e| e.pf8_path().to_string()).collect();
+    assert_eq!(listed, ["a.txt"]);
+    assert_eq!(reader.entries_by_offset().len(), 1);
+
+    // Hidden sidecars can still be read explicitly
+    assert!(reader.get_entry(".pfsmeta").is_some());
+    assert_eq!(reader.read_file(".pfsmeta").unwrap(), b"meta");
+
+    // Counts and sizes agree with the listing
+    assert_eq!(reader.len(), 1);
+    assert_eq!(reader.total_size(), 1);
+    assert_eq!(reader.count_by_extension().len(), 1);
+    assert_eq!(reader.size_by_extension().get(""), None);
+
+    reader.show_sidecars(true);
+    let listed: Vec<_> = reader.entries().map(|e| e.pf8_path().to_string()).collect();
+    assert_eq!(listed, [".pfsmeta", "a.txt", "empty\\.keep"]);
+    assert_eq!(reader.len(), 3);
+    assert_eq!(reader.total_size(), 5);
+    assert_eq!(reader.count_by_extension().get(""), Some(&2));
+    assert_eq!(reader.size_by_extension().get(""), Some(&4));
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn test_scan_dir_leaves_out_sidecars() {
+    use pf8::testing::build_archive;
+
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("game.pfs"),
+        build_archive(&[(".pfsmeta", b"meta"), ("a.mp4", b"movie")]),
+    )
+    .unwrap();
+
+    let infos: Vec<_> = pf8::scan_dir(temp_dir.path())
+        .collect::<Result<_>>()
+        .unwrap();
+    assert_eq!(infos.len(), 1);
+    assert_eq!(infos[0].entry_count, 1);
+    assert_eq!(infos[0].total_size, 5);
+    assert_eq!(infos[0].encrypted_count, 0);
+}
+
+#[test]
+fn test_write_to_file_hashed() {
+    use sha1::{Digest, Sha1};
+
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("a.txt"), b"hash me").unwrap();
+    fs::write(input_dir.join("b.mp4"), vec![0x5A; 4096]).unwrap();
+
+    let mut builder = Pf8Builder::new();
+    builder.add_dir(&input_dir).unwrap();
+    let (result, hash) = builder.write_to_file_hashed(&archive_path).unwrap();
+
+    let written = fs::read(&archive_path).unwrap();
+    let expected: [u8; 20] = Sha1::digest(&written).into();
+    assert_eq!(hash, expected);
+    assert_eq!(result.files, 2);
+    assert_eq!(result.bytes, 4103);
+    assert_eq!(result.archive_size, written.len() as u64);
+
+    // Atomic writes replace the file instead of rewriting it in place, so
+    // a hard link to the old archive keeps its contents
+    let alias_path = temp_dir.path().join("alias.pfs");
+    fs::write(&archive_path, b"old archive").unwrap();
+    fs::hard_link(&archive_path, &alias_path).unwrap();
+    builder.atomic(true).sync(true);
+    let (_, atomic_hash) = builder.write_to_file_hashed(&archive_path).unwrap();
+    assert_eq!(atomic_hash, hash);
+    assert_eq!(fs::read(&archive_path).unwrap(), written);
+    assert_eq!(fs::read(&alias_path).unwrap(), b"old archive");
+    assert!(!temp_dir.path().join("test.pfs.tmp").exists());
+}
+
+#[test]
+#[cfg(feature = "json")]
+fn test_write_options_serde_round_trip() {
+    let options = WriteOptions {
+        format: ArchiveFormat::Pf6,
+        entry_order: EntryOrder::Insertion,
+        sort_order: SortOrder::SizeDescending,
+        dedup: true,
+        buffer_size: Some(64 * 1024),
+        ..Default::default()
+    };
+    let json = serde_json::to_string(&options).unwrap();
+    let parsed: WriteOptions = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed, options);
+
+    // Fields left out take their defaults
+    let parsed: WriteOptions = serde_json::from_str(r#"{"format": "PF6"}"#).unwrap();
+    assert_eq!(parsed.format, ArchiveFormat::Pf6);
+    assert_eq!(
+        parsed.unencrypted_patterns,
+        WriteOptions::default().unencrypted_patterns
+    );
+}
+
+#[test]
+fn test_one_off_functions_report_written_counts() {
+    // Grows a source file once it has been packed
+    struct GrowSource {
+        source: std::path::PathBuf,
+        finished: usize,
+    }
+
+    impl ArchiveHandler for GrowSource {
+        fn on_entry_finished(&mut self, name: &str) -> ControlAction {
+            self.finished += 1;
+            if name == "a.txt" {
+                fs::write(&self.source, b"grown after packing").unwrap();
+            }
+            ControlAction::Continue
+        }
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+    let output_dir = temp_dir.path().join("output");
+
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("a.txt"), b"first").unwrap();
+    fs::write(input_dir.join("b.mp4"), b"second").unwrap();
+
+    let mut handler = GrowSource {
+        source: input_dir.join("a.txt"),
+        finished: 0,
+    };
+    let result =
+        pf8::archive::create_from_dir_with_progress(&input_dir, &archive_path, &mut handler)
+            .unwrap();
+    assert_eq!((result.files, result.bytes), (2, 11));
+    assert_eq!(handler.finished, 2);
+
+    // Events still reach the caller's handler
+    let mut handler = GrowSource {
+        source: temp_dir.path().join("unused.txt"),
+        finished: 0,
+    };
+    let result =
+        pf8::archive::extract_with_progress(&archive_path, &output_dir, &mut handler).unwrap();
+    assert_eq!((result.files, result.bytes), (2, 11));
+    assert_eq!(handler.finished, 2);
+}
+
+#[test]
+fn test_extract_all_with_shared_buffer() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut scratch = Vec::new();
+
+    for name in ["first", "second"] {
+        let input_dir = temp_dir.path().join(name);
+        let archive_path = temp_dir.path().join(format!("{name}.pfs"));
+        let output_dir = temp_dir.path().join(format!("{name}_out"));
+
+        fs::create_dir_all(&input_dir).unwrap();
+        fs::write(input_dir.join(format!("{name}.txt")), name.repeat(100)).unwrap();
+        create_from_dir(&input_dir, &archive_path).unwrap();
+
+        let mut reader = Pf8Reader::open(&archive_path).unwrap();
+        reader
+            .extract_all_with_buffer(&output_dir, &mut scratch)
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(output_dir.join(format!("{name}.txt"))).unwrap(),
+            name.repeat(100)
+        );
+    }
+
+    // The buffer was grown once and reused
+    assert_eq!(scratch.len(), 4 * 1024 * 1024);
+}
+
+#[test]
+fn test_entry_count_from_header() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+
+    fs::create_dir_all(input_dir.join("sub")).unwrap();
+    for i in 0..5 {
+        fs::write(input_dir.join(format!("file{i}.txt")), b"data").unwrap();
+    }
+    fs::write(input_dir.join("sub").join("nested.txt"), b"nested").unwrap();
+    create_from_dir(&input_dir, &archive_path).unwrap();
+
+    let reader = Pf8Reader::open(&archive_path).unwrap();
+    assert_eq!(entry_count(&archive_path).unwrap() as usize, reader.len());
+    assert_eq!(entry_count(&archive_path).unwrap(), 6);
+
+    let not_archive = temp_dir.path().join("readme.txt");
+    fs::write(&not_archive, b"hello world").unwrap();
+    assert!(matches!(
+        entry_count(&not_archive),
+        Err(Error::InvalidFormat(_))
+    ));
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn test_path_normalization() {
+    use pf8::testing::build_archive;
+
+    let temp_dir = TempDir::new().unwrap();
+    let archive_path = temp_dir.path().join("mixed.pfs");
+    fs::write(&archive_path, build_archive(&[("a/b.txt", b"mixed")])).unwrap();
+
+    // Lenient (default) treats `/` as a separator
+    let lenient_dir = temp_dir.path().join("lenient");
+    let mut reader = Pf8Reader::open(&archive_path).unwrap();
+    reader.extract_all(&lenient_dir).unwrap();
+    assert_eq!(
+        fs::read(lenient_dir.join("a").join("b.txt")).unwrap(),
+        b"mixed"
+    );
+
+    // Strict only splits on `\`, producing a single file
+    let strict_dir = temp_dir.path().join("strict");
+    let options = ReaderOptions {
+        path_normalization: PathNormalization::Strict,
+        ..Default::default()
+    };
+    let mut reader = Pf8Reader::open_with(&archive_path, options).unwrap();
+    reader.extract_all(&strict_dir).unwrap();
+    assert_eq!(fs::read(strict_dir.join("a_b.txt")).unwrap(), b"mixed");
+    assert!(!strict_dir.join("a").exists());
+}
+
+#[test]
+fn test_aggregating_handler_across_archives() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut archive_paths = Vec::new();
+
+    for (name, files) in [("first", 2), ("second", 3)] {
+        let input_dir = temp_dir.path().join(name);
+        fs::create_dir_all(&input_dir).unwrap();
+        for i in 0..files {
+            fs::write(input_dir.join(format!("{i}.txt")), vec![b'x'; 100]).unwrap();
+        }
+        let archive_path = temp_dir.path().join(format!("{name}.pfs"));
+        create_from_dir(&input_dir, &archive_path).unwrap();
+        archive_paths.push(archive_path);
+    }
+
+    let mut handler = AggregatingHandler::default();
+    for archive_path in &archive_paths {
+        let output_dir = temp_dir.path().join("output");
+        let mut archive = Pf8Archive::open(archive_path).unwrap();
+        archive
+            .extract_all_with_progress(&output_dir, &mut handler)
+            .unwrap();
+    }
+
+    let stats = handler.stats();
+    assert_eq!(handler.archives(), 2);
+    assert_eq!(stats.files, 2 + 3);
+    assert_eq!(stats.bytes, 5 * 100);
+}
+
+#[test]
+fn test_open_entry_with_meta() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+
+    fs::create_dir_all(input_dir.join("image")).unwrap();
+    let content = (0..10_000u32).map(|i| i as u8).collect::<Vec<_>>();
+    fs::write(input_dir.join("image").join("bg.png"), &content).unwrap();
+    create_from_dir(&input_dir, &archive_path).unwrap();
+
+    let mut reader = Pf8Reader::open(&archive_path).unwrap();
+    let (meta, mut body) = reader.open_entry_with_meta("image/bg.png").unwrap();
+    assert_eq!(meta.content_type, "image/png");
+
+    let mut streamed = Vec::new();
+    let copied = std::io::copy(&mut body, &mut streamed).unwrap();
+    assert_eq!(meta.size, copied);
+    assert_eq!(streamed, content);
+
+    assert!(matches!(
+        reader.open_entry_with_meta("missing.png"),
+        Err(Error::FileNotFound(_))
+    ));
+}
+
+#[test]
+fn test_add_empty_dir() {
+    let temp_dir = TempDir::new().unwrap();
+    let empty_dir = temp_dir.path().join("empty");
+    fs::create_dir_all(empty_dir.join("nested")).unwrap();
+    let archive_path = temp_dir.path().join("test.pfs");
+
+    // By default the directory is remembered and named in the error
+    let mut builder = Pf8Builder::new();
+    builder.add_dir(&empty_dir).unwrap();
+    assert_eq!(
+        builder.empty_dirs().collect::<Vec<_>>(),
+        [empty_dir.as_path()]
+    );
+    let err = builder.write_to_file(&archive_path).unwrap_err();
+    assert!(err.to_string().contains("empty directories"));
+    assert!(err.to_string().contains(&empty_dir.display().to_string()));
+
+    // Opting in fails at the call that added the directory
+    let mut builder = Pf8Builder::new();
+    builder.error_on_empty_dir(true);
+    let err = builder.add_dir_as(&empty_dir, "data").err().unwrap();
+    assert!(err.to_string().contains("Directory contains no files"));
+    assert!(builder.is_empty());
+}
+
+#[test]
+fn test_empty_dir_warning_when_packing() {
+    struct WarningCollector(Vec<String>);
+
+    impl ArchiveHandler for WarningCollector {
+        fn on_warning(&mut self, message: &str) -> ControlAction {
+            self.0.push(message.to_string());
+            ControlAction::Continue
+        }
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let empty_dir = temp_dir.path().join("empty");
+    let full_dir = temp_dir.path().join("full");
+    fs::create_dir_all(&empty_dir).unwrap();
+    fs::create_dir_all(&full_dir).unwrap();
+    fs::write(full_dir.join("a.txt"), b"a").unwrap();
+
+    let mut builder = Pf8Builder::new();
+    builder
+        .add_dir(&empty_dir)
+        .unwrap()
+        .add_dir(&full_dir)
+        .unwrap();
+    let mut handler = WarningCollector(Vec::new());
+    builder
+        .write_to_file_with_progress(temp_dir.path().join("test.pfs"), &mut handler)
+        .unwrap();
+
+    assert_eq!(handler.0.len(), 1);
+    assert!(handler.0[0].contains(&empty_dir.display().to_string()));
+}
+
+#[test]
+fn test_infer_unencrypted_filter() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+
+    fs::create_dir_all(input_dir.join("movie")).unwrap();
+    fs::create_dir_all(input_dir.join("script")).unwrap();
+    fs::write(input_dir.join("movie").join("op.mp4"), "opening subtitles").unwrap();
+    fs::write(input_dir.join("movie").join("ed.mp4"), "ending subtitles").unwrap();
+    fs::write(input_dir.join("script").join("main.txt"), "hello world").unwrap();
+    fs::write(input_dir.join("script").join("sub.ast"), "astver = 2.0").unwrap();
+    fs::write(
+        input_dir.join("image.png"),
+        [0x89, b'P', b'N', b'G', 0, 1, 2],
+    )
+    .unwrap();
+    create_from_dir(&input_dir, &archive_path).unwrap();
+
+    // Packed with the default filter, which leaves mp4 unencrypted
+    let reader = Pf8Reader::open(&archive_path).unwrap();
+    assert_eq!(infer_unencrypted_filter(&reader).unwrap(), ["mp4"]);
+}
+
+#[test]
+fn test_case_fold_paths() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir_all(input_dir.join("Sub")).unwrap();
+    fs::write(input_dir.join("Data.txt"), b"upper").unwrap();
+    fs::write(input_dir.join("Sub").join("Data.txt"), b"nested").unwrap();
+
+    let mut builder = Pf8Builder::new();
+    builder
+        .add_file_as(input_dir.join("Data.txt"), "Data.txt")
+        .unwrap();
+    assert_eq!(
+        builder.planned_entries().unwrap()[0].path(),
+        Path::new("Data.txt")
+    );
+
+    builder.case_fold_paths(true);
+    let archive_path = temp_dir.path().join("test.pfs");
+    builder.write_to_file(&archive_path).unwrap();
+    let reader = Pf8Reader::open(&archive_path).unwrap();
+    let paths = reader.entries().map(|e| e.path()).collect::<Vec<_>>();
+    assert_eq!(paths, [Path::new("data.txt")]);
+
+    // Paths equal after folding are rejected
+    builder
+        .add_file_as(input_dir.join("Sub").join("Data.txt"), "DATA.TXT")
+        .unwrap();
+    let err = builder.planned_entries().unwrap_err();
+    assert!(err.to_string().contains("collide when case-folded"));
+}
+
+#[test]
+fn test_patch_replaces_entries() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+    let patched_path = temp_dir.path().join("patched.pfs");
+
+    fs::create_dir_all(input_dir.join("script")).unwrap();
+    fs::write(input_dir.join("script").join("a.txt"), b"original a").unwrap();
+    fs::write(input_dir.join("script").join("b.txt"), b"original b").unwrap();
+    fs::write(input_dir.join("script").join("c.txt"), b"untouched c").unwrap();
+    fs::write(input_dir.join("op.mp4"), b"video").unwrap();
+    create_from_dir(&input_dir, &archive_path).unwrap();
+
+    let larger = temp_dir.path().join("larger.txt");
+    let smaller = temp_dir.path().join("smaller.txt");
+    fs::write(&larger, b"a much longer replacement for a").unwrap();
+    fs::write(&smaller, b"b").unwrap();
+
+    let result = patch(
+        &archive_path,
+        &patched_path,
+        &[("script/a.txt", &larger), ("script\\b.txt", &smaller)],
+    )
+    .unwrap();
+    assert_eq!(result.files, 4);
+
+    let reader = Pf8Reader::open(&patched_path).unwrap();
+    assert_eq!(
+        reader.read_file("script/a.txt").unwrap(),
+        b"a much longer replacement for a"
+    );
+    assert_eq!(reader.read_file("script/b.txt").unwrap(), b"b");
+    assert_eq!(reader.read_file("script/c.txt").unwrap(), b"untouched c");
+    assert_eq!(reader.read_file("op.mp4").unwrap(), b"video");
+
+    // Unknown names and in-place patching are rejected
+    assert!(matches!(
+        patch(&archive_path, &patched_path, &[("missing.txt", &larger)]),
+        Err(Error::FileNotFound(_))
+    ));
+    assert!(patch(&archive_path, &archive_path, &[("script/a.txt", &larger)]).is_err());
+}
+
+#[test]
+fn test_max_file_size_skips_large_files() {
+    struct WarningCollector(Vec<String>);
+
+    impl ArchiveHandler for WarningCollector {
+        fn on_warning(&mut self, message: &str) -> ControlAction {
+            self.0.push(message.to_string());
+            ControlAction::Continue
+        }
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("lite.pfs");
+
+    fs::create_dir_all(input_dir.join("movie")).unwrap();
+    fs::write(input_dir.join("small.txt"), vec![b's'; 100]).unwrap();
+    fs::write(input_dir.join("limit.txt"), vec![b'l'; 1000]).unwrap();
+    fs::write(input_dir.join("movie").join("huge.mp4"), vec![b'h'; 1001]).unwrap();
+
+    let mut builder = Pf8Builder::new();
+    builder.add_dir(&input_dir).unwrap().max_file_size(1000);
+    assert_eq!(builder.estimated_size().unwrap().entry_count, 2);
+
+    let mut handler = WarningCollector(Vec::new());
+    builder
+        .write_to_file_with_progress(&archive_path, &mut handler)
+        .unwrap();
+    assert_eq!(handler.0.len(), 1);
+    assert!(handler.0[0].contains("huge.mp4"));
+
+    let reader = Pf8Reader::open(&archive_path).unwrap();
+    assert_eq!(reader.len(), 2);
+    assert!(reader.contains("small.txt"));
+    assert!(reader.contains("limit.txt"));
+    assert!(!reader.contains("movie/huge.mp4"));
+}
+
+#[test]
+fn test_plan_extraction_strip_components() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+    let output_dir = temp_dir.path().join("output");
+
+    fs::create_dir_all(input_dir.join("root").join("script")).unwrap();
+    fs::write(input_dir.join("root").join("a.txt"), b"a").unwrap();
+    fs::write(input_dir.join("root").join("script").join("b.txt"), b"b").unwrap();
+    fs::write(input_dir.join("top.txt"), b"top").unwrap();
+    create_from_dir(&input_dir, &archive_path).unwrap();
+
+    let reader = Pf8Reader::open(&archive_path).unwrap();
+    let options = ExtractOptions {
+        strip_components: 1,
+        ..Default::default()
+    };
+    let plan = reader.plan_extraction(&output_dir, &options).unwrap();
+    let planned = plan
+        .iter()
+        .map(|(entry, path)| (entry.path().to_path_buf(), path.clone()))
+        .collect::<Vec<_>>();
+
+    // `top.txt` has no component left and is omitted
+    assert_eq!(
+        planned,
+        [
+            (PathBuf::from("root/a.txt"), output_dir.join("a.txt")),
+            (
+                PathBuf::from("root/script/b.txt"),
+                output_dir.join("script").join("b.txt")
+            ),
+        ]
+    );
+    assert!(!output_dir.exists());
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn test_open_as_ignores_damaged_magic() {
+    use pf8::testing::{Corruption, build_archive, corrupt_index};
+
+    let temp_dir = TempDir::new().unwrap();
+    let archive_path = temp_dir.path().join("headerless.pfs");
+
+    let mut archive = build_archive(&[("script/main.txt", b"hello"), ("movie/op.mp4", b"video")]);
+    let index_size = u32::from_le_bytes(archive[3..7].try_into().unwrap());
+    archive[..3].fill(0);
+    fs::write(&archive_path, &archive).unwrap();
+
+    assert!(Pf8Reader::open(&archive_path).is_err());
+    let reader = Pf8Reader::open_as(&archive_path, ArchiveFormat::Pf8, None).unwrap();
+    assert_eq!(reader.format(), ArchiveFormat::Pf8);
+    assert_eq!(reader.read_file("script/main.txt").unwrap(), b"hello");
+    assert_eq!(reader.read_file("movie/op.mp4").unwrap(), b"video");
+
+    // A damaged index_size field can be replaced by a hint
+    corrupt_index(&mut archive, Corruption::IndexSize(u32::MAX));
+    fs::write(&archive_path, &archive).unwrap();
+    assert!(Pf8Reader::open_as(&archive_path, ArchiveFormat::Pf8, None).is_err());
+    let reader = Pf8Reader::open_as(&archive_path, ArchiveFormat::Pf8, Some(index_size)).unwrap();
+    assert_eq!(reader.read_file("script/main.txt").unwrap(), b"hello");
+}
+
+#[test]
+fn test_pack_announces_totals_of_all_inputs() {
+    struct TotalsRecorder {
+        first_writing: Option<ProgressInfo>,
+        entries_before_totals: usize,
+    }
+
+    impl ArchiveHandler for TotalsRecorder {
+        fn on_progress(&mut self, info: &ProgressInfo) -> ControlAction {
+            if info.phase == OperationPhase::Writing && self.first_writing.is_none() {
+                self.first_writing = Some(info.clone());
+            }
+            ControlAction::Continue
+        }
+
+        fn on_entry_started(&mut self, _name: &str) -> ControlAction {
+            if self.first_writing.is_none() {
+                self.entries_before_totals += 1;
+            }
+            ControlAction::Continue
+        }
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let first_dir = temp_dir.path().join("first");
+    let second_dir = temp_dir.path().join("second");
+    fs::create_dir_all(&first_dir).unwrap();
+    fs::create_dir_all(&second_dir).unwrap();
+    fs::write(first_dir.join("a.txt"), vec![b'a'; 10]).unwrap();
+    fs::write(first_dir.join("b.txt"), vec![b'b'; 20]).unwrap();
+    fs::write(second_dir.join("c.txt"), vec![b'c'; 30]).unwrap();
+
+    let mut builder = Pf8Builder::new();
+    builder
+        .add_dir_as(&first_dir, "first")
+        .unwrap()
+        .add_dir_as(&second_dir, "second")
+        .unwrap();
+
+    let mut handler = TotalsRecorder {
+        first_writing: None,
+        entries_before_totals: 0,
+    };
+    builder
+        .write_to_file_with_progress(temp_dir.path().join("test.pfs"), &mut handler)
+        .unwrap();
+
+    let totals = handler.first_writing.unwrap();
+    assert_eq!(handler.entries_before_totals, 0);
+    assert_eq!(totals.processed_files, 0);
+    assert_eq!(totals.total_files, Some(3));
+    assert_eq!(totals.total_bytes, Some(60));
+    assert_eq!(totals.overall_progress(), Some(0.0));
+}
+
+#[test]
+fn test_embed_tool_marker() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("a.txt"), b"a").unwrap();
+
+    let mut builder = Pf8Builder::new();
+    builder
+        .add_dir(&input_dir)
+        .unwrap()
+        .embed_tool_marker(true)
+        .reproducible();
+    assert_eq!(builder.estimated_size().unwrap().entry_count, 2);
+    builder.write_to_file_verified(&archive_path).unwrap();
+
+    let reader = Pf8Reader::open(&archive_path).unwrap();
+    // The marker is a sidecar, hidden from listings but readable by name
+    assert_eq!(reader.entries().count(), 1);
+    let marker = String::from_utf8(reader.read_file(TOOL_MARKER_NAME).unwrap()).unwrap();
+    assert!(marker.contains("tool=pf8\n"));
+    assert!(marker.contains(&format!("version={}\n", env!("CARGO_PKG_VERSION"))));
+    assert!(marker.contains("created=0\n"));
+    assert_eq!(
+        fs::metadata(&archive_path).unwrap().len(),
+        builder.estimated_size().unwrap().total_size
+    );
+
+    // Off by default
+    let mut builder = Pf8Builder::new();
+    builder.add_dir(&input_dir).unwrap();
+    builder.write_to_file(&archive_path).unwrap();
+    assert!(
+        !Pf8Reader::open(&archive_path)
+            .unwrap()
+            .contains(TOOL_MARKER_NAME)
+    );
+}
+
+#[test]
+fn test_extract_iter() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+    let output_dir = temp_dir.path().join("output");
+
+    fs::create_dir_all(input_dir.join("sub")).unwrap();
+    fs::write(input_dir.join("a.txt"), b"aaa").unwrap();
+    fs::write(input_dir.join("sub").join("b.txt"), b"bb").unwrap();
+    fs::write(input_dir.join("sub").join("c.mp4"), b"c").unwrap();
+    create_from_dir(&input_dir, &archive_path).unwrap();
+
+    let mut reader = Pf8Reader::open(&archive_path).unwrap();
+    let expected = reader
+        .entries()
+        .map(|e| e.path().to_path_buf())
+        .collect::<Vec<_>>();
+
+    let mut iter = reader.extract_iter(&output_dir);
+    assert_eq!(iter.size_hint(), (3, Some(3)));
+
+    // Only the first entry is written after one step
+    let first = iter.next().unwrap().unwrap();
+    assert_eq!(first.path, output_dir.join("a.txt"));
+    assert_eq!(first.bytes, 3);
+    assert!(!output_dir.join("sub").exists());
+
+    let mut extracted = vec![first];
+    extracted.extend(iter.map(Result::unwrap));
+    let paths = extracted
+        .iter()
+        .map(|file| file.entry.path().to_path_buf())
+        .collect::<Vec<_>>();
+    assert_eq!(paths, expected);
+    for file in &extracted {
+        assert_eq!(fs::read(&file.path).unwrap().len() as u64, file.bytes);
+    }
+    assert_eq!(
+        fs::read(output_dir.join("sub").join("b.txt")).unwrap(),
+        b"bb"
+    );
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn test_oversized_name_length_reports_offset() {
+    use pf8::testing::{Corruption, build_archive, corrupt_index};
+
+    let mut archive = build_archive(&[("a.txt", b"a"), ("b.txt", b"b")]);
+    // Second entry starts after the first: length, 5-byte name, 12 bytes
+    let second_entry = 11 + 4 + 5 + 12;
+    corrupt_index(
+        &mut archive,
+        Corruption::NameLength {
+            entry: 1,
+            length: 0x7fff_ffff,
+        },
+    );
+
+    match Pf8Reader::from_bytes(archive) {
+        Err(Error::Corrupted(message)) => assert_eq!(
+            message,
+            format!(
+                "Entry name at offset {second_entry} claims length 2147483647 exceeding index bounds"
+            )
+        ),
+        Err(other) => panic!("expected Corrupted, got {other:?}"),
+        Ok(_) => panic!("expected Corrupted, got a reader"),
+    }
+}
+
+#[test]
+#[cfg(feature = "encoding")]
+fn test_auto_name_encoding() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_file = temp_dir.path().join("input.txt");
+    let sjis_path = temp_dir.path().join("sjis.pfs");
+    let utf8_path = temp_dir.path().join("utf8.pfs");
+    fs::write(&input_file, b"scenario").unwrap();
+
+    let mut builder = Pf8Builder::new();
+    builder.add_file_as(&input_file, "開始.txt").unwrap();
+    builder.write_to_file(&utf8_path).unwrap();
+    builder.name_encoding(NameEncoding::ShiftJis);
+    builder.write_to_file(&sjis_path).unwrap();
+
+    // Pf8Archive::open falls back to Shift-JIS for non-UTF-8 names
+    for path in [&sjis_path, &utf8_path] {
+        let archive = Pf8Archive::open(path).unwrap();
+        assert_eq!(archive.entries().next().unwrap().pf8_path(), "開始.txt");
+        assert_eq!(archive.read_file("開始.txt").unwrap(), b"scenario");
+    }
+
+    // Bytes valid in neither encoding are still rejected
+    let options = ReaderOptions {
+        name_encoding: NameEncoding::Auto,
+        ..Default::default()
+    };
+    let mut archive = fs::read(&utf8_path).unwrap();
+    let name_start = 11 + 4;
+    archive[name_start] = 0xff;
+    let broken_path = temp_dir.path().join("broken.pfs");
+    fs::write(&broken_path, archive).unwrap();
+    assert!(Pf8Reader::open_with(&broken_path, options).is_err());
+}
+
+#[test]
+fn test_sort_order_by_size() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("a.txt"), vec![b'a'; 300]).unwrap();
+    fs::write(input_dir.join("b.txt"), vec![b'b'; 100]).unwrap();
+    fs::write(input_dir.join("c.txt"), vec![b'c'; 200]).unwrap();
+
+    let data_order = |order: SortOrder| {
+        let archive_path = temp_dir.path().join("test.pfs");
+        let mut builder = Pf8Builder::new();
+        builder.add_dir(&input_dir).unwrap().sort_order(order);
+        builder.write_to_file_verified(&archive_path).unwrap();
+
+        let reader = Pf8Reader::open(&archive_path).unwrap();
+        // Index order is unaffected
+        let index = reader.entries().map(|e| e.pf8_path()).collect::<Vec<_>>();
+        assert_eq!(index, ["a.txt", "b.txt", "c.txt"]);
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            let data = reader.read_file(name).unwrap();
+            assert!(data.iter().all(|&b| b == name.as_bytes()[0]));
+        }
+        (
+            reader
+                .entries_by_offset()
+                .iter()
+                .map(|e| e.pf8_path().to_string())
+                .collect::<Vec<_>>(),
+            reader.encryption_key().unwrap().to_vec(),
+        )
+    };
+
+    let (lexicographic, lexicographic_key) = data_order(SortOrder::Lexicographic);
+    assert_eq!(lexicographic, ["a.txt", "b.txt", "c.txt"]);
+    let (ascending, ascending_key) = data_order(SortOrder::SizeAscending);
+    assert_eq!(ascending, ["b.txt", "c.txt", "a.txt"]);
+    let (descending, _) = data_order(SortOrder::SizeDescending);
+    assert_eq!(descending, ["a.txt", "c.txt", "b.txt"]);
+
+    // Offsets are part of the index, so the key changes
+    assert_ne!(lexicographic_key, ascending_key);
+}
+
+#[test]
+fn test_write_header_with_offsets_rejects_gaps() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut writer = Pf8Writer::create(temp_dir.path().join("test.pfs")).unwrap();
+
+    // Data starts at offset 85 for these two names; b.txt should follow at 89
+    let entries = [Pf8Entry::new("a.txt", 85, 4), Pf8Entry::new("b.txt", 95, 4)];
+    let err = writer
+        .write_header_with_offsets(&entries.iter().collect::<Vec<_>>())
+        .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("b.txt at offset 95 leaves a gap or overlap")
+    );
+
+    // Data may follow a different order than the index
+    let entries = [Pf8Entry::new("a.txt", 89, 4), Pf8Entry::new("b.txt", 85, 4)];
+    writer
+        .write_header_with_offsets(&entries.iter().collect::<Vec<_>>())
+        .unwrap();
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn test_verify_extraction() {
+    use pf8::testing::build_archive;
+
+    let temp_dir = TempDir::new().unwrap();
+    let archive = build_archive(&[
+        ("script\\main.txt", b"hello world"),
+        ("image.png", b"\x89PNG data"),
+    ]);
+    let mut reader = Pf8Reader::from_bytes(archive).unwrap();
+    let output_dir = temp_dir.path().join("out");
+    reader.extract_all(&output_dir).unwrap();
+
+    let report = verify_extraction(&mut reader, &output_dir, true).unwrap();
+    assert!(report.is_ok(), "{report:?}");
+    assert_eq!(report.checked, 2);
+
+    // Same size, different content is only caught when comparing content
+    fs::write(output_dir.join("image.png"), b"\x89PNG DATA").unwrap();
+    assert!(
+        verify_extraction(&mut reader, &output_dir, false)
+            .unwrap()
+            .is_ok()
+    );
+    let report = verify_extraction(&mut reader, &output_dir, true).unwrap();
+    assert_eq!(report.content_mismatches, ["image.png"]);
+
+    let main_txt = output_dir.join("script").join("main.txt");
+    fs::write(&main_txt, b"hello").unwrap();
+    let report = verify_extraction(&mut reader, &output_dir, true).unwrap();
+    assert_eq!(
+        report.size_mismatches,
+        [Path::new("script").join("main.txt").display().to_string()]
+    );
+
+    fs::remove_file(&main_txt).unwrap();
+    let report = verify_extraction(&mut reader, &output_dir, true).unwrap();
+    assert_eq!(report.missing.len(), 1);
+    assert!(!report.is_ok());
+}
+
+#[test]
+fn test_builder_unencrypted_patterns() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("notes.txt"), b"plain text").unwrap();
+    fs::write(input_dir.join("readme.md"), b"readme").unwrap();
+    fs::write(input_dir.join("movie.mp4"), b"not a default anymore").unwrap();
+    fs::write(input_dir.join("image.png"), b"\x89PNG").unwrap();
+
+    let archive_path = temp_dir.path().join("test.pfs");
+    let mut builder = Pf8Builder::new();
+    builder
+        .unencrypted_extensions(&["txt"])
+        .unencrypted_patterns(&["readme"]);
+    builder.add_dir(&input_dir).unwrap();
+    builder.write_to_file_verified(&archive_path).unwrap();
+
+    let options = ReaderOptions {
+        unencrypted_patterns: Some(builder.unencrypted_filter()),
+        ..Default::default()
+    };
+    let mut reader = Pf8Reader::open_with(&archive_path, options).unwrap();
+    let encrypted = |reader: &Pf8Reader, name: &str| reader.get_entry(name).unwrap().is_encrypted();
+    assert!(!encrypted(&reader, "notes.txt"));
+    assert!(!encrypted(&reader, "readme.md"));
+    assert!(encrypted(&reader, "movie.mp4"));
+    assert!(encrypted(&reader, "image.png"));
+
+    let output_dir = temp_dir.path().join("out");
+    reader.extract_all(&output_dir).unwrap();
+    assert_eq!(
+        fs::read(output_dir.join("notes.txt")).unwrap(),
+        b"plain text"
+    );
+    assert_eq!(
+        fs::read(output_dir.join("movie.mp4")).unwrap(),
+        b"not a default anymore"
+    );
+}
+
+#[test]
+#[cfg(feature = "mmap")]
+fn test_extract_file_mmap_matches_streaming() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir_all(&input_dir).unwrap();
+    // Larger than the 4 MiB streaming buffer
+    let video: Vec<u8> = (0..9 * 1024 * 1024 + 7).map(|i| (i % 251) as u8).collect();
+    fs::write(input_dir.join("movie.dat"), &video).unwrap();
+    fs::write(input_dir.join("empty.txt"), b"").unwrap();
+
+    let archive_path = temp_dir.path().join("test.pfs");
+    create_from_dir(&input_dir, &archive_path).unwrap();
+    let mut reader = Pf8Reader::open(&archive_path).unwrap();
+    assert!(reader.get_entry("movie.dat").unwrap().is_encrypted());
+
+    let streamed_dir = temp_dir.path().join("streamed");
+    reader.extract_all(&streamed_dir).unwrap();
+
+    let mapped = temp_dir.path().join("mapped").join("movie.dat");
+    reader.extract_file_mmap("movie.dat", &mapped).unwrap();
+    let mapped_data = fs::read(&mapped).unwrap();
+    assert_eq!(
+        mapped_data,
+        fs::read(streamed_dir.join("movie.dat")).unwrap()
+    );
+    assert_eq!(mapped_data, video);
+
+    let mapped_empty = temp_dir.path().join("mapped").join("empty.txt");
+    reader
+        .extract_file_mmap("empty.txt", &mapped_empty)
+        .unwrap();
+    assert_eq!(fs::metadata(&mapped_empty).unwrap().len(), 0);
+
+    assert!(matches!(
+        reader.extract_file_mmap("missing.dat", &mapped),
+        Err(Error::FileNotFound(_))
+    ));
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn test_open_with_combined_options() {
+    use pf8::testing::build_archive;
+
+    let temp_dir = TempDir::new().unwrap();
+    let archive_path = temp_dir.path().join("test.pfs");
+    let large: Vec<u8> = (0..10_000).map(|i| (i % 253) as u8).collect();
+    fs::write(
+        &archive_path,
+        build_archive(&[("Script\\Main.txt", &large), ("Image.png", b"png")]),
+    )
+    .unwrap();
+
+    // Case-insensitive lookups and a buffer much smaller than the entry
+    let options = ReaderOptions {
+        case_sensitivity: CaseSensitivity::Insensitive,
+        buffer_size: 4096,
+        ..Default::default()
+    };
+    let mut reader = Pf8Reader::open_with(&archive_path, options).unwrap();
+    assert!(reader.contains("script/main.txt"));
+    assert!(reader.contains("IMAGE.PNG"));
+
+    let output_dir = temp_dir.path().join("out");
+    reader.extract_all(&output_dir).unwrap();
+    assert_eq!(
+        fs::read(output_dir.join("Script").join("Main.txt")).unwrap(),
+        large
+    );
+
+    // The defaults are case-sensitive
+    let reader = Pf8Reader::open(&archive_path).unwrap();
+    assert!(!reader.contains("script/main.txt"));
+
+    for buffer_size in [0, 1, 4095] {
+        let options = ReaderOptions {
+            buffer_size,
+            ..Default::default()
+        };
+        assert!(Pf8Reader::open_with(&archive_path, options).is_err());
+    }
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn test_open_with_strict_rejects_duplicates() {
+    use pf8::testing::build_archive;
+
+    let temp_dir = TempDir::new().unwrap();
+    let archive_path = temp_dir.path().join("test.pfs");
+    fs::write(
+        &archive_path,
+        build_archive(&[("a.txt", b"first"), ("A.TXT", b"second")]),
+    )
+    .unwrap();
+
+    let strict = |case_sensitivity| ReaderOptions {
+        strictness: Strictness::Strict,
+        case_sensitivity,
+        ..Default::default()
+    };
+    assert!(Pf8Reader::open_with(&archive_path, strict(CaseSensitivity::Sensitive)).is_ok());
+    assert!(matches!(
+        Pf8Reader::open_with(&archive_path, strict(CaseSensitivity::Insensitive)),
+        Err(Error::DuplicateEntry(name)) if name == "A.TXT"
+    ));
+
+    // Lenient mode lets the later entry shadow the earlier one
+    let options = ReaderOptions {
+        case_sensitivity: CaseSensitivity::Insensitive,
+        ..Default::default()
+    };
+    let reader = Pf8Reader::open_with(&archive_path, options).unwrap();
+    assert_eq!(reader.read_file("a.txt").unwrap(), b"second");
+    assert_eq!(reader.len(), 2);
+}
+
+#[test]
+fn test_open_with_patterns() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("notes.txt"), b"plain text").unwrap();
+
+    let archive_path = temp_dir.path().join("test.pfs");
+    let mut builder = Pf8Builder::new();
+    builder
+        .unencrypted_extensions(&["txt"])
+        .unencrypted_patterns(&[]);
+    builder.add_dir(&input_dir).unwrap();
+    builder.write_to_file(&archive_path).unwrap();
+
+    let reader = Pf8Reader::open_with_patterns(&archive_path, &[".txt"]).unwrap();
+    assert!(!reader.get_entry("notes.txt").unwrap().is_encrypted());
+    assert_eq!(reader.read_file("notes.txt").unwrap(), b"plain text");
+}
+
+#[test]
+fn test_extract_all_parallel_matches_sequential() {
+    #[derive(Default)]
+    struct Counter {
+        started: usize,
+        finished: usize,
+        last_bytes: u64,
+    }
+
+    impl ArchiveHandler for Counter {
+        fn on_entry_started(&mut self, _name: &str) -> ControlAction {
+            self.started += 1;
+            ControlAction::Continue
+        }
+
+        fn on_progress(&mut self, info: &ProgressInfo) -> ControlAction {
+            assert!(info.processed_bytes >= self.last_bytes);
+            self.last_bytes = info.processed_bytes;
+            ControlAction::Continue
+        }
+
+        fn on_entry_finished(&mut self, _name: &str) -> ControlAction {
+            self.finished += 1;
+            ControlAction::Continue
+        }
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir_all(input_dir.join("sub")).unwrap();
+    for i in 0..20 {
+        let data: Vec<u8> = (0..i * 997).map(|b| (b % 241) as u8).collect();
+        fs::write(input_dir.join("sub").join(format!("file{i}.bin")), data).unwrap();
+    }
+    let large: Vec<u8> = (0..5 * 1024 * 1024).map(|b| (b % 239) as u8).collect();
+    fs::write(input_dir.join("movie.mp4"), &large).unwrap();
+
+    let archive_path = temp_dir.path().join("test.pfs");
+    create_from_dir(&input_dir, &archive_path).unwrap();
+
+    let options = ReaderOptions {
+        buffer_size: 64 * 1024,
+        ..Default::default()
+    };
+    let reader = Pf8Reader::open_with(&archive_path, options).unwrap();
+    let parallel_dir = temp_dir.path().join("parallel");
+    let mut counter = Counter::default();
+    reader
+        .extract_all_parallel(&parallel_dir, 4, &mut counter)
+        .unwrap();
+    assert_eq!(counter.started, 21);
+    assert_eq!(counter.finished, 21);
+    assert_eq!(counter.last_bytes, reader.total_size());
+
+    // In-memory archives are extracted through the shared buffer
+    let memory_reader = Pf8Reader::from_bytes(fs::read(&archive_path).unwrap()).unwrap();
+    let memory_dir = temp_dir.path().join("memory");
+    memory_reader
+        .extract_all_parallel(&memory_dir, 0, &mut pf8::callbacks::NoOpHandler)
+        .unwrap();
+
+    for i in 0..20 {
+        let name = Path::new("sub").join(format!("file{i}.bin"));
+        let expected = fs::read(input_dir.join(&name)).unwrap();
+        assert_eq!(fs::read(parallel_dir.join(&name)).unwrap(), expected);
+        assert_eq!(fs::read(memory_dir.join(&name)).unwrap(), expected);
+    }
+    assert_eq!(fs::read(parallel_dir.join("movie.mp4")).unwrap(), large);
+}
+
+#[test]
+fn test_extract_all_parallel_abort() {
+    struct AbortAfter(usize);
+
+    impl ArchiveHandler for AbortAfter {
+        fn on_entry_finished(&mut self, _name: &str) -> ControlAction {
+            self.0 = self.0.saturating_sub(1);
+            if self.0 == 0 {
+                ControlAction::Abort
+            } else {
+                ControlAction::Continue
+            }
+        }
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir_all(&input_dir).unwrap();
+    for i in 0..50 {
+        fs::write(input_dir.join(format!("file{i}.txt")), b"data").unwrap();
+    }
+    let archive_path = temp_dir.path().join("test.pfs");
+    create_from_dir(&input_dir, &archive_path).unwrap();
+
+    let reader = Pf8Reader::open(&archive_path).unwrap();
+    let output_dir = temp_dir.path().join("out");
+    let result = reader.extract_all_parallel(&output_dir, 3, &mut AbortAfter(5));
+    assert!(matches!(result, Err(Error::Cancelled)));
+    assert!(fs::read_dir(&output_dir).unwrap().count() < 50);
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn test_extract_all_parallel_duplicate_names() {
+    use pf8::testing::build_archive;
+
+    #[derive(Default)]
+    struct Finished(Vec<String>);
+
+    impl ArchiveHandler for Finished {
+        fn on_entry_finished(&mut self, name: &str) -> ControlAction {
+            self.0.push(name.to_string());
+            ControlAction::Continue
+        }
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let large_first = vec![b'1'; 256 * 1024];
+    let reader = Pf8Reader::from_bytes(build_archive(&[
+        ("dup.txt", &large_first),
+        ("other.txt", b"other"),
+        ("dup.txt", b"last"),
+    ]))
+    .unwrap();
+
+    for threads in [1, 4] {
+        let output_dir = temp_dir.path().join(format!("output{threads}"));
+        let mut handler = Finished::default();
+        reader
+            .extract_all_parallel(&output_dir, threads, &mut handler)
+            .unwrap();
+
+        // The last entry wins, as in a sequential extraction
+        assert_eq!(fs::read(output_dir.join("dup.txt")).unwrap(), b"last");
+        assert_eq!(fs::read(output_dir.join("other.txt")).unwrap(), b"other");
+        handler.0.sort();
+        assert_eq!(handler.0, ["dup.txt", "other.txt"]);
+    }
+}
+
+#[test]
+fn test_write_with_options() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir_all(input_dir.join("Script")).unwrap();
+    fs::write(input_dir.join("Script").join("Main.txt"), b"plain script").unwrap();
+    fs::write(input_dir.join("image.png"), b"\x89PNG image data").unwrap();
+    fs::write(input_dir.join("huge.bin"), vec![0u8; 4096]).unwrap();
+
+    let mut builder = Pf8Builder::new();
+    builder.add_dir(&input_dir).unwrap();
+
+    let options = WriteOptions {
+        format: ArchiveFormat::Pf8,
+        name_encoding: NameEncoding::Utf8,
+        unencrypted_patterns: Vec::new(),
+        unencrypted_extensions: vec![".txt".to_string()],
+        entry_order: EntryOrder::ByPath,
+        sort_order: SortOrder::SizeDescending,
+        skip_unreadable: true,
+        reproducible: true,
+        embed_tool_marker: true,
+        case_fold_paths: true,
+        max_file_size: Some(1024),
+        max_archive_size: Some(1024 * 1024),
+        atomic: true,
+        sync: true,
+        dedup: true,
+        buffer_size: Some(64 * 1024),
+    };
+    let archive_path = temp_dir.path().join("test.pfs");
+    builder.write_with(options.clone(), &archive_path).unwrap();
+    assert!(!temp_dir.path().join("test.pfs.tmp").exists());
+    // The builder's own options are untouched
+    assert_eq!(builder.options(), &WriteOptions::default());
+
+    let reader_options = ReaderOptions {
+        unencrypted_patterns: Some(vec![".txt".to_string()]),
+        ..Default::default()
+    };
+    let mut reader = Pf8Reader::open_with(&archive_path, reader_options).unwrap();
+    let paths: Vec<_> = reader.entries().map(|e| e.pf8_path().to_string()).collect();
+    assert_eq!(paths, ["image.png", "script\\main.txt"]);
+    assert!(!reader.get_entry("script/main.txt").unwrap().is_encrypted());
+    assert_eq!(
+        reader.read_file("image.png").unwrap(),
+        b"\x89PNG image data"
+    );
+    reader.show_sidecars(true);
+    assert!(reader.contains(TOOL_MARKER_NAME));
+
+    // Writing the same configuration through the setters gives the same bytes
+    let mut configured = Pf8Builder::new();
+    configured.add_dir(&input_dir).unwrap();
+    configured.set_options(options);
+    let configured_path = temp_dir.path().join("configured.pfs");
+    configured.write_to_file(&configured_path).unwrap();
+    assert_eq!(
+        fs::read(&archive_path).unwrap(),
+        fs::read(&configured_path).unwrap()
+    );
+
+    let pf6 = WriteOptions {
+        format: ArchiveFormat::Pf6,
+        ..Default::default()
+    };
+    let pf6_path = temp_dir.path().join("pf6.pfs");
+    builder.write_with(pf6, &pf6_path).unwrap();
+    assert_eq!(
+        Pf8Reader::open(&pf6_path).unwrap().format(),
+        ArchiveFormat::Pf6
+    );
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn test_from_reader() {
+    use pf8::testing::build_archive;
+    use std::io::Cursor;
+
+    let archive = build_archive(&[("script\\main.txt", b"hello"), ("movie.mp4", b"frames")]);
+    let mut reader =
+        Pf8Reader::from_reader(Cursor::new(archive), ReaderOptions::default()).unwrap();
+    assert_eq!(reader.len(), 2);
+    assert_eq!(reader.read_file("script/main.txt").unwrap(), b"hello");
+    assert!(reader.as_bytes().is_none());
+    assert!(reader.entry_slice("movie.mp4").is_none());
+
+    let temp_dir = TempDir::new().unwrap();
+    let output_dir = temp_dir.path().join("out");
+    reader.extract_all(&output_dir).unwrap();
+    assert_eq!(fs::read(output_dir.join("movie.mp4")).unwrap(), b"frames");
+
+    let parallel_dir = temp_dir.path().join("parallel");
+    reader
+        .extract_all_parallel(&parallel_dir, 2, &mut pf8::callbacks::NoOpHandler)
+        .unwrap();
+    assert_eq!(
+        fs::read(parallel_dir.join("script").join("main.txt")).unwrap(),
+        b"hello"
+    );
+
+    let truncated = Cursor::new(vec![b'p', b'f', b'8']);
+    assert!(Pf8Reader::from_reader(truncated, ReaderOptions::default()).is_err());
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn test_reader_verify_integrity() {
+    use pf8::testing::{Corruption, build_archive, corrupt_index};
+
+    let files: [(&str, &[u8]); 3] = [
+        ("a.txt", b"aaaaaaaaaa"),
+        ("b.txt", b"bbbbbbbbbb"),
+        ("c.txt", b"cccccccccc"),
+    ];
+    let mut archive = build_archive(&files);
+    let reader = Pf8Reader::from_bytes(archive.clone()).unwrap();
+    let report = reader.verify().unwrap();
+    assert!(report.is_ok(), "{report:?}");
+    assert_eq!(report.declared_count, 3);
+    assert_eq!(report.entries_ok(), 3);
+    assert_eq!((report.gaps, report.gap_bytes), (0, 0));
+    assert_eq!(report.first_bad_offset(), None);
+
+    // Shrink a.txt, leaving a gap, and move c.txt into the middle of b.txt
+    let b_offset = reader.get_entry("b.txt").unwrap().offset();
+    corrupt_index(&mut archive, Corruption::Size { entry: 0, size: 5 });
+    corrupt_index(
+        &mut archive,
+        Corruption::Offset {
+            entry: 2,
+            offset: b_offset + 5,
+        },
+    );
+
+    let report = Pf8Reader::from_bytes(archive).unwrap().verify().unwrap();
+    assert!(!report.is_ok());
+    assert!(report.key_matches);
+    assert_eq!(
+        report.problems,
+        [EntryProblem {
+            name: "c.txt".to_string(),
+            offset: b_offset + 5,
+            size: 10,
+            kind: ProblemKind::Overlaps("b.txt".to_string()),
+        }]
+    );
+    assert_eq!(report.entries_ok(), 2);
+    assert_eq!(report.first_bad_offset(), Some(b_offset + 5));
+    // Five bytes after a.txt and the five bytes c.txt no longer covers
+    assert_eq!((report.gaps, report.gap_bytes), (2, 10));
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn test_verify_reports_entries_that_fail_to_open() {
+    use pf8::testing::{Corruption, build_archive, corrupt_index};
+
+    let temp_dir = TempDir::new().unwrap();
+    let archive_path = temp_dir.path().join("test.pfs");
+    let files: [(&str, &[u8]); 3] = [
+        ("a.txt", b"aaaaaaaaaa"),
+        ("b.txt", b"bbbbbbbbbb"),
+        ("c.txt", b"cccccccccc"),
+    ];
+    let original = build_archive(&files);
+    let c_offset = Pf8Reader::from_bytes(original.clone())
+        .unwrap()
+        .get_entry("c.txt")
+        .unwrap()
+        .offset();
+
+    let verify = |corruption: Corruption| {
+        let mut archive = original.clone();
+        corrupt_index(&mut archive, corruption);
+        fs::write(&archive_path, &archive).unwrap();
+        assert!(matches!(
+            Pf8Reader::open(&archive_path),
+            Err(Error::Corrupted(_))
+        ));
+        let options = ReaderOptions {
+            strictness: Strictness::Permissive,
+            ..Default::default()
+        };
+        Pf8Reader::open_with(&archive_path, options)
+            .unwrap()
+            .verify()
+            .unwrap()
+    };
+
+    let report = verify(Corruption::Size {
+        entry: 2,
+        size: 1000,
+    });
+    assert!(!report.is_ok());
+    assert_eq!(
+        report.problems,
+        [EntryProblem {
+            name: "c.txt".to_string(),
+            offset: c_offset,
+            size: 1000,
+            kind: ProblemKind::OutOfBounds,
+        }]
+    );
+    assert_eq!(report.entries_ok(), 2);
+    assert_eq!(report.first_bad_offset(), Some(c_offset));
+
+    let report = verify(Corruption::Offset {
+        entry: 0,
+        offset: 0x10,
+    });
+    assert!(!report.is_ok());
+    assert_eq!(report.problems.len(), 1);
+    assert_eq!(report.problems[0].name, "a.txt");
+    assert_eq!(report.problems[0].kind, ProblemKind::OverlapsIndex);
+    assert_eq!(report.first_bad_offset(), Some(0x10));
+
+    let report = verify(Corruption::IndexCount(4));
+    assert!(!report.is_ok());
+    assert!(report.problems.is_empty());
+    assert_eq!((report.declared_count, report.entries), (4, 3));
+}
+
+#[test]
+fn test_write_header_rejects_offsets_beyond_4gib() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut writer = Pf8Writer::create(temp_dir.path().join("test.pfs")).unwrap();
+
+    let entries = [
+        Pf8Entry::new("a.bin", 0, u32::MAX - 100),
+        Pf8Entry::new("b.bin", 0, 200),
+    ];
+    let err = writer
+        .write_header(&entries.iter().collect::<Vec<_>>())
+        .unwrap_err();
+    assert!(matches!(err, Error::InvalidFormat(_)));
+    assert!(err.to_string().contains("b.bin would end at offset"));
+
+    let entry = Pf8Entry::new("a.bin", u32::MAX, u32::MAX);
+    assert_eq!(entry.offset_u64() + entry.size_u64(), 2 * u32::MAX as u64);
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn test_empty_key_is_an_error() {
+    use pf8::testing::build_archive;
+
+    let archive = build_archive(&[("script.txt", b"hello")]);
+    let reader = Pf8Reader::from_bytes(archive.clone()).unwrap();
+    let entry = reader.get_entry("script.txt").unwrap();
+    assert!(entry.is_encrypted());
+
+    // A degenerate key must not reach the XOR loops
+    let err = entry.read(&archive, Some(&[])).unwrap_err();
+    assert!(matches!(err, Error::Crypto(ref message) if message == "empty key"));
+    let mut buffer = vec![0u8; entry.size() as usize];
+    assert!(matches!(
+        entry.read_into(&archive, &mut buffer, Some(&[])),
+        Err(Error::Crypto(_))
+    ));
+
+    let key = reader.encryption_key().unwrap();
+    assert_eq!(entry.read(&archive, Some(key)).unwrap(), b"hello");
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn test_extract_matching() {
+    use pf8::testing::build_archive;
+
+    let archive = build_archive(&[
+        ("script\\main.ast", b"main"),
+        ("script\\sub\\extra.ast", b"extra"),
+        ("script\\sub\\notes.txt", b"notes"),
+        ("image.png", b"png"),
+    ]);
+    let reader = Pf8Reader::from_bytes(archive).unwrap();
+    let temp_dir = TempDir::new().unwrap();
+    let output_dir = temp_dir.path().join("out");
+
+    let result = reader
+        .extract_matching(
+            &output_dir,
+            "script/**/*.ast",
+            &mut pf8::callbacks::NoOpHandler,
+        )
+        .unwrap();
+    assert_eq!((result.files, result.skipped), (2, 2));
+    assert_eq!(
+        fs::read(output_dir.join("script").join("main.ast")).unwrap(),
+        b"main"
+    );
+    assert!(
+        output_dir
+            .join("script")
+            .join("sub")
+            .join("extra.ast")
+            .exists()
+    );
+    assert!(
+        !output_dir
+            .join("script")
+            .join("sub")
+            .join("notes.txt")
+            .exists()
+    );
+    assert!(!output_dir.join("image.png").exists());
+
+    let missing_dir = temp_dir.path().join("missing");
+    let err = reader
+        .extract_matching(&missing_dir, "movie/**", &mut pf8::callbacks::NoOpHandler)
+        .unwrap_err();
+    assert!(matches!(err, Error::FileNotFound(_)));
+    assert!(!missing_dir.exists());
+}
+
+#[test]
+fn test_pack_reports_source_removed_mid_pack() {
+    // Deletes a source file after it has been planned, as when packing a live directory
+    struct RemoveSource {
+        victim: std::path::PathBuf,
+    }
+
+    impl ArchiveHandler for RemoveSource {
+        fn on_entry_started(&mut self, name: &str) -> ControlAction {
+            if name == "b.txt" {
+                fs::remove_file(&self.victim).unwrap();
+            }
+            ControlAction::Continue
+        }
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("a.txt"), b"first file").unwrap();
+    fs::write(input_dir.join("b.txt"), b"second file").unwrap();
+
+    let mut builder = Pf8Builder::new();
+    builder.add_dir(&input_dir).unwrap();
+
+    let victim = input_dir.join("b.txt");
+    let mut handler = RemoveSource {
+        victim: victim.clone(),
+    };
+    match builder.write_to_file_with_progress(&archive_path, &mut handler) {
+        Err(Error::Io(err)) => {
+            assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+            assert!(err.to_string().contains(&victim.display().to_string()));
+        }
+        other => panic!("unexpected result: {other:?}"),
+    }
+
+    // Removed before planning, the error names the file as well
+    match builder.write_to_file(&archive_path) {
+        Err(Error::Io(err)) => {
+            assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+            assert!(err.to_string().contains(&victim.display().to_string()));
+        }
+        other => panic!("unexpected result: {other:?}"),
+    }
+}
+
+#[test]
+fn test_write_to_stream_matches_file_output() {
+    use sha1::{Digest, Sha1};
+    use std::sync::{Arc, Mutex};
+
+    // Sink that can be inspected after the builder takes ownership of it
+    #[derive(Clone, Default)]
+    struct SharedSink(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedSink {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+
+    // Same fixture as test_packed_bytes_golden
+    fs::create_dir_all(input_dir.join("script")).unwrap();
+    fs::write(input_dir.join("script").join("main.txt"), b"golden script").unwrap();
+    fs::write(input_dir.join("op.mp4"), b"golden movie").unwrap();
+    fs::write(input_dir.join("system.ini"), vec![0xA5; 300]).unwrap();
+
+    let mut builder = Pf8Builder::new();
+    builder.add_dir(&input_dir).unwrap();
+
+    let sink = SharedSink::default();
+    let result = builder.write_to_stream(sink.clone()).unwrap();
+    let streamed = sink.0.lock().unwrap().clone();
+    assert_eq!(result.files, 3);
+    assert_eq!(result.archive_size, streamed.len() as u64);
+
+    let digest = Sha1::digest(&streamed);
+    let hex: String = digest.iter().map(|b| format!("{b:02x}")).collect();
+    assert_eq!(hex, "321d3e023cbc5829a1fd3ecd7f133c7da4a53506");
+
+    let reader = Pf8Reader::from_bytes(streamed).unwrap();
+    assert_eq!(
+        reader.read_file("script/main.txt").unwrap(),
+        b"golden script"
+    );
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn test_open_entry_read_and_seek() {
+    use pf8::testing::build_archive;
+    use std::io::{Read, Seek, SeekFrom};
+
+    let data: Vec<u8> = (0..=255u8).cycle().take(1000).collect();
+    let archive = build_archive(&[("image.png", &data), ("other.txt", b"other file")]);
+    let temp_dir = TempDir::new().unwrap();
+    let archive_path = temp_dir.path().join("test.pfs");
+    fs::write(&archive_path, archive).unwrap();
+    let reader = Pf8Reader::open(&archive_path).unwrap();
+
+    let mut entry = reader.open_entry("image.png").unwrap();
+    let mut head = [0u8; 16];
+    entry.read_exact(&mut head).unwrap();
+    assert_eq!(head, data[..16]);
+
+    // A second reader keeps its own position
+    let mut other = reader.open_entry("other.txt").unwrap();
+    let mut text = String::new();
+    other.read_to_string(&mut text).unwrap();
+    assert_eq!(text, "other file");
+
+    assert_eq!(entry.seek(SeekFrom::End(0)).unwrap(), 1000);
+    assert_eq!(entry.read(&mut head).unwrap(), 0);
+
+    assert_eq!(entry.seek(SeekFrom::Current(-24)).unwrap(), 976);
+    let mut tail = [0u8; 8];
+    entry.read_exact(&mut tail).unwrap();
+    assert_eq!(tail, data[976..984]);
+
+    let mut rest = Vec::new();
+    entry.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, data[984..]);
+
+    assert!(entry.seek(SeekFrom::Current(-2000)).is_err());
+    assert!(matches!(
+        reader.open_entry("missing.png"),
+        Err(Error::FileNotFound(_))
+    ));
+}
+
+#[test]
+fn test_pack_pf6_round_trip() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("plain.pfs");
+
+    fs::create_dir_all(input_dir.join("script")).unwrap();
+    fs::write(input_dir.join("script").join("main.txt"), b"plain script").unwrap();
+    fs::write(input_dir.join("op.mp4"), b"plain movie").unwrap();
+
+    let mut builder = Pf8Builder::new();
+    builder
+        .format(ArchiveFormat::Pf6)
+        .add_dir(&input_dir)
+        .unwrap();
+    assert!(
+        builder
+            .planned_entries()
+            .unwrap()
+            .iter()
+            .all(|entry| !entry.is_encrypted())
+    );
+    builder.write_to_file(&archive_path).unwrap();
+
+    let bytes = fs::read(&archive_path).unwrap();
+    assert_eq!(&bytes[..3], b"pf6");
+    // Data is stored as is
+    assert!(bytes.windows(12).any(|w| w == b"plain script"));
+
+    let reader = Pf8Reader::open(&archive_path).unwrap();
+    assert_eq!(reader.format(), ArchiveFormat::Pf6);
+    assert!(reader.entries().all(|entry| !entry.is_encrypted()));
+    assert_eq!(
+        reader.read_file("script/main.txt").unwrap(),
+        b"plain script"
+    );
+    assert_eq!(reader.read_file("op.mp4").unwrap(), b"plain movie");
+}
+
+#[test]
+fn test_pack_reports_progress_within_large_files() {
+    const MIB: u64 = 1024 * 1024;
+
+    #[derive(Default)]
+    struct ByteRecorder {
+        started: Vec<OperationType>,
+        bytes: Vec<(String, u64, Option<u64>)>,
+        abort_after: Option<u64>,
+    }
+
+    impl ArchiveHandler for ByteRecorder {
+        fn on_started(&mut self, operation: OperationType) -> ControlAction {
+            self.started.push(operation);
+            ControlAction::Continue
+        }
+
+        fn on_progress(&mut self, info: &ProgressInfo) -> ControlAction {
+            if info.phase != OperationPhase::Writing {
+                return ControlAction::Continue;
+            }
+            self.bytes.push((
+                info.current_file.clone(),
+                info.processed_bytes,
+                info.total_bytes,
+            ));
+            match self.abort_after {
+                Some(limit) if info.processed_bytes > limit => ControlAction::Abort,
+                _ => ControlAction::Continue,
+            }
+        }
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("a.txt"), b"small").unwrap();
+    fs::write(input_dir.join("big.bin"), vec![0x5A; 10 * MIB as usize]).unwrap();
+
+    let mut builder = Pf8Builder::new();
+    builder.add_dir(&input_dir).unwrap();
+
+    let mut recorder = ByteRecorder::default();
+    builder
+        .write_to_file_with_progress(&archive_path, &mut recorder)
+        .unwrap();
+    assert_eq!(recorder.started, [OperationType::Pack]);
+
+    let total = 5 + 10 * MIB;
+    let events: Vec<_> = recorder
+        .bytes
+        .iter()
+        .map(|(name, bytes, total)| (name.as_str(), *bytes, *total))
+        .collect();
+    assert_eq!(
+        events,
+        [
+            ("", 0, Some(total)),
+            ("a.txt", 5, Some(total)),
+            ("big.bin", 5 + 4 * MIB, Some(total)),
+            ("big.bin", 5 + 8 * MIB, Some(total)),
+            ("big.bin", total, Some(total)),
+        ]
+    );
+
+    // Aborting in the middle of a file stops the pack
+    let mut aborting = ByteRecorder {
+        abort_after: Some(5),
+        ..Default::default()
+    };
+    let result = builder.write_to_file_with_progress(&archive_path, &mut aborting);
+    assert!(matches!(result, Err(Error::Cancelled)));
+    assert_eq!(aborting.bytes.last().unwrap().1, 5 + 4 * MIB);
+}
+
+#[test]
+fn test_archive_remove_entries() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("game.pfs");
+    let trimmed_path = temp_dir.path().join("trimmed.pfs");
+
+    fs::create_dir_all(input_dir.join("script")).unwrap();
+    fs::write(input_dir.join("script").join("main.txt"), b"main script").unwrap();
+    fs::write(input_dir.join("script").join("debug.txt"), b"debug only").unwrap();
+    fs::write(input_dir.join("op.mp4"), b"movie").unwrap();
+    create_from_dir(&input_dir, &archive_path).unwrap();
+
+    let mut archive = Pf8Archive::open(&archive_path).unwrap();
+    let result = archive
+        .remove_entries(&["script/debug.txt"], &trimmed_path)
+        .unwrap();
+    assert_eq!(result.files, 2);
+
+    // The trimmed archive matches one packed without the file
+    fs::remove_file(input_dir.join("script").join("debug.txt")).unwrap();
+    let expected_path = temp_dir.path().join("expected.pfs");
+    create_from_dir(&input_dir, &expected_path).unwrap();
+    assert_eq!(
+        fs::read(&trimmed_path).unwrap(),
+        fs::read(&expected_path).unwrap()
+    );
+
+    // Unknown names fail without writing anything
+    let missing_path = temp_dir.path().join("missing.pfs");
+    assert!(matches!(
+        archive.remove_entries(&["script/none.txt"], &missing_path),
+        Err(Error::FileNotFound(_))
+    ));
+    assert!(!missing_path.exists());
+
+    // The archive can be rewritten in place
+    archive.remove_entries(&["op.mp4"], &archive_path).unwrap();
+    let reader = Pf8Reader::open(&archive_path).unwrap();
+    assert_eq!(reader.len(), 2);
+    assert_eq!(reader.read_file("script/main.txt").unwrap(), b"main script");
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn test_archive_remove_entries_keeps_pf6_format() {
+    use pf8::testing::build_pf6_archive;
+
+    let temp_dir = TempDir::new().unwrap();
+    let archive_path = temp_dir.path().join("game.pfs");
+    let output_path = temp_dir.path().join("removed.pfs");
+
+    let data = build_pf6_archive(&[("a.txt", b"alpha"), ("b.txt", b"beta")]);
+    fs::write(&archive_path, data).unwrap();
+
+    let mut archive = Pf8Archive::open(&archive_path).unwrap();
+    archive.remove_entries(&["a.txt"], &output_path).unwrap();
+
+    let removed = Pf8Reader::open(&output_path).unwrap();
+    assert_eq!(removed.format(), ArchiveFormat::Pf6);
+    assert_eq!(removed.len(), 1);
+    assert!(!removed.get_entry("b.txt").unwrap().is_encrypted());
+    assert_eq!(removed.read_file("b.txt").unwrap(), b"beta");
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn test_archive_update_from_dir_keeps_pf6_format() {
+    use pf8::testing::build_pf6_archive;
+
+    let temp_dir = TempDir::new().unwrap();
+    let patch_dir = temp_dir.path().join("patch");
+    let archive_path = temp_dir.path().join("game.pfs");
+    let patched_path = temp_dir.path().join("patched.pfs");
+
+    let data = build_pf6_archive(&[("script/main.txt", b"old"), ("other.txt", b"kept")]);
+    fs::write(&archive_path, data).unwrap();
+    fs::create_dir_all(patch_dir.join("script")).unwrap();
+    fs::write(patch_dir.join("script").join("main.txt"), b"new").unwrap();
+    fs::write(patch_dir.join("added.txt"), b"added").unwrap();
+
+    let mut archive = Pf8Archive::open(&archive_path).unwrap();
+    archive.update_from_dir(&patch_dir, &patched_path).unwrap();
+
+    let patched = Pf8Reader::open(&patched_path).unwrap();
+    assert_eq!(patched.format(), ArchiveFormat::Pf6);
+    assert!(patched.entries().all(|entry| !entry.is_encrypted()));
+    assert_eq!(patched.read_file("script/main.txt").unwrap(), b"new");
+    assert_eq!(patched.read_file("other.txt").unwrap(), b"kept");
+    assert_eq!(patched.read_file("added.txt").unwrap(), b"added");
+}
+
+#[test]
+fn test_archive_update_from_dir_keeps_custom_patterns() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let patch_dir = temp_dir.path().join("patch");
+    let archive_path = temp_dir.path().join("game.pfs");
+    let patched_path = temp_dir.path().join("patched.pfs");
+
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("readme.txt"), b"plain").unwrap();
+    fs::write(input_dir.join("script.dat"), b"secret").unwrap();
+    let mut builder = Pf8Builder::new();
+    builder
+        .unencrypted_patterns(&[".txt"])
+        .add_dir(&input_dir)
+        .unwrap();
+    builder.write_to_file(&archive_path).unwrap();
+
+    fs::create_dir_all(&patch_dir).unwrap();
+    fs::write(patch_dir.join("script.dat"), b"new secret").unwrap();
+    fs::write(patch_dir.join("notes.txt"), b"notes").unwrap();
+
+    let reader = Pf8Reader::open_with_patterns(&archive_path, &[".txt"]).unwrap();
+    let mut archive = Pf8Archive::from(reader);
+    archive.update_from_dir(&patch_dir, &patched_path).unwrap();
+
+    let patched = Pf8Reader::open_with_patterns(&patched_path, &[".txt"]).unwrap();
+    for (path, encrypted) in [
+        ("readme.txt", false),
+        ("notes.txt", false),
+        ("script.dat", true),
+    ] {
+        assert_eq!(patched.get_entry(path).unwrap().is_encrypted(), encrypted);
+    }
+    assert_eq!(patched.read_file("readme.txt").unwrap(), b"plain");
+    assert_eq!(patched.read_file("notes.txt").unwrap(), b"notes");
+    assert_eq!(patched.read_file("script.dat").unwrap(), b"new secret");
+}
+
+#[test]
+#[cfg(feature = "encoding")]
+fn test_archive_update_from_dir_keeps_shift_jis_names() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_file = temp_dir.path().join("input.txt");
+    let patch_dir = temp_dir.path().join("patch");
+    let archive_path = temp_dir.path().join("game.pfs");
+    let patched_path = temp_dir.path().join("patched.pfs");
+
+    fs::write(&input_file, b"scenario").unwrap();
+    let mut builder = Pf8Builder::new();
+    builder
+        .name_encoding(NameEncoding::ShiftJis)
+        .add_file_as(&input_file, "開始.txt")
+        .unwrap();
+    builder.write_to_file(&archive_path).unwrap();
+
+    fs::create_dir_all(&patch_dir).unwrap();
+    fs::write(patch_dir.join("added.txt"), b"added").unwrap();
+
+    let mut archive = Pf8Archive::open(&archive_path).unwrap();
+    assert_eq!(archive.name_encoding(), NameEncoding::ShiftJis);
+    archive.update_from_dir(&patch_dir, &patched_path).unwrap();
+
+    let options = ReaderOptions {
+        name_encoding: NameEncoding::ShiftJis,
+        ..Default::default()
+    };
+    let patched = Pf8Reader::open_with(&patched_path, options).unwrap();
+    assert_eq!(patched.read_file("開始.txt").unwrap(), b"scenario");
+    assert_eq!(patched.read_file("added.txt").unwrap(), b"added");
+}
+
+#[test]
+fn test_archive_update_from_dir() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let patch_dir = temp_dir.path().join("patch");
+    let archive_path = temp_dir.path().join("game.pfs");
+    let patched_path = temp_dir.path().join("patched.pfs");
+
+    fs::create_dir_all(input_dir.join("script")).unwrap();
+    fs::write(input_dir.join("script").join("main.txt"), b"old script").unwrap();
+    fs::write(input_dir.join("script").join("other.txt"), b"untouched").unwrap();
+    fs::write(input_dir.join("op.mp4"), b"movie").unwrap();
+    create_from_dir(&input_dir, &archive_path).unwrap();
+
+    fs::create_dir_all(patch_dir.join("script")).unwrap();
+    fs::write(
+        patch_dir.join("script").join("main.txt"),
+        b"new, longer script",
+    )
+    .unwrap();
+    fs::write(patch_dir.join("script").join("added.txt"), b"added").unwrap();
+
+    let mut archive = Pf8Archive::open(&archive_path).unwrap();
+    let result = archive.update_from_dir(&patch_dir, &patched_path).unwrap();
+    assert_eq!(result.files, 4);
+
+    // Untouched entries decrypt under the new key
+    let patched = Pf8Reader::open(&patched_path).unwrap();
+    assert_ne!(patched.encryption_key(), archive.encryption_key());
+    assert_eq!(
+        patched.read_file("script/main.txt").unwrap(),
+        b"new, longer script"
+    );
+    assert_eq!(patched.read_file("script/other.txt").unwrap(), b"untouched");
+    assert_eq!(patched.read_file("script/added.txt").unwrap(), b"added");
+    assert_eq!(patched.read_file("op.mp4").unwrap(), b"movie");
+    assert!(!patched.get_entry("op.mp4").unwrap().is_encrypted());
+
+    // Same bytes as packing the merged tree from scratch
+    fs::copy(
+        patch_dir.join("script").join("main.txt"),
+        input_dir.join("script").join("main.txt"),
+    )
+    .unwrap();
+    fs::copy(
+        patch_dir.join("script").join("added.txt"),
+        input_dir.join("script").join("added.txt"),
+    )
+    .unwrap();
+    let expected_path = temp_dir.path().join("expected.pfs");
+    create_from_dir(&input_dir, &expected_path).unwrap();
+    assert_eq!(
+        fs::read(&patched_path).unwrap(),
+        fs::read(&expected_path).unwrap()
+    );
+}
+
+#[test]
+fn test_builder_dedup_shares_identical_data() {
+    const MIB: usize = 1024 * 1024;
+
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("dedup.pfs");
+
+    fs::create_dir_all(&input_dir).unwrap();
+    let blob: Vec<u8> = (0..MIB).map(|i| (i % 251) as u8).collect();
+    for name in ["a.bin", "b.bin", "c.bin"] {
+        fs::write(input_dir.join(name), &blob).unwrap();
+    }
+    // Same content, but stored unencrypted, so it cannot share the data
+    fs::write(input_dir.join("d.mp4"), &blob).unwrap();
+
+    let mut builder = Pf8Builder::new();
+    builder.dedup(true).add_dir(&input_dir).unwrap();
+    builder.write_to_file(&archive_path).unwrap();
+
+    let archive_size = fs::metadata(&archive_path).unwrap().len();
+    assert!(archive_size > 2 * MIB as u64);
+    assert!(archive_size < 2 * MIB as u64 + 4096);
+
+    let reader = Pf8Reader::open(&archive_path).unwrap();
+    assert_eq!(reader.len(), 4);
+    let offsets: Vec<_> = ["a.bin", "b.bin", "c.bin"]
+        .iter()
+        .map(|name| reader.get_entry(name).unwrap().offset())
+        .collect();
+    assert!(offsets.iter().all(|&offset| offset == offsets[0]));
+    for name in ["a.bin", "b.bin", "c.bin", "d.mp4"] {
+        assert_eq!(reader.read_file(name).unwrap(), blob);
+    }
+    assert!(reader.verify().unwrap().is_ok());
+
+    // Without dedup every copy is stored
+    let plain_path = temp_dir.path().join("plain.pfs");
+    builder.dedup(false).write_to_file(&plain_path).unwrap();
+    assert!(fs::metadata(&plain_path).unwrap().len() > 4 * MIB as u64);
+}
+
+#[test]
+fn test_builder_write_split() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let output_base = temp_dir.path().join("root.pfs");
+
+    fs::create_dir_all(&input_dir).unwrap();
+    for name in ["a.bin", "b.bin", "c.bin", "d.bin"] {
+        fs::write(input_dir.join(name), vec![name.as_bytes()[0]; 400]).unwrap();
+    }
+    // Larger than a volume on its own
+    fs::write(input_dir.join("e.bin"), vec![b'e'; 1500]).unwrap();
+
+    let mut builder = Pf8Builder::new();
+    builder.add_dir(&input_dir).unwrap();
+    let volumes = builder.write_split(&output_base, 1000).unwrap();
+
+    let names: Vec<_> = volumes
+        .iter()
+        .map(|path| path.file_name().unwrap().to_string_lossy().to_string())
+        .collect();
+    assert_eq!(names, ["root.pfs", "root.pfs.000", "root.pfs.001"]);
+
+    let mut contents = Vec::new();
+    for (index, volume) in volumes.iter().enumerate() {
+        let size = fs::metadata(volume).unwrap().len();
+        let reader = Pf8Reader::open(volume).unwrap();
+        if index < 2 {
+            assert!(size <= 1000, "{} is {} bytes", volume.display(), size);
+        }
+        // Every volume is a complete archive
+        for entry in reader.entries() {
+            let name = entry.pf8_path().to_string();
+            let data = reader.read_file(&name).unwrap();
+            assert!(data.iter().all(|&b| b == name.as_bytes()[0]));
+            contents.push(name);
+        }
+    }
+    assert_eq!(contents, ["a.bin", "b.bin", "c.bin", "d.bin", "e.bin"]);
+
+    assert!(builder.write_split(&output_base, 0).is_err());
+}
+
+#[test]
+fn test_builder_write_split_removes_stale_volumes() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let output_base = temp_dir.path().join("root.pfs");
+
+    fs::create_dir_all(&input_dir).unwrap();
+    for name in ["a.bin", "b.bin", "c.bin", "d.bin"] {
+        fs::write(input_dir.join(name), vec![name.as_bytes()[0]; 600]).unwrap();
+    }
+    let mut builder = Pf8Builder::new();
+    builder.add_dir(&input_dir).unwrap();
+    assert_eq!(builder.write_split(&output_base, 1000).unwrap().len(), 4);
+
+    // The second split needs only two volumes
+    fs::remove_file(input_dir.join("c.bin")).unwrap();
+    fs::remove_file(input_dir.join("d.bin")).unwrap();
+    let mut builder = Pf8Builder::new();
+    builder.add_dir(&input_dir).unwrap();
+    let volumes = builder.write_split(&output_base, 1000).unwrap();
+    assert_eq!(volumes.len(), 2);
+
+    assert!(!temp_dir.path().join("root.pfs.001").exists());
+    assert!(!temp_dir.path().join("root.pfs.002").exists());
+    assert_eq!(pf8::volume_paths(&output_base), volumes);
+    let archive = Pf8Reader::open_multi(&output_base).unwrap();
+    let names: Vec<_> = archive
+        .entries()
+        .map(|entry| entry.pf8_path().to_string())
+        .collect();
+    assert_eq!(names, ["a.bin", "b.bin"]);
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn test_open_multi_reads_all_volumes() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let output_base = temp_dir.path().join("root.pfs");
+
+    fs::create_dir_all(&input_dir).unwrap();
+    for name in ["a.bin", "b.bin", "c.bin"] {
+        fs::write(input_dir.join(name), vec![name.as_bytes()[0]; 600]).unwrap();
+    }
+    let mut builder = Pf8Builder::new();
+    builder.add_dir(&input_dir).unwrap();
+    builder.write_split(&output_base, 1000).unwrap();
+
+    // A later volume replacing b.bin
+    fs::write(
+        temp_dir.path().join("root.pfs.002"),
+        pf8::testing::build_archive(&[("b.bin", b"patched"), ("d.bin", b"new")]),
+    )
+    .unwrap();
+
+    let mut archive = Pf8Reader::open_multi(&output_base).unwrap();
+    assert_eq!(archive.volumes().count(), 4);
+    let names: Vec<_> = archive
+        .entries()
+        .map(|entry| entry.pf8_path().to_string())
+        .collect();
+    assert_eq!(names, ["a.bin", "b.bin", "c.bin", "d.bin"]);
+
+    assert_eq!(archive.read_file("a.bin").unwrap(), vec![b'a'; 600]);
+    assert_eq!(archive.read_file("b.bin").unwrap(), b"patched");
+    assert_eq!(
+        archive.volume_of("b.bin").unwrap(),
+        temp_dir.path().join("root.pfs.002")
+    );
+    assert!(archive.read_file("missing.bin").is_err());
+
+    let output_dir = temp_dir.path().join("output");
+    let result = archive
+        .extract_with_options(
+            &output_dir,
+            &ExtractOptions::default(),
+            &mut pf8::callbacks::NoOpHandler,
+        )
+        .unwrap();
+    assert_eq!(result.files, 4);
+    assert_eq!(result.skipped, 1);
+    assert_eq!(fs::read(output_dir.join("b.bin")).unwrap(), b"patched");
+    assert_eq!(fs::read(output_dir.join("c.bin")).unwrap(), vec![b'c'; 600]);
+}
+
+#[test]
+#[cfg(all(feature = "testing", feature = "json"))]
+fn test_manifest_to_json() {
+    let data =
+        pf8::testing::build_archive(&[("scripts\\main.ast", b"main"), ("movie.mp4", b"mp4")]);
+    let reader = Pf8Reader::from_bytes(data).unwrap();
+    let manifest = reader.manifest();
+    let json = manifest.to_json().unwrap();
+
+    assert!(json.contains(r#""format": "PF8""#), "{json}");
+    assert!(json.contains(r#""total_size": 7"#), "{json}");
+    assert!(json.contains(r#""path": "scripts/main.ast""#), "{json}");
+    assert!(json.contains(&format!(r#""offset": {}"#, manifest.entries[1].offset)));
+    assert!(json.contains(r#""encrypted": false"#), "{json}");
+    // Output paths are only present once an output directory is set
+    assert!(!json.contains("output_path"), "{json}");
+    let json = manifest.with_output_dir("out").to_json().unwrap();
+    assert!(json.contains("output_path"), "{json}");
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn test_manifest_to_csv() {
+    let data = pf8::testing::build_archive(&[
+        ("scripts\\main.ast", b"main"),
+        ("voice\\a,\"b\".ogg", b"ogg"),
+        ("画像\\背景.png", b"png!!"),
+    ]);
+    let reader = Pf8Reader::from_bytes(data).unwrap();
+    let manifest = reader.manifest();
+    let offsets: Vec<_> = manifest.entries.iter().map(|e| e.offset).collect();
+
+    assert_eq!(
+        manifest.to_csv(),
+        format!(
+            "path,size,encrypted,offset\n\
+             scripts/main.ast,4,true,{}\n\
+             \"voice/a,\"\"b\"\".ogg\",3,true,{}\n\
+             画像/背景.png,5,true,{}\n\
+             Total,12,,\n",
+            offsets[0], offsets[1], offsets[2]
+        )
+    );
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn test_diff_archives() {
+    let a = Pf8Reader::from_bytes(pf8::testing::build_archive(&[
+        ("z\\same.txt", b"same"),
+        ("removed.txt", b"gone"),
+        ("resized.txt", b"short"),
+        ("edited.txt", b"aaaa"),
+    ]))
+    .unwrap();
+    let b = Pf8Reader::from_bytes(pf8::testing::build_archive(&[
+        ("edited.txt", b"bbbb"),
+        ("resized.txt", b"much longer"),
+        ("added.txt", b"new"),
+        ("z\\same.txt", b"same"),
+    ]))
+    .unwrap();
+
+    let report = a.diff(&b).unwrap();
+    assert_eq!(report.only_in_a, ["removed.txt"]);
+    assert_eq!(report.only_in_b, ["added.txt"]);
+    assert_eq!(
+        report.changed,
+        [
+            ChangedEntry {
+                path: "edited.txt".to_string(),
+                old_size: 4,
+                new_size: 4,
+            },
+            ChangedEntry {
+                path: "resized.txt".to_string(),
+                old_size: 5,
+                new_size: 11,
+            },
+        ]
+    );
+    assert_eq!(report.len(), 4);
+
+    // Comparing only the index misses the same-size edit
+    let report = a.diff_index(&b);
+    let changed: Vec<_> = report.changed.iter().map(|e| e.path.as_str()).collect();
+    assert_eq!(changed, ["resized.txt"]);
+
+    assert!(a.diff(&a).unwrap().is_empty());
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn test_open_with_key() {
+    let temp_dir = TempDir::new().unwrap();
+    let archive_path = temp_dir.path().join("test.pfs");
+    fs::write(
+        &archive_path,
+        pf8::testing::build_archive(&[("script.txt", b"secret text"), ("movie.mp4", b"plain")]),
+    )
+    .unwrap();
+
+    let derived = Pf8Reader::open(&archive_path)
+        .unwrap()
+        .encryption_key()
+        .unwrap()
+        .to_vec();
+    assert_eq!(derived.len(), 20);
+
+    // The derived key supplied explicitly decrypts as usual
+    let reader = Pf8Reader::open_with_key(&archive_path, derived.clone()).unwrap();
+    assert_eq!(reader.read_file("script.txt").unwrap(), b"secret text");
+    assert!(reader.verify().unwrap().key_matches);
+
+    // Any other key is used as given
+    let reader = Pf8Reader::open_with_key(&archive_path, vec![0xAA]).unwrap();
+    assert_eq!(reader.encryption_key(), Some(&[0xAA][..]));
+    let expected: Vec<u8> = b"secret text"
+        .iter()
+        .enumerate()
+        .map(|(i, b)| b ^ derived[i % derived.len()] ^ 0xAA)
+        .collect();
+    assert_eq!(reader.read_file("script.txt").unwrap(), expected);
+    assert_eq!(reader.read_file("movie.mp4").unwrap(), b"plain");
+    assert!(!reader.verify().unwrap().key_matches);
+
+    // key_matches still checks the key derived from the index
+    let derived_hex: String = derived.iter().map(|b| format!("{b:02x}")).collect();
+    assert!(reader.key_matches(&derived_hex));
+    assert!(!reader.key_matches("aa"));
+
+    assert!(Pf8Reader::open_with_key(&archive_path, Vec::new()).is_err());
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn test_overlapping_entries_warn_on_extraction() {
+    use pf8::testing::{Corruption, build_archive, corrupt_index};
+
+    struct WarningCollector(Vec<String>);
+
+    impl ArchiveHandler for WarningCollector {
+        fn on_warning(&mut self, message: &str) -> ControlAction {
+            self.0.push(message.to_string());
+            ControlAction::Continue
+        }
+    }
+
+    let mut archive = build_archive(&[("a.txt", b"first"), ("b.txt", b"second")]);
+    let reader = Pf8Reader::from_bytes(archive.clone()).unwrap();
+    let a_offset = reader.get_entry("a.txt").unwrap().offset();
+    assert_eq!(
+        reader.data_layout(),
+        vec![
+            ("a.txt".to_string(), a_offset, 5),
+            ("b.txt".to_string(), a_offset + 5, 6),
+        ]
+    );
+
+    // b.txt starts inside a.txt
+    corrupt_index(
+        &mut archive,
+        Corruption::Offset {
+            entry: 1,
+            offset: a_offset + 2,
+        },
+    );
+    let mut reader = Pf8Reader::from_bytes(archive).unwrap();
+    assert_eq!(
+        reader.data_layout(),
+        vec![
+            ("a.txt".to_string(), a_offset, 5),
+            ("b.txt".to_string(), a_offset + 2, 6),
+        ]
+    );
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut handler = WarningCollector(Vec::new());
+    reader
+        .extract_all_with_progress(temp_dir.path(), &mut handler)
+        .unwrap();
+    assert_eq!(handler.0.len(), 1);
+    assert!(
+        handler.0[0].contains("b.txt overlaps a.txt"),
+        "{:?}",
+        handler.0
+    );
+
+    let mut handler = WarningCollector(Vec::new());
+    reader
+        .extract_with_options(temp_dir.path(), &ExtractOptions::default(), &mut handler)
+        .unwrap();
+    assert_eq!(handler.0.len(), 1);
+}
+
+#[test]
+fn test_custom_buffer_size_round_trip() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let archive_path = temp_dir.path().join("test.pfs");
+    let output_dir = temp_dir.path().join("output");
+
+    // Files smaller than, equal to and spanning several 16 KiB buffers
+    let sizes = [100, 16 * 1024, 16 * 1024 + 1, 100_003];
+    fs::create_dir_all(&input_dir).unwrap();
+    for (index, size) in sizes.iter().enumerate() {
+        let data: Vec<u8> = (0..*size).map(|i| (i * 7 + index) as u8).collect();
+        fs::write(input_dir.join(format!("file{index}.bin")), data).unwrap();
+    }
+
+    let mut builder = Pf8Builder::new();
+    builder.add_dir(&input_dir).unwrap();
+    builder.buffer_size(16 * 1024).unwrap();
+    builder.write_to_file(&archive_path).unwrap();
+
+    let mut reader = Pf8Reader::open(&archive_path).unwrap();
+    reader.set_buffer_size(16 * 1024).unwrap();
+    reader.extract_all(&output_dir).unwrap();
+    for index in 0..sizes.len() {
+        let name = format!("file{index}.bin");
+        assert_eq!(
+            fs::read(output_dir.join(&name)).unwrap(),
+            fs::read(input_dir.join(&name)).unwrap(),
+            "{name}"
+        );
+    }
+
+    // Buffers below 4 KiB are rejected
+    let err = reader.set_buffer_size(1024).err().unwrap();
+    assert!(matches!(err, Error::Io(ref e) if e.kind() == std::io::ErrorKind::InvalidInput));
+    assert!(builder.buffer_size(1024).is_err());
+    let mut writer = Pf8Writer::create(temp_dir.path().join("writer.pfs")).unwrap();
+    assert!(writer.set_buffer_size(4 * 1024).is_ok());
+    assert!(writer.set_buffer_size(4 * 1024 - 1).is_err());
+
+    // A builder without an explicit size keeps the writer's own
+    #[derive(Default)]
+    struct ProgressCounter(usize);
+
+    impl ArchiveHandler for ProgressCounter {
+        fn on_progress(&mut self, _info: &ProgressInfo) -> ControlAction {
+            self.0 += 1;
+            ControlAction::Continue
+        }
+    }
+
+    let mut builder = Pf8Builder::new();
+    builder.add_dir(&input_dir).unwrap();
+    let mut counts = Vec::new();
+    for size in [4 * 1024 * 1024, 16 * 1024] {
+        let mut writer = Pf8Writer::create(temp_dir.path().join("writer.pfs")).unwrap();
+        writer.set_buffer_size(size).unwrap();
+        let mut counter = ProgressCounter::default();
+        builder
+            .write_to_writer_with_progress(&mut writer, &mut counter)
+            .unwrap();
+        counts.push(counter.0);
+    }
+    assert!(counts[1] > counts[0], "{counts:?}");
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn test_get_entry_ci() {
+    let reader = Pf8Reader::from_bytes(pf8::testing::build_archive(&[
+        ("script\\main.ast", b"main"),
+        ("Image\\BG.png", b"png"),
+    ]))
+    .unwrap();
+
+    // Separators are normalized by both methods
+    assert!(reader.get_entry("script/main.ast").is_some());
+    assert!(reader.get_entry("script\\main.ast").is_some());
+    assert!(reader.get_entry_ci("script/main.ast").is_some());
+
+    // Only the CI variant ignores case
+    assert!(reader.get_entry("Script/Main.ast").is_none());
+    assert_eq!(
+        reader.get_entry_ci("Script/Main.ast").unwrap().pf8_path(),
+        "script\\main.ast"
+    );
+    assert_eq!(
+        reader.get_entry_ci("image\\bg.PNG").unwrap().pf8_path(),
+        "Image\\BG.png"
+    );
+    assert!(reader.get_entry_ci("image/other.png").is_none());
+
+    // Names differing only by case: the last one wins, as in an
+    // insensitive reader
+    let reader = Pf8Reader::from_bytes(pf8::testing::build_archive(&[
+        ("a.txt", b"lower"),
+        ("A.txt", b"upper"),
+    ]))
+    .unwrap();
+    assert_eq!(reader.get_entry("a.txt").unwrap().pf8_path(), "a.txt");
+    assert_eq!(reader.get_entry_ci("a.txt").unwrap().pf8_path(), "A.txt");
+}
+
+#[test]
+fn test_preserve_insertion_order() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir_all(&input_dir).unwrap();
+    for name in ["c.txt", "a.txt", "b.txt"] {
+        fs::write(input_dir.join(name), name).unwrap();
+    }
+
+    let write = |configure: &dyn Fn(&mut Pf8Builder)| {
+        let archive_path = temp_dir.path().join("test.pfs");
+        let mut builder = Pf8Builder::new();
+        for name in ["c.txt", "a.txt", "b.txt"] {
+            builder.add_file_as(input_dir.join(name), name).unwrap();
+        }
+        builder.embed_tool_marker(true);
+        configure(&mut builder);
+        builder.write_to_file_verified(&archive_path).unwrap();
+
+        let mut reader = Pf8Reader::open(&archive_path).unwrap();
+        reader.show_sidecars(true);
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            assert_eq!(reader.read_file(name).unwrap(), name.as_bytes());
+        }
+        let index: Vec<_> = reader.entries().map(|e| e.pf8_path().to_string()).collect();
+        let data: Vec<_> = reader
+            .entries_by_offset()
+            .iter()
+            .map(|e| e.pf8_path().to_string())
+            .collect();
+        (index, data)
+    };
+
+    let (index, data) = write(&|_| {});
+    assert_eq!(index, [TOOL_MARKER_NAME, "a.txt", "b.txt", "c.txt"]);
+    assert_eq!(data, index);
+
+    let (index, data) = write(&|builder| {
+        builder.preserve_order(true);
+    });
+    assert_eq!(index, ["c.txt", "a.txt", "b.txt", TOOL_MARKER_NAME]);
+    assert_eq!(data, index);
+
+    // The data order can still be chosen separately
+    let (index, data) = write(&|builder| {
+        builder
+            .entry_order(EntryOrder::Insertion)
+            .sort_order(SortOrder::SizeAscending);
+    });
+    assert_eq!(index, ["c.txt", "a.txt", "b.txt", TOOL_MARKER_NAME]);
+    assert_eq!(data[3], TOOL_MARKER_NAME);
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn test_archive_statistics() {
+    use pf8::testing::{Corruption, build_archive, corrupt_index};
+
+    let archive = build_archive(&[("a.txt", b"12345"), ("movie.mp4", b"123")]);
+    let reader = Pf8Reader::from_bytes(archive.clone()).unwrap();
+    let data_start = reader.get_entry("a.txt").unwrap().offset() as u64;
+    assert_eq!(
+        reader.statistics(),
+        ArchiveStats {
+            total_file_size: archive.len() as u64,
+            index_size: data_start,
+            data_size: 8,
+            logical_size: 8,
+            entry_count: 2,
+            encrypted_count: 1,
+            slack_bytes: 0,
+        }
+    );
+
+    // Trailing padding is slack
+    let mut padded = archive.clone();
+    padded.extend_from_slice(&[0; 100]);
+    let stats = Pf8Reader::from_bytes(padded).unwrap().statistics();
+    assert_eq!(stats.total_file_size, archive.len() as u64 + 100);
+    assert_eq!(stats.slack_bytes, 100);
+
+    // Shared data counts once; the bytes no longer referenced become slack
+    let mut shared = archive;
+    corrupt_index(
+        &mut shared,
+        Corruption::Offset {
+            entry: 1,
+            offset: data_start as u32,
+        },
+    );
+    let stats = Pf8Reader::from_bytes(shared).unwrap().statistics();
+    assert_eq!(stats.data_size, 5);
+    assert_eq!(stats.logical_size, 8);
+    assert_eq!(stats.slack_bytes, 3);
+}
+
+#[test]
+fn test_builder_plan_matches_written_archive() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("a.txt"), b"same").unwrap();
+    fs::write(input_dir.join("b.txt"), b"same").unwrap();
+    fs::write(input_dir.join("movie.mp4"), b"frames").unwrap();
+
+    let mut builder = Pf8Builder::new();
+    builder.add_dir(&input_dir).unwrap();
+    builder.dedup(true).embed_tool_marker(true).reproducible();
+
+    let archive_path = temp_dir.path().join("test.pfs");
+    let planned = builder.plan().unwrap();
+    assert!(!archive_path.exists());
+
+    let paths: Vec<_> = planned.iter().map(|p| p.entry.pf8_path()).collect();
+    assert_eq!(paths, [TOOL_MARKER_NAME, "a.txt", "b.txt", "movie.mp4"]);
+    assert_eq!(planned[0].source, None);
+    assert_eq!(planned[1].source, Some(input_dir.join("a.txt")));
+    assert_eq!(planned[2].source, Some(input_dir.join("b.txt")));
+    assert!(!planned[1].shared);
+    assert!(planned[2].shared);
+    assert!(planned[1].entry.is_encrypted());
+    assert!(!planned[3].entry.is_encrypted());
+
+    builder.write_to_file(&archive_path).unwrap();
+    let reader = Pf8Reader::open(&archive_path).unwrap();
+    for planned in &planned {
+        let entry = reader.get_entry(planned.entry.path()).unwrap();
+        assert_eq!(entry.offset(), planned.entry.offset());
+        assert_eq!(entry.size(), planned.entry.size());
+        assert_eq!(entry.is_encrypted(), planned.entry.is_encrypted());
+    }
+}
+
+#[test]
+fn test_builder_exclude_patterns() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir_all(input_dir.join("script")).unwrap();
+    fs::create_dir_all(input_dir.join(".git").join("objects")).unwrap();
+    fs::write(input_dir.join("a.txt"), b"a").unwrap();
+    fs::write(input_dir.join("a.txt.bak"), b"old").unwrap();
+    fs::write(input_dir.join("Thumbs.db"), b"thumbs").unwrap();
+    fs::write(input_dir.join("script").join("b.txt"), b"b").unwrap();
+    fs::write(input_dir.join("script").join("b.txt.bak"), b"old").unwrap();
+    fs::write(input_dir.join(".git").join("objects").join("x"), b"git").unwrap();
+
+    let archive_paths = |builder: &Pf8Builder| {
+        let mut paths: Vec<_> = builder
+            .plan()
+            .unwrap()
+            .into_iter()
+            .map(|p| p.entry.pf8_path().to_string())
+            .collect();
+        paths.sort();
+        paths
+    };
+
+    let mut builder = Pf8Builder::new();
+    builder
+        .exclude("**/*.bak")
+        .unwrap()
+        .exclude("Thumbs.db")
+        .unwrap()
+        .exclude(".git/**")
+        .unwrap();
+    builder.add_dir(&input_dir).unwrap();
+    assert_eq!(archive_paths(&builder), ["a.txt", "script\\b.txt"]);
+
+    // Patterns match the path relative to the added directory, not the prefix
+    let mut builder = Pf8Builder::new();
+    builder.exclude("script").unwrap().exclude(".git").unwrap();
+    builder.add_dir_as(&input_dir, "data").unwrap();
+    assert_eq!(
+        archive_paths(&builder),
+        ["data\\Thumbs.db", "data\\a.txt", "data\\a.txt.bak"]
+    );
+
+    assert!(Pf8Builder::new().exclude("[").is_err());
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn test_zero_size_entry_offset_is_not_checked() {
+    use pf8::testing::{Corruption, build_archive, corrupt_index};
+
+    let temp_dir = TempDir::new().unwrap();
+    let output_dir = temp_dir.path().join("output");
+
+    // Placeholder entries may point at offset 0 or beyond the end of the
+    // file; the data entry is unencrypted so it survives the index change
+    for offset in [0, u32::MAX] {
+        let mut archive = build_archive(&[("empty.txt", &[]), ("a.mp4", b"data")]);
+        corrupt_index(&mut archive, Corruption::Offset { entry: 0, offset });
+        let mut reader = Pf8Reader::from_bytes(archive.clone()).unwrap();
+        assert_eq!(reader.get_entry("empty.txt").unwrap().offset(), offset);
+        assert_eq!(reader.read_file("empty.txt").unwrap(), b"");
+        assert_eq!(reader.read_file("a.mp4").unwrap(), b"data");
+        reader.extract_all(&output_dir).unwrap();
+        assert_eq!(fs::read(output_dir.join("empty.txt")).unwrap(), b"");
+
+        // Auto decides on the first entry holding data, which is absolute
+        let archive_path = temp_dir.path().join("placeholder.pfs");
+        fs::write(&archive_path, &archive).unwrap();
+        let options = ReaderOptions {
+            offset_mode: OffsetMode::Auto,
+            ..Default::default()
+        };
+        let reader = Pf8Reader::open_with(&archive_path, options).unwrap();
+        assert_eq!(reader.read_file("a.mp4").unwrap(), b"data");
+    }
+}
@@ -58,6 +58,87 @@ pub fn determine_pack_output(
     }
 }
 
+/// Ensure the output archive does not lie inside any input directory
+///
+/// Packing `data/` into `data/out.pfs` would walk the archive being written
+/// (or a previous one that is about to be truncated) as a source file.
+pub fn ensure_output_outside_inputs(inputs: &[PathBuf], output: &Path) -> Result<()> {
+    // The output may not exist yet, so resolve its parent directory instead
+    let parent = match output.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let (Ok(parent), Some(file_name)) = (parent.canonicalize(), output.file_name()) else {
+        return Ok(());
+    };
+    let output = parent.join(file_name);
+
+    for input in inputs.iter().filter(|input| input.is_dir()) {
+        let input = input.canonicalize()?;
+        if output.starts_with(&input) {
+            return Err(anyhow::anyhow!(
+                "Output {:?} is inside input directory {:?}; choose an output outside the source tree",
+                output,
+                input
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Render a log record as a single JSON object line
+///
+/// The object has `level`, `target` and `message` fields, followed by any
+/// structured key-values attached to the record (e.g. `entry`, `path`).
+pub fn json_log_line(record: &log::Record) -> String {
+    struct Fields(String);
+
+    impl<'kvs> log::kv::VisitSource<'kvs> for Fields {
+        fn visit_pair(
+            &mut self,
+            key: log::kv::Key<'kvs>,
+            value: log::kv::Value<'kvs>,
+        ) -> std::result::Result<(), log::kv::Error> {
+            self.0.push_str(&format!(
+                ",{}:{}",
+                json_string(key.as_str()),
+                json_string(&value.to_string())
+            ));
+            Ok(())
+        }
+    }
+
+    let mut fields = Fields(String::new());
+    let _ = record.key_values().visit(&mut fields);
+
+    format!(
+        "{{\"level\":{},\"target\":{},\"message\":{}{}}}",
+        json_string(record.level().as_str()),
+        json_string(record.target()),
+        json_string(&record.args().to_string()),
+        fields.0
+    )
+}
+
+/// Quote and escape a string as a JSON string literal
+fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -85,4 +166,39 @@ mod tests {
         std::env::set_current_dir(original)?;
         Ok(())
     }
+
+    #[test]
+    fn test_output_inside_input_dir() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let data = temp_dir.path().join("data");
+        std::fs::create_dir_all(data.join("sub"))?;
+        let inputs = vec![data.clone()];
+
+        assert!(ensure_output_outside_inputs(&inputs, &data.join("out.pfs")).is_err());
+        assert!(ensure_output_outside_inputs(&inputs, &data.join("sub").join("out.pfs")).is_err());
+        assert!(ensure_output_outside_inputs(&inputs, &temp_dir.path().join("out.pfs")).is_ok());
+
+        // Sibling directories sharing a name prefix are not nested
+        let sibling = temp_dir.path().join("data2");
+        std::fs::create_dir_all(&sibling)?;
+        assert!(ensure_output_outside_inputs(&inputs, &sibling.join("out.pfs")).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_log_line() {
+        let fields = [("entry", "a\\b.txt")];
+        let line = json_log_line(
+            &log::Record::builder()
+                .level(log::Level::Warn)
+                .target("pfs_rs")
+                .args(format_args!("say \"hi\"\n"))
+                .key_values(&fields)
+                .build(),
+        );
+        assert_eq!(
+            line,
+            r#"{"level":"WARN","target":"pfs_rs","message":"say \"hi\"\n","entry":"a\\b.txt"}"#
+        );
+    }
 }
@@ -1,10 +1,15 @@
 use anyhow::Result;
 use clap::CommandFactory;
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use log::{error, info};
 use pf8::{self, ArchiveHandler, ControlAction};
-use pfs_rs::{determine_extract_output, determine_pack_output, util};
+use pfs_rs::{
+    determine_extract_output, determine_pack_output, ensure_output_outside_inputs, json_log_line,
+    util,
+};
+use std::collections::HashSet;
 use std::fs;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::time::Instant;
 
@@ -26,6 +31,23 @@ struct Args {
     /// Verbose mode (show detailed information)
     #[arg(short = 'v', long = "verbose", global = true, default_value_t = false)]
     verbose: bool,
+    /// Print the archive encryption key (hex SHA1) to stderr
+    #[arg(
+        long = "print-key",
+        visible_alias = "show-key",
+        global = true,
+        default_value_t = false
+    )]
+    print_key: bool,
+    /// Produce byte-identical output for identical inputs (fixed epoch for generated metadata)
+    #[arg(long = "reproducible", global = true, default_value_t = false)]
+    reproducible: bool,
+    /// Format of log and diagnostic output on stderr
+    #[arg(long = "log-format", global = true, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+    /// Print what create or extract would do without writing anything
+    #[arg(short = 'n', long = "dry-run", global = true, default_value_t = false)]
+    dry_run: bool,
     /// Input file or dir use for drag-in
     #[arg(hide = true)]
     inputs: Vec<PathBuf>,
@@ -49,6 +71,19 @@ enum Commands {
         /// Strip NUMBER leading components from file names on extraction
         #[arg(long, value_name = "NUMBER")]
         strip_components: Option<usize>,
+        /// Only extract entries matching GLOB (repeatable)
+        #[arg(long, value_name = "GLOB")]
+        include: Vec<String>,
+        /// Skip entries matching GLOB (repeatable)
+        #[arg(long, value_name = "GLOB")]
+        exclude: Vec<String>,
+        /// How archive names map to output paths.
+        ///
+        /// 'backslash' writes flattened files literally named like
+        /// 'dir\file.txt', which many tools mishandle; on Windows it behaves
+        /// like 'native'.
+        #[arg(long, value_enum, default_value_t = NameStyle::Native)]
+        name_style: NameStyle,
     },
     /// Create pfs archive from files/directories
     ///
@@ -67,6 +102,103 @@ enum Commands {
         /// Disable smart detection (e.g., system.ini auto-pathstrip)
         #[arg(long, default_value_t = false)]
         no_smart_detect: bool,
+        /// Skip source files larger than BYTES (with a warning)
+        #[arg(long, value_name = "BYTES")]
+        max_file_size: Option<u64>,
+        /// Split the archive into volumes of about SIZE (e.g. 2G, 500M)
+        ///
+        /// Writes root.pfs, root.pfs.000, root.pfs.001, ... Each volume is a
+        /// complete archive; files are never split across volumes.
+        #[arg(long, value_name = "SIZE", value_parser = util::parse_size)]
+        split_size: Option<u64>,
+        /// Skip files and directories matching GLOB (repeatable)
+        ///
+        /// Matched against the path relative to each input directory, e.g.
+        /// '**/*.bak' or '.git'. Excluded directories are not descended into.
+        #[arg(long, value_name = "GLOB")]
+        exclude: Vec<String>,
+    },
+    /// Show a summary of a pfs archive
+    Info {
+        /// Input pfs file
+        input: PathBuf,
+        /// Number of extensions to show in the size breakdown
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+    },
+    /// Check the consistency of a pfs archive
+    Verify {
+        /// Input pfs file
+        input: PathBuf,
+        /// Decrypt a sample of text entries to detect a key/data mismatch
+        #[arg(long, default_value_t = false)]
+        decrypt_check: bool,
+        /// Number of text entries checked by --decrypt-check
+        #[arg(long, value_name = "N", default_value_t = 16)]
+        sample: usize,
+        /// Expected encryption key (hex SHA1); fail if the derived key differs
+        #[arg(long, value_name = "HEX")]
+        key: Option<String>,
+    },
+    /// Summarize every pfs archive in a directory
+    Scan {
+        /// Directory to scan (non-recursive)
+        dir: PathBuf,
+    },
+    /// Merge several pfs archives into one
+    Merge {
+        /// Input pfs files, later archives take precedence on overwrite
+        #[arg(required = true, num_args = 2..)]
+        inputs: Vec<PathBuf>,
+        /// Output pfs file
+        #[arg(short = 'o', long = "output")]
+        output: PathBuf,
+        /// How to handle a path present in more than one input
+        #[arg(long, value_enum, default_value_t = OnConflict::Error)]
+        on_conflict: OnConflict,
+    },
+    /// Write a copy of a pfs archive without the given entries
+    Remove {
+        /// Input pfs file
+        input: PathBuf,
+        /// Output pfs file (may be the input file)
+        #[arg(short = 'o', long = "out", alias = "output")]
+        output: PathBuf,
+        /// Paths of the entries to remove
+        #[arg(required = true)]
+        paths: Vec<String>,
+    },
+    /// Write a copy of a pfs archive with the files of a directory applied
+    ///
+    /// Files replace the entries at the same path relative to the directory;
+    /// files without a matching entry are added.
+    Update {
+        /// Input pfs file
+        input: PathBuf,
+        /// Directory holding the replacement and new files
+        dir: PathBuf,
+        /// Output pfs file (may be the input file)
+        #[arg(short = 'o', long = "output")]
+        output: PathBuf,
+    },
+    /// Compare the entries of two pfs archives
+    ///
+    /// Lists entries only in A ('-'), only in B ('+') and present in both
+    /// with different size or content ('M'), sorted by path. Fails if the
+    /// archives differ.
+    Diff {
+        /// First pfs file
+        a: PathBuf,
+        /// Second pfs file
+        b: PathBuf,
+        /// Compare only paths and sizes from the index, without reading data
+        #[arg(long, default_value_t = false)]
+        names_only: bool,
+    },
+    /// Low-level tools for inspecting archives
+    Debug {
+        #[command(subcommand)]
+        command: DebugCommands,
     },
     /// List contents of pfs archive
     #[command(visible_alias = "l", alias = "ls")]
@@ -76,40 +208,578 @@ enum Commands {
         /// Show detailed information
         #[arg(short = 'l', long, default_value_t = false)]
         long: bool,
+        /// Output format of the listing
+        #[arg(long, value_enum, default_value_t = ListFormat::Text)]
+        format: ListFormat,
+        /// Show how the file splits into index, data and slack instead of
+        /// the entries
+        #[arg(long, default_value_t = false, conflicts_with_all = ["long", "format"])]
+        stats: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum DebugCommands {
+    /// Print a hex+ASCII dump of the start of an entry
+    Dump {
+        /// Input pfs file
+        archive: PathBuf,
+        /// Path of the entry inside the archive
+        entry: String,
+        /// Dump the stored (encrypted) bytes instead of the decrypted ones
+        #[arg(long, default_value_t = false)]
+        raw: bool,
+        /// Number of bytes to dump
+        #[arg(long, value_name = "N", default_value_t = 256)]
+        len: usize,
     },
 }
 
+/// Output format of the logger
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum LogFormat {
+    /// Human-readable lines
+    Text,
+    /// One JSON object per line, for tooling
+    Json,
+}
+
+/// Output format of `list`
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ListFormat {
+    /// Human-readable listing
+    Text,
+    /// JSON manifest with the offset, size and encryption of every entry
+    Json,
+    /// CSV rows of path, size, encryption and offset with a total line
+    Csv,
+}
+
+/// Output path mapping for `extract`
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum NameStyle {
+    /// Nested directories using the OS separator
+    Native,
+    /// Nested directories joined with '/'
+    Forward,
+    /// Flattened file names containing literal '\'
+    Backslash,
+}
+
+impl From<NameStyle> for pf8::NameStyle {
+    fn from(value: NameStyle) -> Self {
+        match value {
+            NameStyle::Native => pf8::NameStyle::Native,
+            NameStyle::Forward => pf8::NameStyle::Forward,
+            NameStyle::Backslash => pf8::NameStyle::Backslash,
+        }
+    }
+}
+
+/// Collision policy for `merge`
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum OnConflict {
+    /// Keep the entry from the first archive
+    Skip,
+    /// Keep the entry from the last archive
+    Overwrite,
+    /// Abort the merge
+    Error,
+}
+
+impl From<OnConflict> for pf8::ConflictPolicy {
+    fn from(value: OnConflict) -> Self {
+        match value {
+            OnConflict::Skip => pf8::ConflictPolicy::Skip,
+            OnConflict::Overwrite => pf8::ConflictPolicy::Overwrite,
+            OnConflict::Error => pf8::ConflictPolicy::Error,
+        }
+    }
+}
+
+fn command_merge(
+    inputs: &[PathBuf],
+    output: &Path,
+    on_conflict: OnConflict,
+    overwrite: bool,
+    quiet: bool,
+) -> Result<()> {
+    if output.exists() && !overwrite {
+        return Err(anyhow::anyhow!(
+            "Output file already exists: {:?} (use --force to overwrite)",
+            output
+        ));
+    }
+
+    if !quiet {
+        info!("Merging {} archives into {:?}", inputs.len(), output);
+    }
+
+    let result = pf8::merge(inputs, output, on_conflict.into())?;
+
+    if !quiet {
+        info!(
+            "Done: Files: {}, Size: {:.2} MB",
+            result.files,
+            result.archive_size as f64 / 1024.0 / 1024.0
+        );
+    }
+    Ok(())
+}
+
+fn command_remove(
+    input: &Path,
+    output: &Path,
+    paths: &[String],
+    overwrite: bool,
+    quiet: bool,
+) -> Result<()> {
+    if output.exists() && !overwrite {
+        return Err(anyhow::anyhow!(
+            "Output file already exists: {:?} (use --force to overwrite)",
+            output
+        ));
+    }
+
+    let mut archive = pf8::Pf8Archive::open(input)?;
+    let result = archive.remove_entries(paths, output)?;
+
+    if !quiet {
+        info!(
+            "Removed {} entries, Files: {}, Size: {:.2} MB",
+            paths.len(),
+            result.files,
+            result.archive_size as f64 / 1024.0 / 1024.0
+        );
+    }
+    Ok(())
+}
+
+fn command_update(
+    input: &Path,
+    dir: &Path,
+    output: &Path,
+    overwrite: bool,
+    quiet: bool,
+) -> Result<()> {
+    if output.exists() && !overwrite {
+        return Err(anyhow::anyhow!(
+            "Output file already exists: {:?} (use --force to overwrite)",
+            output
+        ));
+    }
+
+    let mut archive = pf8::Pf8Archive::open(input)?;
+    let result = archive.update_from_dir(dir, output)?;
+
+    if !quiet {
+        info!(
+            "Done: Files: {}, Size: {:.2} MB",
+            result.files,
+            result.archive_size as f64 / 1024.0 / 1024.0
+        );
+    }
+    Ok(())
+}
+
+fn command_debug_dump(archive: &Path, entry: &str, raw: bool, len: usize) -> Result<()> {
+    let mut archive = pf8::Pf8Archive::open(archive)?;
+    let data = if raw {
+        archive.read_file_raw_prefix(entry, len)?
+    } else {
+        archive.read_file_prefix(entry, len)?
+    };
+
+    print!("{}", util::hex_dump(&data));
+    Ok(())
+}
+
+fn command_info(input: &Path, top: usize) -> Result<()> {
+    let archive = pf8::Pf8Archive::open(input)?;
+    let total_size = archive.total_size();
+
+    println!("Archive:   {}", input.display());
+    println!("Format:    {}", archive.format());
+    println!("Entries:   {}", archive.len());
+    println!(
+        "Encrypted: {}",
+        archive.entries().filter(|e| e.is_encrypted()).count()
+    );
+    let shared = pf8::check_layout(&archive).shared_entries;
+    if shared > 0 {
+        println!("Shared:    {} entries share data via dedup", shared);
+    }
+    println!("Data size: {} bytes", total_size);
+    println!("File size: {} bytes", archive.archive_size());
+
+    let mut by_size: Vec<(String, u64)> = archive.size_by_extension().into_iter().collect();
+    by_size.sort_by(|(a_ext, a_size), (b_ext, b_size)| b_size.cmp(a_size).then(a_ext.cmp(b_ext)));
+
+    println!();
+    println!("Size by extension:");
+    for (ext, size) in by_size.into_iter().take(top) {
+        let share = if total_size > 0 {
+            size as f64 * 100.0 / total_size as f64
+        } else {
+            0.0
+        };
+        let ext = if ext.is_empty() {
+            "(none)".to_string()
+        } else {
+            format!(".{ext}")
+        };
+        println!("  {:<10} {:>14} bytes {:>6.2}%", ext, size, share);
+    }
+    Ok(())
+}
+
+fn command_diff(a: &Path, b: &Path, names_only: bool) -> Result<()> {
+    let archive_a = pf8::Pf8Archive::open(a)?;
+    let archive_b = pf8::Pf8Archive::open(b)?;
+    let report = if names_only {
+        archive_a.diff_index(&archive_b)
+    } else {
+        archive_a.diff(&archive_b)?
+    };
+
+    // Merge the three sorted lists back into a single listing by path
+    let mut lines = Vec::with_capacity(report.len());
+    lines.extend(
+        report
+            .only_in_a
+            .iter()
+            .map(|path| (path, "-", String::new())),
+    );
+    lines.extend(
+        report
+            .only_in_b
+            .iter()
+            .map(|path| (path, "+", String::new())),
+    );
+    lines.extend(report.changed.iter().map(|entry| {
+        let detail = if entry.old_size == entry.new_size {
+            " (content differs)".to_string()
+        } else {
+            format!(" ({} -> {} bytes)", entry.old_size, entry.new_size)
+        };
+        (&entry.path, "M", detail)
+    }));
+    lines.sort_by(|x, y| x.0.cmp(y.0));
+    for (path, marker, detail) in &lines {
+        println!("{} {}{}", marker, path, detail);
+    }
+
+    if !report.is_empty() {
+        return Err(anyhow::anyhow!(
+            "{:?} and {:?} differ: {} only in A, {} only in B, {} changed",
+            a,
+            b,
+            report.only_in_a.len(),
+            report.only_in_b.len(),
+            report.changed.len()
+        ));
+    }
+    println!("No differences");
+    Ok(())
+}
+
+fn command_verify(
+    input: &Path,
+    decrypt_check: bool,
+    sample: usize,
+    key: Option<&str>,
+) -> Result<()> {
+    let archive = pf8::Pf8Archive::open_permissive(input)?;
+    let report = archive.verify()?;
+
+    if let Some(expected) = key
+        && !archive.key_matches(expected)
+    {
+        let actual = archive
+            .encryption_key()
+            .map(util::to_hex)
+            .unwrap_or_else(|| "no key (pf6)".to_string());
+        return Err(anyhow::anyhow!(
+            "{:?}: key mismatch, expected {} but the index derives {}",
+            input,
+            expected.trim(),
+            actual
+        ));
+    }
+
+    for problem in &report.problems {
+        match &problem.kind {
+            pf8::ProblemKind::Overlaps(other) => {
+                println!("Overlap: {} and {}", other, problem.name)
+            }
+            pf8::ProblemKind::OutOfBounds => println!(
+                "Out of bounds: {} ({} bytes at offset {:#x})",
+                problem.name, problem.size, problem.offset
+            ),
+            pf8::ProblemKind::OverlapsIndex => println!(
+                "Inside index: {} ({} bytes at offset {:#x})",
+                problem.name, problem.size, problem.offset
+            ),
+        }
+    }
+    if report.declared_count as usize != report.entries {
+        println!(
+            "Header declares {} entries, index holds {}",
+            report.declared_count, report.entries
+        );
+    }
+    if !report.key_matches {
+        println!("Key does not match the index on disk");
+    }
+    let shared = pf8::check_layout(&archive).shared_entries;
+    if shared > 0 {
+        println!("{} entries share data via dedup", shared);
+    }
+
+    println!("Files OK: {}/{}", report.entries_ok(), report.entries);
+    if let Some(offset) = report.first_bad_offset() {
+        println!("First bad offset: {:#x}", offset);
+    }
+    println!("Gaps: {} ({} bytes)", report.gaps, report.gap_bytes);
+    if !report.is_ok() {
+        return Err(anyhow::anyhow!(
+            "{:?} is corrupted: {} problems found",
+            input,
+            report.problems.len()
+        ));
+    }
+
+    if decrypt_check {
+        let suspicious = pf8::decrypt_check(&archive, sample)?;
+        for name in &suspicious {
+            println!("Not valid UTF-8 after decryption: {}", name);
+        }
+        if !suspicious.is_empty() {
+            return Err(anyhow::anyhow!(
+                "{:?}: {} text entries do not decrypt cleanly; the index may have been altered after packing",
+                input,
+                suspicious.len()
+            ));
+        }
+    }
+
+    println!("OK: {} entries", archive.len());
+    Ok(())
+}
+
+fn command_scan(dir: &Path) -> Result<()> {
+    println!(
+        "{:<32} | {:<6} | {:>8} | {:>14} | {:>10}",
+        "archive", "format", "files", "size", "encrypted%"
+    );
+    for info in pf8::scan_dir(dir) {
+        let info = match info {
+            Ok(info) => info,
+            Err(e) => {
+                log::warn!(dir:% = dir.display(); "Skipping unreadable archive: {}", e);
+                continue;
+            }
+        };
+        let encrypted = if info.entry_count > 0 {
+            info.encrypted_count as f64 * 100.0 / info.entry_count as f64
+        } else {
+            0.0
+        };
+        let name = info
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        println!(
+            "{:<32} | {:<6} | {:>8} | {:>14} | {:>9.1}%",
+            name, info.format, info.entry_count, info.file_size, encrypted
+        );
+    }
+    Ok(())
+}
+
 fn command_unpack_paths(
     paths: &[PathBuf],
     output: Option<&Path>,
     separate: bool,
+    options: &pf8::ExtractOptions,
     quiet: bool,
+    print_key: bool,
 ) -> Result<()> {
+    // One handler for the whole batch so the summary covers every archive
+    let mut handler = pf8::AggregatingHandler::new(ProgressHandler::new());
+    let mut total_bytes = 0;
+    let filtered = !options.include.is_empty() || !options.exclude.is_empty();
+    let mut matched_any = false;
+    let mut seen_volumes = HashSet::new();
+
     for path in paths {
+        // Numbered volumes are read together with their base archive
+        if !seen_volumes.insert(path.clone()) {
+            continue;
+        }
         let output_path = determine_extract_output(path, output, separate);
+        let mut archive = open_volumes(path)?;
+        seen_volumes.extend(archive.volumes().map(Path::to_path_buf));
+
+        // Don't leave empty output directories behind for archives the
+        // patterns select nothing from
+        if filtered && archive.plan_extraction(&output_path, options)?.is_empty() {
+            log::warn!(
+                "No entries in {:?} match the include/exclude patterns",
+                path
+            );
+            continue;
+        }
+        matched_any = true;
+
         fs::create_dir_all(&output_path)?;
         if !quiet {
             info!("Extracting {:?} to {:?}", path, output_path);
         }
 
-        let mut archive = pf8::Pf8Archive::open(path)?;
+        if print_key {
+            for reader in archive.readers() {
+                print_encryption_key(reader.encryption_key());
+            }
+        }
 
         // Use handler for progress tracking and statistics
         if quiet {
             let mut handler = pf8::callbacks::NoOpHandler;
-            archive.extract_all_with_progress(&output_path, &mut handler)?;
+            archive.extract_with_options(&output_path, options, &mut handler)?;
         } else {
-            let mut handler = ProgressHandler::new();
-            archive.extract_all_with_progress(&output_path, &mut handler)?;
+            archive.extract_with_options(&output_path, options, &mut handler)?;
+
+            // Use source pfs file sizes as total size
+            for volume in archive.volumes() {
+                total_bytes += fs::metadata(volume)?.len();
+            }
+        }
+    }
+
+    if filtered && !matched_any {
+        return Err(anyhow::anyhow!(
+            "No entries match the include/exclude patterns"
+        ));
+    }
+
+    if !quiet {
+        let stats = handler.stats();
+        print_summary(stats.duration.as_secs_f64(), stats.files, total_bytes);
+    }
+    Ok(())
+}
+
+/// Prints the size breakdown of an archive
+fn command_list_stats(input: &Path) -> Result<()> {
+    let archive = pf8::Pf8Archive::open(input)?;
+    let stats = archive.statistics();
+
+    println!("Archive:      {}", input.display());
+    println!("Entries:      {}", stats.entry_count);
+    println!("Encrypted:    {}", stats.encrypted_count);
+    println!("File size:    {} bytes", stats.total_file_size);
+    println!("Index size:   {} bytes", stats.index_size);
+    println!("Data size:    {} bytes", stats.data_size);
+    println!("Logical size: {} bytes", stats.logical_size);
+    println!("Slack:        {} bytes", stats.slack_bytes);
+    Ok(())
+}
+
+/// Prints the manifest of an archive as JSON
+#[cfg(feature = "json")]
+fn command_list_json(input: &Path) -> Result<()> {
+    let archive = pf8::Pf8Archive::open(input)?;
+    println!("{}", archive.manifest().to_json()?);
+    Ok(())
+}
+
+#[cfg(not(feature = "json"))]
+fn command_list_json(_input: &Path) -> Result<()> {
+    Err(anyhow::anyhow!(
+        "JSON output requires pfs-rs to be built with the 'json' feature"
+    ))
+}
+
+/// Prints the output path and size of every entry `extract` would write
+fn command_unpack_dry_run(
+    paths: &[PathBuf],
+    output: Option<&Path>,
+    separate: bool,
+    options: &pf8::ExtractOptions,
+) -> Result<()> {
+    let mut total_files = 0;
+    let mut total_bytes = 0u64;
+
+    let mut seen_volumes = HashSet::new();
 
-            // Use source pfs file size as total size
-            let total_bytes = fs::metadata(path)?.len();
-            handler.print_summary(total_bytes);
+    for path in paths {
+        if !seen_volumes.insert(path.clone()) {
+            continue;
+        }
+        let output_path = determine_extract_output(path, output, separate);
+        let archive = open_volumes(path)?;
+        seen_volumes.extend(archive.volumes().map(Path::to_path_buf));
+        for (entry, file_path) in archive.plan_extraction(&output_path, options)? {
+            println!("{} ({} bytes)", file_path.display(), entry.size());
+            total_files += 1;
+            total_bytes += entry.size() as u64;
         }
     }
+
+    println!("Total: {} files, {} bytes", total_files, total_bytes);
     Ok(())
 }
 
+/// Prints every entry `create` would write, with its encryption, and the
+/// projected archive size
+fn command_pack_dry_run(
+    builder: &pf8::Pf8Builder,
+    output_file: &Path,
+    split_size: Option<u64>,
+) -> Result<()> {
+    let planned = builder.plan()?;
+    let mut total_bytes = 0u64;
+    let mut archive_size = 0u64;
+
+    for planned in &planned {
+        let entry = &planned.entry;
+        let encryption = if entry.is_encrypted() {
+            "encrypted"
+        } else {
+            "plain"
+        };
+        let shared = if planned.shared { ", shared" } else { "" };
+        println!(
+            "{} ({} bytes, {}{})",
+            entry.path().display(),
+            entry.size(),
+            encryption,
+            shared
+        );
+        total_bytes += entry.size_u64();
+        archive_size = archive_size.max(entry.offset_u64() + entry.size_u64());
+    }
+
+    println!("Total: {} files, {} bytes", planned.len(), total_bytes);
+    match split_size {
+        Some(volume_size) => println!(
+            "Archive: {} ({} bytes before splitting into volumes of {} bytes)",
+            output_file.display(),
+            archive_size,
+            volume_size
+        ),
+        None => println!(
+            "Archive: {} ({} bytes)",
+            output_file.display(),
+            archive_size
+        ),
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
 fn command_pack(
     input: &Path,
     output: Option<&Path>,
@@ -117,12 +787,19 @@ fn command_pack(
     overwrite: bool,
     quiet: bool,
     no_smart_detect: bool,
+    print_key: bool,
+    reproducible: bool,
+    max_file_size: Option<u64>,
+    exclude: &[String],
+    split_size: Option<u64>,
+    dry_run: bool,
 ) -> Result<()> {
     if !input.is_dir() {
         return Err(anyhow::anyhow!("Input must be a directory"));
     }
 
     let output_file = determine_pack_output(&[input.to_path_buf()], output, overwrite)?;
+    ensure_output_outside_inputs(&[input.to_path_buf()], &output_file)?;
     if !quiet {
         info!("Creating archive {:?} from {:?}", output_file, input);
     }
@@ -137,6 +814,15 @@ fn command_pack(
     }
 
     let mut builder = pf8::Pf8Builder::new();
+    if reproducible {
+        builder.reproducible();
+    }
+    if let Some(bytes) = max_file_size {
+        builder.max_file_size(bytes);
+    }
+    for pattern in exclude {
+        builder.exclude(pattern)?;
+    }
 
     if should_preserve_dir {
         // Pack directory itself (e.g., 'root/a' -> 'a/...')
@@ -149,23 +835,100 @@ fn command_pack(
         builder.add_dir(input)?;
     }
 
+    write_archive(
+        &builder,
+        &output_file,
+        quiet,
+        print_key,
+        split_size,
+        dry_run,
+    )
+}
+
+/// Writes the builder's archive to `output_file`, reporting progress unless quiet
+///
+/// With `split_size`, the archive is written as volumes starting at
+/// `output_file` instead. With `dry_run`, the planned entries are printed
+/// and nothing is written.
+fn write_archive(
+    builder: &pf8::Pf8Builder,
+    output_file: &Path,
+    quiet: bool,
+    print_key: bool,
+    split_size: Option<u64>,
+    dry_run: bool,
+) -> Result<()> {
+    if dry_run {
+        return command_pack_dry_run(builder, output_file, split_size);
+    }
+
+    if let Some(volume_size) = split_size {
+        for volume in builder.write_split(output_file, volume_size)? {
+            if !quiet {
+                info!(
+                    "Wrote volume {:?} ({:.2} MB)",
+                    volume,
+                    fs::metadata(&volume)?.len() as f64 / 1024.0 / 1024.0
+                );
+            }
+            if print_key {
+                print_encryption_key(pf8::Pf8Reader::open(&volume)?.encryption_key());
+            }
+        }
+        return Ok(());
+    }
+
+    let mut writer = pf8::Pf8Writer::create(output_file)?;
+
     if quiet {
-        builder.write_to_file(&output_file)?;
+        builder.write_to_writer(&mut writer)?;
     } else {
         let mut handler = ProgressHandler::new();
-        builder.write_to_file_with_progress(&output_file, &mut handler)?;
+        builder.write_to_writer_with_progress(&mut writer, &mut handler)?;
 
         // Get archive file size
-        let total_bytes = fs::metadata(&output_file)?.len();
+        let total_bytes = fs::metadata(output_file)?.len();
         handler.print_summary(total_bytes);
     }
 
+    if print_key {
+        print_encryption_key(writer.encryption_key());
+    }
+
     Ok(())
 }
+
+/// Opens an archive with its numbered volumes for extraction
+///
+/// With the `encoding` feature, names are decoded like
+/// [`pf8::Pf8Archive::open`] does, falling back to Shift-JIS.
+fn open_volumes(path: &Path) -> pf8::Result<pf8::MultiVolumeReader> {
+    let options = pf8::ReaderOptions {
+        #[cfg(feature = "encoding")]
+        name_encoding: pf8::NameEncoding::Auto,
+        ..Default::default()
+    };
+    pf8::MultiVolumeReader::open_with(path, options)
+}
+
+/// Prints the archive encryption key as hex to stderr
+fn print_encryption_key(key: Option<&[u8]>) {
+    match key {
+        Some(key) => eprintln!("{}", util::to_hex(key)),
+        None => eprintln!("no key (pf6)"),
+    }
+}
+
 /// Progress handler that collects statistics and prints progress
 struct ProgressHandler {
     start_time: Instant,
     total_files: usize,
+    /// Index of the current entry within the running operation
+    current_file: usize,
+    /// Files and bytes of the running operation, once announced
+    planned: Option<(usize, u64)>,
+    /// Bytes processed by the running operation
+    processed_bytes: u64,
 }
 
 impl ProgressHandler {
@@ -173,52 +936,113 @@ impl ProgressHandler {
         Self {
             start_time: Instant::now(),
             total_files: 0,
+            current_file: 0,
+            planned: None,
+            processed_bytes: 0,
         }
     }
 
     fn print_summary(&self, total_bytes: u64) {
-        let elapsed = self.start_time.elapsed();
-        let elapsed_secs = elapsed.as_secs_f64();
-        let speed = if elapsed_secs > 0.0 {
-            total_bytes as f64 / elapsed_secs / 1024.0 / 1024.0
-        } else {
-            0.0
-        };
-
-        info!(
-            "Done: Time: {:.2}s, Files: {}, Size: {:.2} MB, Speed: {:.2} MB/s",
-            elapsed_secs,
-            self.total_files,
-            total_bytes as f64 / 1024.0 / 1024.0,
-            speed
-        );
+        let elapsed_secs = self.start_time.elapsed().as_secs_f64();
+        print_summary(elapsed_secs, self.total_files, total_bytes);
     }
 }
 
+/// Logs the final time, file count, size and speed of an operation
+fn print_summary(elapsed_secs: f64, files: usize, total_bytes: u64) {
+    let speed = if elapsed_secs > 0.0 {
+        total_bytes as f64 / elapsed_secs / 1024.0 / 1024.0
+    } else {
+        0.0
+    };
+
+    info!(
+        "Done: Time: {:.2}s, Files: {}, Size: {:.2} MB, Speed: {:.2} MB/s",
+        elapsed_secs,
+        files,
+        total_bytes as f64 / 1024.0 / 1024.0,
+        speed
+    );
+}
+
 impl ArchiveHandler for ProgressHandler {
+    fn on_started(&mut self, _op_type: pf8::OperationType) -> ControlAction {
+        self.current_file = 0;
+        self.planned = None;
+        self.processed_bytes = 0;
+        ControlAction::Continue
+    }
+
     fn on_entry_started(&mut self, name: &str) -> ControlAction {
         self.total_files += 1;
-        info!("Processing: {}", name);
+        self.current_file += 1;
+        match self.planned {
+            Some((files, bytes)) => {
+                let percent = if bytes > 0 {
+                    self.processed_bytes as f64 / bytes as f64 * 100.0
+                } else {
+                    0.0
+                };
+                info!(
+                    entry = name;
+                    "Processing [{}/{}] {:.0}%: {}",
+                    self.current_file, files, percent, name
+                );
+            }
+            None => info!(entry = name; "Processing: {}", name),
+        }
+        ControlAction::Continue
+    }
+
+    fn on_progress(&mut self, info: &pf8::ProgressInfo) -> ControlAction {
+        if info.phase == pf8::OperationPhase::Writing
+            && let (Some(files), Some(bytes)) = (info.total_files, info.total_bytes)
+        {
+            self.planned = Some((files, bytes));
+            self.processed_bytes = info.processed_bytes;
+        }
+        ControlAction::Continue
+    }
+
+    fn on_warning(&mut self, message: &str) -> ControlAction {
+        log::warn!("{}", message);
         ControlAction::Continue
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn command_pack_multiple_inputs_with_flags(
     inpath_dirs: &[(PathBuf, bool)], // (path, preserve_dir_name)
     inpath_files: &[PathBuf],
     output: Option<&Path>,
     overwrite: bool,
     quiet: bool,
+    print_key: bool,
+    reproducible: bool,
+    max_file_size: Option<u64>,
+    exclude: &[String],
+    split_size: Option<u64>,
+    dry_run: bool,
 ) -> Result<()> {
     // Combine all inputs for output determination
     let mut all_inputs: Vec<PathBuf> = inpath_dirs.iter().map(|(p, _)| p.clone()).collect();
     all_inputs.extend(inpath_files.iter().cloned());
 
     let output_file = determine_pack_output(&all_inputs, output, overwrite)?;
+    ensure_output_outside_inputs(&all_inputs, &output_file)?;
     info!("Creating archive {:?}", output_file);
 
     // Use new pf8 library API with builder
     let mut builder = pf8::Pf8Builder::new();
+    if reproducible {
+        builder.reproducible();
+    }
+    if let Some(bytes) = max_file_size {
+        builder.max_file_size(bytes);
+    }
+    for pattern in exclude {
+        builder.exclude(pattern)?;
+    }
 
     // Add directories according to their flags
     for (dir, preserve_dir_name) in inpath_dirs {
@@ -239,34 +1063,34 @@ fn command_pack_multiple_inputs_with_flags(
         builder.add_file(file)?;
     }
 
-    if quiet {
-        builder.write_to_file(&output_file)?;
-    } else {
-        let mut handler = ProgressHandler::new();
-        builder.write_to_file_with_progress(&output_file, &mut handler)?;
-
-        // Get archive file size
-        let total_bytes = fs::metadata(&output_file)?.len();
-        handler.print_summary(total_bytes);
-    }
-
-    Ok(())
+    write_archive(
+        &builder,
+        &output_file,
+        quiet,
+        print_key,
+        split_size,
+        dry_run,
+    )
 }
 
 fn main() {
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
-        .format_timestamp(None)
-        .init();
+    let cli = Args::parse();
 
-    if let Err(e) = run() {
+    let mut logger =
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"));
+    match cli.log_format {
+        LogFormat::Text => logger.format_timestamp(None),
+        LogFormat::Json => logger.format(|buf, record| writeln!(buf, "{}", json_log_line(record))),
+    };
+    logger.init();
+
+    if let Err(e) = run(cli) {
         error!("Fatal error: {e}");
         std::process::exit(1);
     }
 }
 
-fn run() -> Result<()> {
-    let cli = Args::parse();
-
+fn run(cli: Args) -> Result<()> {
     // Change directory if specified
     if let Some(dir) = &cli.directory {
         std::env::set_current_dir(dir)
@@ -277,12 +1101,27 @@ fn run() -> Result<()> {
     let overwrite = cli.overwrite;
     let quiet = cli.quiet;
     let verbose = cli.verbose;
+    let print_key = cli.print_key;
+    let reproducible = cli.reproducible;
+    let dry_run = cli.dry_run;
 
     // Set log level based on verbose/quiet flags
     if verbose && !quiet {
         log::set_max_level(log::LevelFilter::Debug);
     }
 
+    // Only create and extract know how to describe their work up front
+    if dry_run
+        && !matches!(
+            cli.command,
+            None | Some(Commands::Extract { .. }) | Some(Commands::Create { .. })
+        )
+    {
+        return Err(anyhow::anyhow!(
+            "--dry-run is only supported by create and extract"
+        ));
+    }
+
     match &cli.command {
         Some(command) => match command {
             Commands::Extract {
@@ -290,17 +1129,37 @@ fn run() -> Result<()> {
                 output,
                 separate,
                 strip_components,
+                include,
+                exclude,
+                name_style,
             } => {
                 let files = util::glob_expand(input)?;
-                if let Some(_strips) = strip_components {
-                    log::warn!("--strip-components is not yet implemented");
+                let options = pf8::ExtractOptions {
+                    include: include.clone(),
+                    exclude: exclude.clone(),
+                    strip_components: strip_components.unwrap_or(0),
+                    name_style: (*name_style).into(),
+                    ..Default::default()
+                };
+                if dry_run {
+                    return command_unpack_dry_run(&files, output.as_deref(), *separate, &options);
                 }
-                command_unpack_paths(&files, output.as_deref(), *separate, quiet)?;
+                command_unpack_paths(
+                    &files,
+                    output.as_deref(),
+                    *separate,
+                    &options,
+                    quiet,
+                    print_key,
+                )?;
             }
             Commands::Create {
                 inputs,
                 output,
                 no_smart_detect,
+                max_file_size,
+                split_size,
+                exclude,
             } => {
                 // Parse inputs with rsync-style trailing slash semantics
                 // input_str, path, preserve_dir_name
@@ -316,9 +1175,8 @@ fn run() -> Result<()> {
                     }
 
                     if path.is_dir() {
-                        // Check if original string ends with / or /.
-                        let has_trailing_slash =
-                            input_str.ends_with('/') || input_str.ends_with("/.");
+                        // Check if original string ends with / or /. (or \ on Windows)
+                        let has_trailing_slash = util::has_trailing_separator(input_str);
                         // trailing slash means: pack contents only (don't preserve dir name)
                         // no trailing slash means: pack dir itself (preserve dir name)
                         let preserve_dir_name = !has_trailing_slash;
@@ -344,6 +1202,12 @@ fn run() -> Result<()> {
                             overwrite,
                             quiet,
                             *no_smart_detect,
+                            print_key,
+                            reproducible,
+                            *max_file_size,
+                            exclude,
+                            *split_size,
+                            dry_run,
                         )?;
                     } else {
                         // Single file - use multiple inputs handler
@@ -353,6 +1217,12 @@ fn run() -> Result<()> {
                             output.as_deref(),
                             overwrite,
                             quiet,
+                            print_key,
+                            reproducible,
+                            *max_file_size,
+                            exclude,
+                            *split_size,
+                            dry_run,
                         )?;
                     }
                 } else {
@@ -374,10 +1244,84 @@ fn run() -> Result<()> {
                         output.as_deref(),
                         overwrite,
                         quiet,
+                        print_key,
+                        reproducible,
+                        *max_file_size,
+                        exclude,
+                        *split_size,
+                        dry_run,
                     )?;
                 }
             }
-            Commands::List { input, long } => {
+            Commands::Info { input, top } => {
+                command_info(input, *top)?;
+            }
+            Commands::Verify {
+                input,
+                decrypt_check,
+                sample,
+                key,
+            } => {
+                command_verify(input, *decrypt_check, *sample, key.as_deref())?;
+            }
+            Commands::Scan { dir } => {
+                command_scan(dir)?;
+            }
+            Commands::Merge {
+                inputs,
+                output,
+                on_conflict,
+            } => {
+                command_merge(inputs, output, *on_conflict, overwrite, quiet)?;
+            }
+            Commands::Remove {
+                input,
+                output,
+                paths,
+            } => {
+                command_remove(input, output, paths, overwrite, quiet)?;
+            }
+            Commands::Update { input, dir, output } => {
+                command_update(input, dir, output, overwrite, quiet)?;
+            }
+            Commands::Diff { a, b, names_only } => {
+                command_diff(a, b, *names_only)?;
+            }
+            Commands::Debug { command } => match command {
+                DebugCommands::Dump {
+                    archive,
+                    entry,
+                    raw,
+                    len,
+                } => {
+                    command_debug_dump(archive, entry, *raw, *len)?;
+                }
+            },
+            Commands::List {
+                input,
+                long,
+                format,
+                stats,
+            } => {
+                if print_key {
+                    let archive = pf8::Pf8Archive::open(input)?;
+                    print_encryption_key(archive.encryption_key());
+                }
+
+                if *stats {
+                    return command_list_stats(input);
+                }
+
+                match format {
+                    ListFormat::Json => return command_list_json(input),
+                    ListFormat::Csv => {
+                        let archive = pf8::Pf8Archive::open(input)?;
+                        print!("{}", archive.manifest().to_csv());
+                        return Ok(());
+                    }
+                    ListFormat::Text => {}
+                }
+
                 #[cfg(feature = "display")]
                 {
                     if *long {
@@ -415,7 +1359,22 @@ fn run() -> Result<()> {
                         match result {
                             util::InputType::PfsFiles(pfs_files) => {
                                 // Extract operation - use auto-detect
-                                command_unpack_paths(&pfs_files, None, true, quiet)?;
+                                if dry_run {
+                                    return command_unpack_dry_run(
+                                        &pfs_files,
+                                        None,
+                                        true,
+                                        &pf8::ExtractOptions::default(),
+                                    );
+                                }
+                                command_unpack_paths(
+                                    &pfs_files,
+                                    None,
+                                    true,
+                                    &pf8::ExtractOptions::default(),
+                                    quiet,
+                                    print_key,
+                                )?;
                             }
                             util::InputType::PackFiles { dirs, files } => {
                                 // Pack operation - use auto-detect
@@ -449,6 +1408,12 @@ fn run() -> Result<()> {
                                     None,
                                     overwrite,
                                     quiet,
+                                    print_key,
+                                    reproducible,
+                                    None,
+                                    &[],
+                                    None,
+                                    dry_run,
                                 )?;
                             }
                         }
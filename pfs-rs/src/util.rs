@@ -78,6 +78,83 @@ pub fn try_get_next_nonexist_pfs(dir: &Path, base: &str) -> Result<PathBuf> {
     }
 }
 
+/// Checks if an input ends with a path separator (`dir/` or `dir/.`)
+///
+/// Windows `\` is accepted as well, so `game\` behaves like `game/`.
+pub fn has_trailing_separator(input: &str) -> bool {
+    ends_with_separator(input, cfg!(windows))
+}
+
+fn ends_with_separator(input: &str, backslash_is_separator: bool) -> bool {
+    let separators: &[char] = if backslash_is_separator {
+        &['/', '\\']
+    } else {
+        &['/']
+    };
+    let input = input.strip_suffix('.').unwrap_or(input);
+    input.ends_with(separators)
+}
+
+/// Parses a byte size such as `4096`, `500M` or `2G`
+///
+/// Suffixes `K`, `M`, `G` and `T` are binary multiples (`2G` is 2 GiB), may
+/// be lowercase and may be followed by `B` or `iB`.
+pub fn parse_size(input: &str) -> std::result::Result<u64, String> {
+    let trimmed = input.trim();
+    let upper = trimmed.to_ascii_uppercase();
+    let number = upper
+        .strip_suffix("IB")
+        .or_else(|| upper.strip_suffix('B'))
+        .unwrap_or(&upper);
+    let (digits, shift) = match number.char_indices().last() {
+        Some((i, 'K')) => (&number[..i], 10),
+        Some((i, 'M')) => (&number[..i], 20),
+        Some((i, 'G')) => (&number[..i], 30),
+        Some((i, 'T')) => (&number[..i], 40),
+        _ => (number, 0),
+    };
+    let value: u64 = digits
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid size: {input:?}"))?;
+    value
+        .checked_mul(1u64 << shift)
+        .filter(|&bytes| bytes > 0)
+        .ok_or_else(|| format!("size out of range: {input:?}"))
+}
+
+/// Formats bytes as a lowercase hex string
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Formats bytes as a hex+ASCII dump, 16 bytes per line
+///
+/// Each line holds the offset, the hex bytes and their printable ASCII
+/// characters, with `.` standing in for anything else.
+pub fn hex_dump(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for (line, chunk) in bytes.chunks(16).enumerate() {
+        let hex = chunk
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let ascii: String = chunk
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        out.push_str(&format!("{:08x}  {hex:<47}  |{ascii}|\n", line * 16));
+    }
+    out
+}
+
 /// 输入类型枚举
 #[derive(Debug, Clone)]
 pub enum InputType {
@@ -171,6 +248,20 @@ mod tests {
         Ok(temp_dir)
     }
 
+    #[test]
+    fn test_hex_dump() {
+        let dump = hex_dump(b"hello, world!\n\x00\xffmore");
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            "00000000  68 65 6c 6c 6f 2c 20 77 6f 72 6c 64 21 0a 00 ff  |hello, world!...|"
+        );
+        assert!(lines[1].starts_with("00000010  6d 6f 72 65 "));
+        assert!(lines[1].ends_with("|more|"));
+        assert!(hex_dump(&[]).is_empty());
+    }
+
     #[test]
     fn test_is_file_pf8_from_filename() {
         assert!(is_file_pf8_from_filename(Path::new("game.pfs")));
@@ -267,6 +358,36 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_trailing_separator() {
+        assert!(ends_with_separator("game/", false));
+        assert!(ends_with_separator("game/.", false));
+        assert!(!ends_with_separator("game", false));
+        assert!(!ends_with_separator("game.", false));
+
+        // Windows 风格的反斜杠
+        assert!(ends_with_separator("game\\", true));
+        assert!(ends_with_separator("game\\.", true));
+        assert!(ends_with_separator("C:\\data\\game\\", true));
+        assert!(!ends_with_separator("game\\", false));
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert_eq!(parse_size("500M"), Ok(500 << 20));
+        assert_eq!(parse_size("2G"), Ok(2 << 30));
+        assert_eq!(parse_size("2gib"), Ok(2 << 30));
+        assert_eq!(parse_size("64KB"), Ok(64 << 10));
+
+        // 非法输入与零
+        assert!(parse_size("").is_err());
+        assert!(parse_size("G").is_err());
+        assert!(parse_size("1.5G").is_err());
+        assert!(parse_size("0").is_err());
+        assert!(parse_size("99999999999T").is_err());
+    }
+
     #[test]
     fn test_process_cli_inputs_empty_error() {
         let result = process_cli_inputs(vec![]);
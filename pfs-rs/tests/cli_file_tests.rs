@@ -483,4 +483,1340 @@ mod pack_unpack_integration_tests {
 
         Ok(())
     }
+
+    #[test]
+    #[ignore = "Ignored by default because it involves filesystem and process operations"]
+    fn test_print_key_flag() -> anyhow::Result<()> {
+        let temp = assert_fs::TempDir::new()?;
+
+        let source = temp.child("source");
+        source.create_dir_all()?;
+        source.child("file1.txt").write_str("content")?;
+
+        let archive = temp.child("test.pfs");
+
+        cargo_bin_cmd!("pfs-rs")
+            .arg("c")
+            .arg(source.path())
+            .arg("-o")
+            .arg(archive.path())
+            .arg("-q")
+            .assert()
+            .success();
+
+        // 执行列表命令并打印密钥:
+        // pfs-rs l test.pfs --print-key
+        // 标准错误输出应为 40 位十六进制 SHA1 密钥
+        let output = cargo_bin_cmd!("pfs-rs")
+            .arg("l")
+            .arg(archive.path())
+            .arg("--print-key")
+            .output()?;
+        assert!(output.status.success());
+
+        let stderr = String::from_utf8(output.stderr)?;
+        let key = stderr.trim();
+        assert_eq!(key.len(), 40);
+        assert!(key.chars().all(|c| c.is_ascii_hexdigit()));
+
+        // --show-key 是 --print-key 的别名
+        // pfs-rs list test.pfs --show-key
+        let output = cargo_bin_cmd!("pfs-rs")
+            .arg("list")
+            .arg(archive.path())
+            .arg("--show-key")
+            .output()?;
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8(output.stderr)?.trim(), key);
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "Ignored by default because it involves filesystem and process operations"]
+    fn test_merge_overwrite_later_wins() -> anyhow::Result<()> {
+        let temp = assert_fs::TempDir::new()?;
+
+        // 创建两个源目录，其中 shared.txt 路径重复:
+        // first/  -> only_first.txt, shared.txt ("old")
+        // second/ -> only_second.txt, shared.txt ("new")
+        let first = temp.child("first");
+        first.create_dir_all()?;
+        first.child("only_first.txt").write_str("first")?;
+        first.child("shared.txt").write_str("old")?;
+
+        let second = temp.child("second");
+        second.create_dir_all()?;
+        second.child("only_second.txt").write_str("second")?;
+        second.child("shared.txt").write_str("new")?;
+
+        let first_pfs = temp.child("first.pfs");
+        let second_pfs = temp.child("second.pfs");
+        for (dir, archive) in [(&first, &first_pfs), (&second, &second_pfs)] {
+            cargo_bin_cmd!("pfs-rs")
+                .arg("c")
+                .arg(format!("{}/", dir.path().display()))
+                .arg("-o")
+                .arg(archive.path())
+                .arg("-q")
+                .assert()
+                .success();
+        }
+
+        // 默认策略遇到重复路径时报错
+        let merged = temp.child("merged.pfs");
+        cargo_bin_cmd!("pfs-rs")
+            .arg("merge")
+            .arg(first_pfs.path())
+            .arg(second_pfs.path())
+            .arg("-o")
+            .arg(merged.path())
+            .assert()
+            .failure();
+
+        // 执行合并命令:
+        // pfs-rs merge first.pfs second.pfs -o merged.pfs --on-conflict overwrite
+        cargo_bin_cmd!("pfs-rs")
+            .arg("merge")
+            .arg(first_pfs.path())
+            .arg(second_pfs.path())
+            .arg("-o")
+            .arg(merged.path())
+            .arg("--on-conflict")
+            .arg("overwrite")
+            .arg("-f")
+            .assert()
+            .success();
+
+        // 解包合并后的归档并验证后者覆盖前者
+        let output = temp.child("output");
+        cargo_bin_cmd!("pfs-rs")
+            .arg("x")
+            .arg(merged.path())
+            .arg(output.path())
+            .arg("-q")
+            .assert()
+            .success();
+
+        output.child("only_first.txt").assert("first");
+        output.child("only_second.txt").assert("second");
+        output.child("shared.txt").assert("new");
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "Ignored by default because it involves filesystem and process operations"]
+    fn test_scan_lists_archive_row() -> anyhow::Result<()> {
+        let temp = assert_fs::TempDir::new()?;
+
+        // 创建源目录: 一个加密文件和一个不加密的 mp4 文件
+        let source = temp.child("source");
+        source.create_dir_all()?;
+        source.child("script.txt").write_str("script")?;
+        source.child("op.mp4").write_str("movie")?;
+
+        // 扫描目录中放入一个归档和一个普通文件
+        let archives = temp.child("archives");
+        archives.create_dir_all()?;
+        archives.child("notes.txt").write_str("not an archive")?;
+        let archive = archives.child("game.pfs");
+        cargo_bin_cmd!("pfs-rs")
+            .arg("c")
+            .arg(format!("{}/", source.path().display()))
+            .arg("-o")
+            .arg(archive.path())
+            .arg("-q")
+            .assert()
+            .success();
+
+        // 执行扫描命令:
+        // pfs-rs scan archives
+        let row = predicate::str::is_match(r"game\.pfs\s+\| PF8\s+\|\s+2 \|\s+\d+ \|\s+50\.0%")?;
+        cargo_bin_cmd!("pfs-rs")
+            .arg("scan")
+            .arg(archives.path())
+            .assert()
+            .success()
+            .stdout(row)
+            .stdout(predicate::str::contains("encrypted%"))
+            .stdout(predicate::str::contains("notes.txt").not());
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "Ignored by default because it involves filesystem and process operations"]
+    fn test_log_format_json_warning() -> anyhow::Result<()> {
+        let temp = assert_fs::TempDir::new()?;
+
+        // 只有 magic 没有索引的损坏归档，扫描时会产生警告
+        let archives = temp.child("archives");
+        archives.create_dir_all()?;
+        archives.child("broken.pfs").write_binary(b"pf8")?;
+
+        // 执行扫描命令:
+        // pfs-rs scan archives --log-format json
+        let output = cargo_bin_cmd!("pfs-rs")
+            .arg("scan")
+            .arg(archives.path())
+            .arg("--log-format")
+            .arg("json")
+            .output()?;
+        assert!(output.status.success());
+
+        // 警告行是一个包含 level 字段的 JSON 对象
+        let stderr = String::from_utf8(output.stderr)?;
+        let warning = stderr
+            .lines()
+            .find(|line| line.contains("Skipping unreadable archive"))
+            .expect("missing warning line");
+        let object = predicate::str::is_match(
+            r#"^\{"level":"WARN","target":"[^"]*","message":"[^"]*"(,"[a-z]+":"[^"]*")*\}$"#,
+        )?;
+        assert!(object.eval(warning), "not a JSON object: {warning}");
+        assert!(warning.contains(r#""dir":"#));
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "Ignored by default because it involves filesystem and process operations"]
+    fn test_extract_name_style() -> anyhow::Result<()> {
+        let temp = assert_fs::TempDir::new()?;
+
+        // 创建测试目录结构:
+        // source/
+        // └── script/
+        //     └── main.txt
+        let source = temp.child("source");
+        source.create_dir_all()?;
+        source.child("script").child("main.txt").write_str("main")?;
+
+        let archive = temp.child("test.pfs");
+        cargo_bin_cmd!("pfs-rs")
+            .arg("c")
+            .arg(format!("{}/", source.path().display()))
+            .arg("-o")
+            .arg(archive.path())
+            .arg("-q")
+            .assert()
+            .success();
+
+        // 执行解包命令:
+        // pfs-rs x test.pfs forward --name-style forward
+        let forward = temp.child("forward");
+        cargo_bin_cmd!("pfs-rs")
+            .arg("x")
+            .arg(archive.path())
+            .arg(forward.path())
+            .arg("--name-style")
+            .arg("forward")
+            .arg("-q")
+            .assert()
+            .success();
+
+        // 验证创建了嵌套目录
+        forward.child("script").assert(predicate::path::is_dir());
+        forward.child("script").child("main.txt").assert("main");
+
+        // backslash 风格在 Unix 上展开为单个文件名
+        #[cfg(unix)]
+        {
+            let flat = temp.child("flat");
+            cargo_bin_cmd!("pfs-rs")
+                .arg("x")
+                .arg(archive.path())
+                .arg(flat.path())
+                .arg("--name-style")
+                .arg("backslash")
+                .arg("-q")
+                .assert()
+                .success();
+
+            flat.child("script\\main.txt").assert("main");
+            flat.child("script").assert(predicate::path::missing());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "Ignored by default because it involves filesystem and process operations"]
+    fn test_dedup_reported_by_verify_and_info() -> anyhow::Result<()> {
+        use pf8::testing::{Corruption, build_archive, corrupt_index};
+
+        let temp = assert_fs::TempDir::new()?;
+
+        // 构造去重归档: b.txt 与 a.txt 指向同一段数据
+        let mut data = build_archive(&[("a.txt", b"shared"), ("b.txt", b"shared")]);
+        let offset = pf8::Pf8Reader::from_bytes(data.clone())?
+            .get_entry("a.txt")
+            .unwrap()
+            .offset();
+        corrupt_index(&mut data, Corruption::Offset { entry: 1, offset });
+        let archive = temp.child("dedup.pfs");
+        archive.write_binary(&data)?;
+
+        // 执行校验命令:
+        // pfs-rs verify dedup.pfs
+        cargo_bin_cmd!("pfs-rs")
+            .arg("verify")
+            .arg(archive.path())
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("2 entries share data via dedup"));
+
+        // 执行信息命令:
+        // pfs-rs info dedup.pfs
+        cargo_bin_cmd!("pfs-rs")
+            .arg("info")
+            .arg(archive.path())
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("2 entries share data via dedup"));
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "Ignored by default because it involves filesystem and process operations"]
+    fn test_verify_decrypt_check_flags_altered_index() -> anyhow::Result<()> {
+        use pf8::testing::{Corruption, corrupt_index};
+
+        let temp = assert_fs::TempDir::new()?;
+
+        // 创建包含文本文件的源目录并打包
+        let source = temp.child("source");
+        source.create_dir_all()?;
+        source
+            .child("script.txt")
+            .write_str(&"The quick brown fox jumps over the lazy dog.\n".repeat(4))?;
+
+        let archive = temp.child("test.pfs");
+        cargo_bin_cmd!("pfs-rs")
+            .arg("c")
+            .arg(format!("{}/", source.path().display()))
+            .arg("-o")
+            .arg(archive.path())
+            .arg("-q")
+            .assert()
+            .success();
+
+        // 未修改的归档通过解密检查
+        cargo_bin_cmd!("pfs-rs")
+            .arg("verify")
+            .arg(archive.path())
+            .arg("--decrypt-check")
+            .assert()
+            .success();
+
+        // 打包后修改索引中的保留字节，密钥随之改变但数据未重新加密
+        let mut data = std::fs::read(archive.path())?;
+        corrupt_index(&mut data, Corruption::Reserved { entry: 0, value: 1 });
+        archive.write_binary(&data)?;
+
+        // 执行校验命令:
+        // pfs-rs verify test.pfs --decrypt-check
+        cargo_bin_cmd!("pfs-rs")
+            .arg("verify")
+            .arg(archive.path())
+            .arg("--decrypt-check")
+            .assert()
+            .failure()
+            .stdout(predicate::str::contains(
+                "Not valid UTF-8 after decryption: script.txt",
+            ));
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "Ignored by default because it involves filesystem and process operations"]
+    fn test_verify_key() -> anyhow::Result<()> {
+        let temp = assert_fs::TempDir::new()?;
+
+        let source = temp.child("source");
+        source.create_dir_all()?;
+        source.child("file.txt").write_str("content")?;
+
+        let archive = temp.child("test.pfs");
+        cargo_bin_cmd!("pfs-rs")
+            .arg("c")
+            .arg(format!("{}/", source.path().display()))
+            .arg("-o")
+            .arg(archive.path())
+            .arg("-q")
+            .assert()
+            .success();
+
+        let key: String = pf8::Pf8Reader::open(archive.path())?
+            .encryption_key()
+            .unwrap()
+            .iter()
+            .map(|b| format!("{b:02X}"))
+            .collect();
+
+        // 执行校验命令，密钥匹配 (大小写不敏感):
+        // pfs-rs verify test.pfs --key <HEX>
+        cargo_bin_cmd!("pfs-rs")
+            .arg("verify")
+            .arg(archive.path())
+            .arg("--key")
+            .arg(&key)
+            .assert()
+            .success();
+
+        // 密钥不匹配时失败
+        let wrong = format!("{}00", &key[..key.len() - 2]);
+        let wrong = if wrong == key { "f".repeat(40) } else { wrong };
+        cargo_bin_cmd!("pfs-rs")
+            .arg("verify")
+            .arg(archive.path())
+            .arg("--key")
+            .arg(&wrong)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("key mismatch"));
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "Ignored by default because it involves filesystem and process operations"]
+    fn test_debug_dump() -> anyhow::Result<()> {
+        let temp = assert_fs::TempDir::new()?;
+
+        let source = temp.child("source");
+        source.create_dir_all()?;
+        source.child("script.txt").write_str("hello dump")?;
+
+        let archive = temp.child("test.pfs");
+        cargo_bin_cmd!("pfs-rs")
+            .arg("c")
+            .arg(format!("{}/", source.path().display()))
+            .arg("-o")
+            .arg(archive.path())
+            .arg("-q")
+            .assert()
+            .success();
+
+        // 执行调试转储命令，默认输出解密后的内容:
+        // pfs-rs debug dump test.pfs script.txt
+        cargo_bin_cmd!("pfs-rs")
+            .arg("debug")
+            .arg("dump")
+            .arg(archive.path())
+            .arg("script.txt")
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("|hello dump|"))
+            .stdout(predicate::str::contains("68 65 6c 6c 6f"));
+
+        // --raw 输出加密的原始字节，--len 限制长度
+        cargo_bin_cmd!("pfs-rs")
+            .arg("debug")
+            .arg("dump")
+            .arg(archive.path())
+            .arg("script.txt")
+            .arg("--raw")
+            .arg("--len")
+            .arg("4")
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("hello").not())
+            .stdout(predicate::str::starts_with("00000000  "));
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "Ignored by default because it involves filesystem and process operations"]
+    fn test_create_max_file_size() -> anyhow::Result<()> {
+        let temp = assert_fs::TempDir::new()?;
+
+        let source = temp.child("source");
+        source.create_dir_all()?;
+        source.child("small.txt").write_str("small")?;
+        source.child("large.bin").write_binary(&[0u8; 4096])?;
+
+        // 执行打包命令，跳过超过大小限制的文件:
+        // pfs-rs c source/ -o lite.pfs --max-file-size 1024
+        let archive = temp.child("lite.pfs");
+        cargo_bin_cmd!("pfs-rs")
+            .arg("c")
+            .arg(format!("{}/", source.path().display()))
+            .arg("-o")
+            .arg(archive.path())
+            .arg("--max-file-size")
+            .arg("1024")
+            .assert()
+            .success()
+            .stderr(predicate::str::contains("large.bin"));
+
+        let reader = pf8::Pf8Reader::open(archive.path())?;
+        assert!(reader.contains("small.txt"));
+        assert!(!reader.contains("large.bin"));
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "Ignored by default because it involves filesystem and process operations"]
+    fn test_extract_dry_run() -> anyhow::Result<()> {
+        let temp = assert_fs::TempDir::new()?;
+
+        // 创建测试目录结构:
+        // source/
+        // └── root/
+        //     ├── a.txt
+        //     └── script/
+        //         └── b.txt
+        let source = temp.child("source");
+        source.create_dir_all()?;
+        source.child("root").child("a.txt").write_str("aaa")?;
+        source
+            .child("root")
+            .child("script")
+            .child("b.txt")
+            .write_str("bb")?;
+
+        let archive = temp.child("test.pfs");
+        cargo_bin_cmd!("pfs-rs")
+            .arg("c")
+            .arg(format!("{}/", source.path().display()))
+            .arg("-o")
+            .arg(archive.path())
+            .arg("-q")
+            .assert()
+            .success();
+
+        // 执行预演解包命令:
+        // pfs-rs x test.pfs output --strip-components 1 --dry-run
+        let output = temp.child("output");
+        let a_path = output.path().join("a.txt");
+        let b_path = output.path().join("script").join("b.txt");
+        cargo_bin_cmd!("pfs-rs")
+            .arg("x")
+            .arg(archive.path())
+            .arg(output.path())
+            .arg("--strip-components")
+            .arg("1")
+            .arg("--dry-run")
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(format!(
+                "{} (3 bytes)",
+                a_path.display()
+            )))
+            .stdout(predicate::str::contains(format!(
+                "{} (2 bytes)",
+                b_path.display()
+            )))
+            .stdout(predicate::str::contains("Total: 2 files, 5 bytes"));
+
+        // 验证没有写入任何文件
+        output.assert(predicate::path::missing());
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "Ignored by default because it involves filesystem and process operations"]
+    fn test_pack_multiple_dirs_progress_totals() -> anyhow::Result<()> {
+        let temp = assert_fs::TempDir::new()?;
+
+        // 创建两个输入目录，共 3 个文件
+        let dir_a = temp.child("a");
+        dir_a.create_dir_all()?;
+        dir_a.child("1.txt").write_str("one")?;
+        dir_a.child("2.txt").write_str("two")?;
+        let dir_b = temp.child("b");
+        dir_b.create_dir_all()?;
+        dir_b.child("3.txt").write_str("three")?;
+
+        // 执行打包命令，进度显示合并后的总数:
+        // pfs-rs c a b -o test.pfs
+        cargo_bin_cmd!("pfs-rs")
+            .arg("c")
+            .arg(dir_a.path())
+            .arg(dir_b.path())
+            .arg("-o")
+            .arg(temp.child("test.pfs").path())
+            .assert()
+            .success()
+            .stderr(predicate::str::contains("Processing [1/3] 0%"))
+            .stderr(predicate::str::contains("Processing [3/3]"));
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "Ignored by default because it involves filesystem and process operations"]
+    fn test_verify_reports_integrity_summary() -> anyhow::Result<()> {
+        use pf8::testing::{Corruption, build_archive, corrupt_index};
+
+        let temp = assert_fs::TempDir::new()?;
+        let archive = temp.child("test.pfs");
+        let mut data = build_archive(&[("a.txt", b"aaaaaaaaaa"), ("b.txt", b"bbbbbbbbbb")]);
+        archive.write_binary(&data)?;
+
+        // 完整的归档通过校验并打印摘要
+        cargo_bin_cmd!("pfs-rs")
+            .arg("verify")
+            .arg(archive.path())
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("Files OK: 2/2"))
+            .stdout(predicate::str::contains("Gaps: 0 (0 bytes)"));
+
+        // 将 b.txt 的偏移移入 a.txt 的数据中
+        let a_offset = pf8::Pf8Reader::from_bytes(data.clone())?
+            .get_entry("a.txt")
+            .unwrap()
+            .offset();
+        corrupt_index(
+            &mut data,
+            Corruption::Offset {
+                entry: 1,
+                offset: a_offset + 4,
+            },
+        );
+        archive.write_binary(&data)?;
+
+        // 执行校验命令:
+        // pfs-rs verify test.pfs
+        cargo_bin_cmd!("pfs-rs")
+            .arg("verify")
+            .arg(archive.path())
+            .assert()
+            .failure()
+            .stdout(predicate::str::contains("Overlap: a.txt and b.txt"))
+            .stdout(predicate::str::contains("Files OK: 1/2"))
+            .stdout(predicate::str::contains(format!(
+                "First bad offset: {:#x}",
+                a_offset + 4
+            )));
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "Ignored by default because it involves filesystem and process operations"]
+    fn test_extract_include_exclude() -> anyhow::Result<()> {
+        let temp = assert_fs::TempDir::new()?;
+
+        // 创建测试目录结构:
+        // source/
+        // ├── image.png
+        // └── script/
+        //     ├── main.ast
+        //     └── sub/
+        //         ├── extra.ast
+        //         └── notes.txt
+        let source = temp.child("source");
+        source.create_dir_all()?;
+        source.child("image.png").write_str("png")?;
+        let script = source.child("script");
+        script.child("main.ast").write_str("main")?;
+        script.child("sub").child("extra.ast").write_str("extra")?;
+        script.child("sub").child("notes.txt").write_str("notes")?;
+
+        let archive = temp.child("test.pfs");
+        cargo_bin_cmd!("pfs-rs")
+            .arg("c")
+            .arg(format!("{}/", source.path().display()))
+            .arg("-o")
+            .arg(archive.path())
+            .arg("-q")
+            .assert()
+            .success();
+
+        // 执行选择性解包命令:
+        // pfs-rs x test.pfs output --include 'script/**' --exclude '**/*.txt'
+        let output = temp.child("output");
+        cargo_bin_cmd!("pfs-rs")
+            .arg("x")
+            .arg(archive.path())
+            .arg(output.path())
+            .arg("--include")
+            .arg("script/**")
+            .arg("--exclude")
+            .arg("**/*.txt")
+            .arg("-q")
+            .assert()
+            .success();
+
+        output.child("script").child("main.ast").assert("main");
+        output
+            .child("script")
+            .child("sub")
+            .child("extra.ast")
+            .assert("extra");
+        output
+            .child("script")
+            .child("sub")
+            .child("notes.txt")
+            .assert(predicate::path::missing());
+        output.child("image.png").assert(predicate::path::missing());
+
+        // 没有匹配的条目时报错且不创建输出目录
+        let empty = temp.child("empty");
+        cargo_bin_cmd!("pfs-rs")
+            .arg("x")
+            .arg(archive.path())
+            .arg(empty.path())
+            .arg("--include")
+            .arg("movie/**")
+            .arg("-q")
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(
+                "No entries match the include/exclude patterns",
+            ));
+        empty.assert(predicate::path::missing());
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "Ignored by default because it involves filesystem and process operations"]
+    fn test_remove_entries() -> anyhow::Result<()> {
+        let temp = assert_fs::TempDir::new()?;
+
+        // 创建测试目录结构:
+        // source/
+        // └── script/
+        //     ├── main.txt
+        //     └── debug.txt
+        let source = temp.child("source");
+        source.create_dir_all()?;
+        source.child("script").child("main.txt").write_str("main")?;
+        source
+            .child("script")
+            .child("debug.txt")
+            .write_str("debug")?;
+
+        let game = temp.child("game.pfs");
+        cargo_bin_cmd!("pfs-rs")
+            .arg("c")
+            .arg(format!("{}/", source.path().display()))
+            .arg("-o")
+            .arg(game.path())
+            .arg("-q")
+            .assert()
+            .success();
+
+        // 不存在的路径报错且不生成输出文件
+        let trimmed = temp.child("trimmed.pfs");
+        cargo_bin_cmd!("pfs-rs")
+            .arg("remove")
+            .arg(game.path())
+            .arg("--out")
+            .arg(trimmed.path())
+            .arg("script/none.txt")
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("script/none.txt"));
+        trimmed.assert(predicate::path::missing());
+
+        // 执行删除命令:
+        // pfs-rs remove game.pfs --out trimmed.pfs script/debug.txt
+        cargo_bin_cmd!("pfs-rs")
+            .arg("remove")
+            .arg(game.path())
+            .arg("--out")
+            .arg(trimmed.path())
+            .arg("script/debug.txt")
+            .arg("-q")
+            .assert()
+            .success();
+
+        // 解包并验证被删除的文件不存在
+        let output = temp.child("output");
+        cargo_bin_cmd!("pfs-rs")
+            .arg("x")
+            .arg(trimmed.path())
+            .arg(output.path())
+            .arg("-q")
+            .assert()
+            .success();
+        output.child("script").child("main.txt").assert("main");
+        output
+            .child("script")
+            .child("debug.txt")
+            .assert(predicate::path::missing());
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "Ignored by default because it involves filesystem and process operations"]
+    fn test_update_from_dir() -> anyhow::Result<()> {
+        let temp = assert_fs::TempDir::new()?;
+
+        // 创建原始目录与补丁目录:
+        // source/ -> script/main.txt ("old"), script/other.txt
+        // patch/  -> script/main.txt ("new"), script/added.txt
+        let source = temp.child("source");
+        source.child("script").child("main.txt").write_str("old")?;
+        source
+            .child("script")
+            .child("other.txt")
+            .write_str("other")?;
+        let patch = temp.child("patch");
+        patch.child("script").child("main.txt").write_str("new")?;
+        patch
+            .child("script")
+            .child("added.txt")
+            .write_str("added")?;
+
+        let game = temp.child("game.pfs");
+        cargo_bin_cmd!("pfs-rs")
+            .arg("c")
+            .arg(format!("{}/", source.path().display()))
+            .arg("-o")
+            .arg(game.path())
+            .arg("-q")
+            .assert()
+            .success();
+
+        // 执行更新命令:
+        // pfs-rs update game.pfs patch/ -o game_patched.pfs
+        let patched = temp.child("game_patched.pfs");
+        cargo_bin_cmd!("pfs-rs")
+            .arg("update")
+            .arg(game.path())
+            .arg(format!("{}/", patch.path().display()))
+            .arg("-o")
+            .arg(patched.path())
+            .arg("-q")
+            .assert()
+            .success();
+
+        // 解包并验证替换、新增与保留的文件
+        let output = temp.child("output");
+        cargo_bin_cmd!("pfs-rs")
+            .arg("x")
+            .arg(patched.path())
+            .arg(output.path())
+            .arg("-q")
+            .assert()
+            .success();
+        output.child("script").child("main.txt").assert("new");
+        output.child("script").child("added.txt").assert("added");
+        output.child("script").child("other.txt").assert("other");
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "Ignored by default because it involves filesystem and process operations"]
+    fn test_create_split_volumes() -> anyhow::Result<()> {
+        let temp = assert_fs::TempDir::new()?;
+
+        // 创建三个 600 字节的文件，每个分卷只能容纳一个
+        let source = temp.child("source");
+        source.create_dir_all()?;
+        for name in ["a.bin", "b.bin", "c.bin"] {
+            source.child(name).write_binary(&[0x42; 600])?;
+        }
+
+        // 执行分卷打包命令:
+        // pfs-rs create source/ -o root.pfs --split-size 1K
+        let root = temp.child("root.pfs");
+        cargo_bin_cmd!("pfs-rs")
+            .arg("create")
+            .arg(format!("{}/", source.path().display()))
+            .arg("-o")
+            .arg(root.path())
+            .arg("--split-size")
+            .arg("1K")
+            .arg("-q")
+            .assert()
+            .success();
+
+        root.assert(predicate::path::exists());
+        temp.child("root.pfs.000").assert(predicate::path::exists());
+        temp.child("root.pfs.001").assert(predicate::path::exists());
+        temp.child("root.pfs.002")
+            .assert(predicate::path::missing());
+
+        // 每个分卷都能单独解包
+        let output = temp.child("output");
+        cargo_bin_cmd!("pfs-rs")
+            .arg("x")
+            .arg(temp.child("root.pfs.001").path())
+            .arg(output.path())
+            .arg("-q")
+            .assert()
+            .success();
+        output.child("c.bin").assert(predicate::path::exists());
+
+        // 非法的分卷大小
+        cargo_bin_cmd!("pfs-rs")
+            .arg("create")
+            .arg(format!("{}/", source.path().display()))
+            .arg("-o")
+            .arg(temp.child("bad.pfs").path())
+            .arg("--split-size")
+            .arg("lots")
+            .assert()
+            .failure();
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "Ignored by default because it involves filesystem and process operations"]
+    fn test_extract_multi_volume() -> anyhow::Result<()> {
+        let temp = assert_fs::TempDir::new()?;
+
+        // 分卷打包三个文件
+        let source = temp.child("source");
+        source.create_dir_all()?;
+        for (name, byte) in [("a.bin", 0x41), ("b.bin", 0x42), ("c.bin", 0x43)] {
+            source.child(name).write_binary(&[byte; 600])?;
+        }
+        let root = temp.child("root.pfs");
+        cargo_bin_cmd!("pfs-rs")
+            .arg("create")
+            .arg(format!("{}/", source.path().display()))
+            .arg("-o")
+            .arg(root.path())
+            .arg("--split-size")
+            .arg("1K")
+            .arg("-q")
+            .assert()
+            .success();
+
+        // 解包基础文件时自动读取所有分卷:
+        // pfs-rs x root.pfs output/
+        let output = temp.child("output");
+        cargo_bin_cmd!("pfs-rs")
+            .arg("x")
+            .arg(root.path())
+            .arg(output.path())
+            .arg("-q")
+            .assert()
+            .success();
+        output.child("a.bin").assert(predicate::path::exists());
+        output.child("b.bin").assert(predicate::path::exists());
+        output.child("c.bin").assert(predicate::path::exists());
+        assert_eq!(
+            std::fs::read(output.child("c.bin").path())?,
+            vec![0x43; 600]
+        );
+
+        // 通配符同时匹配基础文件和分卷时不会重复解包
+        let dry_run = cargo_bin_cmd!("pfs-rs")
+            .arg("x")
+            .arg(format!("{}*", root.path().display()))
+            .arg(temp.child("output2").path())
+            .arg("--dry-run")
+            .assert()
+            .success();
+        let stdout = String::from_utf8(dry_run.get_output().stdout.clone())?;
+        assert!(stdout.contains("Total: 3 files"), "{stdout}");
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "Ignored by default because it involves filesystem and process operations"]
+    #[cfg(feature = "json")]
+    fn test_list_json_format() -> anyhow::Result<()> {
+        let temp = assert_fs::TempDir::new()?;
+
+        // 创建带子目录的测试文件
+        let source = temp.child("source");
+        source.create_dir_all()?;
+        source.child("scripts/main.ast").write_str("main")?;
+
+        let archive = temp.child("test.pfs");
+        cargo_bin_cmd!("pfs-rs")
+            .arg("c")
+            .arg(format!("{}/", source.path().display()))
+            .arg("-o")
+            .arg(archive.path())
+            .arg("-q")
+            .assert()
+            .success();
+
+        // 执行 JSON 列表命令:
+        // pfs-rs list test.pfs --format json
+        // 输出验证: 路径使用正斜杠
+        cargo_bin_cmd!("pfs-rs")
+            .arg("list")
+            .arg(archive.path())
+            .arg("--format")
+            .arg("json")
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(r#""format": "PF8""#))
+            .stdout(predicate::str::contains(r#""path": "scripts/main.ast""#))
+            .stdout(predicate::str::contains(r#""size": 4"#));
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "Ignored by default because it involves filesystem and process operations"]
+    fn test_list_csv_format() -> anyhow::Result<()> {
+        let temp = assert_fs::TempDir::new()?;
+
+        // 创建文件名包含逗号和中文的测试文件
+        let source = temp.child("source");
+        source.create_dir_all()?;
+        source.child("a,b.txt").write_str("ab")?;
+        source.child("中文.txt").write_str("text")?;
+
+        let archive = temp.child("test.pfs");
+        cargo_bin_cmd!("pfs-rs")
+            .arg("c")
+            .arg(format!("{}/", source.path().display()))
+            .arg("-o")
+            .arg(archive.path())
+            .arg("-q")
+            .assert()
+            .success();
+
+        // 执行 CSV 列表命令:
+        // pfs-rs list test.pfs --format csv
+        let assert = cargo_bin_cmd!("pfs-rs")
+            .arg("list")
+            .arg(archive.path())
+            .arg("--format")
+            .arg("csv")
+            .assert()
+            .success();
+        let stdout = String::from_utf8(assert.get_output().stdout.clone())?;
+
+        // 输出验证: 表头、逗号路径被引号包裹、UTF-8 无 BOM、合计行
+        assert!(
+            stdout.starts_with("path,size,encrypted,offset\n"),
+            "{stdout}"
+        );
+        assert!(stdout.contains("\"a,b.txt\",2,true,"), "{stdout}");
+        assert!(stdout.contains("中文.txt,4,true,"), "{stdout}");
+        assert!(stdout.ends_with("Total,6,,\n"), "{stdout}");
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "Ignored by default because it involves filesystem and process operations"]
+    fn test_diff_command() -> anyhow::Result<()> {
+        let temp = assert_fs::TempDir::new()?;
+
+        // 创建两个版本的源目录
+        let old = temp.child("old");
+        old.child("same.txt").write_str("same")?;
+        old.child("edited.txt").write_str("aaaa")?;
+        old.child("removed.txt").write_str("gone")?;
+        let new = temp.child("new");
+        new.child("same.txt").write_str("same")?;
+        new.child("edited.txt").write_str("bbbb")?;
+        new.child("added.txt").write_str("new")?;
+
+        for (source, name) in [(&old, "a.pfs"), (&new, "b.pfs")] {
+            cargo_bin_cmd!("pfs-rs")
+                .arg("c")
+                .arg(format!("{}/", source.path().display()))
+                .arg("-o")
+                .arg(temp.child(name).path())
+                .arg("-q")
+                .assert()
+                .success();
+        }
+
+        // 执行比较命令:
+        // pfs-rs diff a.pfs b.pfs
+        // 输出验证: 按路径排序，存在差异时失败
+        let assert = cargo_bin_cmd!("pfs-rs")
+            .arg("diff")
+            .arg(temp.child("a.pfs").path())
+            .arg(temp.child("b.pfs").path())
+            .assert()
+            .failure();
+        let stdout = String::from_utf8(assert.get_output().stdout.clone())?;
+        assert_eq!(
+            stdout,
+            "+ added.txt\nM edited.txt (content differs)\n- removed.txt\n"
+        );
+
+        // 仅比较索引时不会发现同大小的内容修改
+        cargo_bin_cmd!("pfs-rs")
+            .arg("diff")
+            .arg(temp.child("a.pfs").path())
+            .arg(temp.child("b.pfs").path())
+            .arg("--names-only")
+            .assert()
+            .failure()
+            .stdout(predicate::str::contains("edited.txt").not());
+
+        // 相同的归档没有差异
+        cargo_bin_cmd!("pfs-rs")
+            .arg("diff")
+            .arg(temp.child("a.pfs").path())
+            .arg(temp.child("a.pfs").path())
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("No differences"));
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "Ignored by default because it involves filesystem and process operations"]
+    fn test_list_stats() -> anyhow::Result<()> {
+        let temp = assert_fs::TempDir::new()?;
+
+        let source = temp.child("source");
+        source.create_dir_all()?;
+        source.child("a.txt").write_str("12345")?;
+        source.child("movie.mp4").write_str("123")?;
+
+        let archive = temp.child("test.pfs");
+        cargo_bin_cmd!("pfs-rs")
+            .arg("c")
+            .arg(format!("{}/", source.path().display()))
+            .arg("-o")
+            .arg(archive.path())
+            .arg("-q")
+            .assert()
+            .success();
+
+        // 在归档末尾追加填充字节
+        let mut data = std::fs::read(archive.path())?;
+        data.extend_from_slice(&[0; 64]);
+        std::fs::write(archive.path(), &data)?;
+
+        // 执行统计命令:
+        // pfs-rs list test.pfs --stats
+        // 输出验证: 数据大小、加密条目数和末尾空闲字节
+        cargo_bin_cmd!("pfs-rs")
+            .arg("list")
+            .arg(archive.path())
+            .arg("--stats")
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(format!(
+                "File size:    {} bytes",
+                data.len()
+            )))
+            .stdout(predicate::str::contains("Data size:    8 bytes"))
+            .stdout(predicate::str::contains("Encrypted:    1"))
+            .stdout(predicate::str::contains("Slack:        64 bytes"));
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "Ignored by default because it involves filesystem and process operations"]
+    fn test_create_dry_run() -> anyhow::Result<()> {
+        let temp = assert_fs::TempDir::new()?;
+
+        // 创建测试目录结构:
+        // source/
+        // ├── a.txt
+        // └── movie.mp4
+        let source = temp.child("source");
+        source.create_dir_all()?;
+        source.child("a.txt").write_str("aaa")?;
+        source.child("movie.mp4").write_str("frames")?;
+
+        // 执行预演打包命令:
+        // pfs-rs --dry-run create source/ -o test.pfs
+        let archive = temp.child("test.pfs");
+        cargo_bin_cmd!("pfs-rs")
+            .arg("--dry-run")
+            .arg("create")
+            .arg(format!("{}/", source.path().display()))
+            .arg("-o")
+            .arg(archive.path())
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("a.txt (3 bytes, encrypted)"))
+            .stdout(predicate::str::contains("movie.mp4 (6 bytes, plain)"))
+            .stdout(predicate::str::contains("Total: 2 files, 9 bytes"))
+            .stdout(predicate::str::contains(format!(
+                "Archive: {}",
+                archive.path().display()
+            )));
+
+        // 验证没有创建压缩包
+        archive.assert(predicate::path::missing());
+
+        // 不支持预演的命令应报错
+        cargo_bin_cmd!("pfs-rs")
+            .arg("info")
+            .arg(archive.path())
+            .arg("--dry-run")
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("--dry-run"));
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "Ignored by default because it involves filesystem and process operations"]
+    fn test_create_exclude() -> anyhow::Result<()> {
+        let temp = assert_fs::TempDir::new()?;
+
+        // 创建测试目录结构:
+        // source/
+        // ├── a.txt
+        // ├── a.txt.bak
+        // ├── .git/
+        // │   └── HEAD
+        // └── script/
+        //     ├── b.txt
+        //     └── Thumbs.db
+        let source = temp.child("source");
+        source.create_dir_all()?;
+        source.child("a.txt").write_str("aaa")?;
+        source.child("a.txt.bak").write_str("old")?;
+        source.child(".git").child("HEAD").write_str("ref")?;
+        source.child("script").child("b.txt").write_str("bb")?;
+        source.child("script").child("Thumbs.db").write_str("db")?;
+
+        // 执行打包命令:
+        // pfs-rs c source/ -o test.pfs --exclude "**/*.bak" --exclude "**/Thumbs.db" --exclude ".git/**"
+        let archive = temp.child("test.pfs");
+        cargo_bin_cmd!("pfs-rs")
+            .arg("c")
+            .arg(format!("{}/", source.path().display()))
+            .arg("-o")
+            .arg(archive.path())
+            .arg("--exclude")
+            .arg("**/*.bak")
+            .arg("--exclude")
+            .arg("**/Thumbs.db")
+            .arg("--exclude")
+            .arg(".git/**")
+            .arg("-q")
+            .assert()
+            .success();
+
+        // 验证被排除的文件不在压缩包中
+        let reader = pf8::Pf8Reader::open(archive.path())?;
+        let mut paths: Vec<_> = reader.entries().map(|e| e.pf8_path().to_string()).collect();
+        paths.sort();
+        assert_eq!(paths, ["a.txt", "script\\b.txt"]);
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "Ignored by default because it involves filesystem and process operations"]
+    fn test_verify_reports_out_of_bounds_entry() -> anyhow::Result<()> {
+        use pf8::testing::{Corruption, build_archive, corrupt_index};
+
+        let temp = assert_fs::TempDir::new()?;
+        let archive = temp.child("test.pfs");
+        let mut data = build_archive(&[("a.txt", b"aaaaaaaaaa"), ("b.txt", b"bbbbbbbbbb")]);
+        let b_offset = pf8::Pf8Reader::from_bytes(data.clone())?
+            .get_entry("b.txt")
+            .unwrap()
+            .offset();
+
+        // 将 b.txt 的大小改为超出归档末尾
+        corrupt_index(
+            &mut data,
+            Corruption::Size {
+                entry: 1,
+                size: 1000,
+            },
+        );
+        archive.write_binary(&data)?;
+
+        // 执行校验命令:
+        // pfs-rs verify test.pfs
+        cargo_bin_cmd!("pfs-rs")
+            .arg("verify")
+            .arg(archive.path())
+            .assert()
+            .failure()
+            .stdout(predicate::str::contains(format!(
+                "Out of bounds: b.txt (1000 bytes at offset {:#x})",
+                b_offset
+            )))
+            .stdout(predicate::str::contains("Files OK: 1/2"))
+            .stdout(predicate::str::contains(format!(
+                "First bad offset: {:#x}",
+                b_offset
+            )));
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "Ignored by default because it involves filesystem and process operations"]
+    #[cfg(feature = "encoding")]
+    fn test_extract_shift_jis_names() -> anyhow::Result<()> {
+        let temp = assert_fs::TempDir::new()?;
+
+        // 创建文件名以 Shift-JIS 编码的归档
+        let input = temp.child("input.txt");
+        input.write_str("scenario")?;
+        let archive = temp.child("game.pfs");
+        let mut builder = pf8::Pf8Builder::new();
+        builder
+            .name_encoding(pf8::NameEncoding::ShiftJis)
+            .add_file_as(input.path(), "シナリオ/開始.txt")?;
+        builder.write_to_file(archive.path())?;
+
+        // 解包时自动识别文件名编码:
+        // pfs-rs x game.pfs output/
+        let output = temp.child("output");
+        cargo_bin_cmd!("pfs-rs")
+            .arg("x")
+            .arg(archive.path())
+            .arg(output.path())
+            .arg("-q")
+            .assert()
+            .success();
+        output
+            .child("シナリオ")
+            .child("開始.txt")
+            .assert("scenario");
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "Ignored by default because it involves filesystem and process operations"]
+    #[cfg(feature = "encoding")]
+    fn test_diff_shift_jis_names() -> anyhow::Result<()> {
+        let temp = assert_fs::TempDir::new()?;
+
+        // 创建两个文件名以 Shift-JIS 编码的归档
+        let input = temp.child("input.txt");
+        input.write_str("scenario")?;
+        for (name, entries) in [
+            ("a.pfs", &["開始.txt"][..]),
+            ("b.pfs", &["開始.txt", "終了.txt"][..]),
+        ] {
+            let mut builder = pf8::Pf8Builder::new();
+            builder.name_encoding(pf8::NameEncoding::ShiftJis);
+            for entry in entries {
+                builder.add_file_as(input.path(), entry)?;
+            }
+            builder.write_to_file(temp.child(name).path())?;
+        }
+
+        // 比较时自动识别文件名编码:
+        // pfs-rs diff a.pfs b.pfs
+        cargo_bin_cmd!("pfs-rs")
+            .arg("diff")
+            .arg(temp.child("a.pfs").path())
+            .arg(temp.child("b.pfs").path())
+            .assert()
+            .failure()
+            .stdout("+ 終了.txt\n");
+
+        Ok(())
+    }
 }